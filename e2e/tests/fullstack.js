@@ -6,7 +6,7 @@ const { cryptoWaitReady, mnemonicGenerate } = require('@polkadot/util-crypto');
 const { Process, TempDir } = require('../pm');
 const { PRuntime } = require('../pruntime');
 const { checkUntil } = require('../utils');
-const types = require('../typedefs.json');
+const { types, signedExtensions } = require('../typedefs.json');
 
 const pathNode = path.resolve('../target/release/phala-node');
 const pathRelayer = path.resolve('../target/release/phost');
@@ -40,7 +40,7 @@ describe('A full stack', function () {
 		]);
 		await processRelayer.startAndWaitForOutput(/runtime_info:InitRuntimeResp/);
 		// create polkadot api and keyring
-		api = await ApiPromise.create({ provider: new WsProvider('ws://localhost:9944'), types });
+		api = await ApiPromise.create({ provider: new WsProvider('ws://localhost:9944'), types, signedExtensions });
 		await cryptoWaitReady();
 		keyring = new Keyring({ type: 'sr25519' });
 		root = alice = keyring.addFromUri('//Alice');
@@ -142,7 +142,7 @@ describe('A full stack', function () {
 				root);
 
 			const workerInfo = await api.query.phalaModule.workerState(stash.address);
-			assert.equal(workerInfo.status.toNumber(), 0);
+			assert.isTrue(workerInfo.state.isIdle);
 		});
 
 		it('can start mining', async function () {
@@ -150,8 +150,8 @@ describe('A full stack', function () {
 				api.tx.phalaModule.startMine(),
 				controller);
 
-			const { status } = await api.query.phalaModule.workerState(stash.address);
-			assert.equal(status.toNumber(), 1);
+			const { state } = await api.query.phalaModule.workerState(stash.address);
+			assert.isTrue(state.isMining);
 		});
 
 		it('can stop mining', async function () {
@@ -159,8 +159,8 @@ describe('A full stack', function () {
 				api.tx.phalaModule.stopMine(),
 				controller);
 
-			const { status } = await api.query.phalaModule.workerState(stash.address);
-			assert.equal(status.toNumber(), 0);
+			const { state } = await api.query.phalaModule.workerState(stash.address);
+			assert.isTrue(state.isPendingStop);
 		});
 	})
 
@@ -25,9 +25,10 @@ use node_runtime::{
 	AuthorityDiscoveryConfig, BabeConfig, BalancesConfig, ContractsConfig, CouncilConfig,
 	DemocracyConfig,GrandpaConfig, ImOnlineConfig, SessionConfig, SessionKeys, StakerStatus,
 	StakingConfig, ElectionsConfig, IndicesConfig, SocietyConfig, SudoConfig, SystemConfig,
-	TechnicalCommitteeConfig, PhalaModuleConfig, wasm_binary_unwrap,
+	TechnicalCommitteeConfig, PhalaModuleConfig, WorkerBond, wasm_binary_unwrap,
 };
 use node_runtime::Block;
+use node_runtime::pallet_phala::constants::{IAS_ROOT_CA_SUBJECT, IAS_ROOT_CA_SPKI};
 use node_runtime::constants::currency::*;
 use sc_service::ChainType;
 use hex_literal::hex;
@@ -249,7 +250,8 @@ pub fn testnet_genesis(
 		pallet_balances: Some(BalancesConfig {
 			balances: endowed_accounts.iter().cloned()
 				.map(|k| (k, ENDOWMENT))
-				.chain(initial_authorities.iter().map(|x| (x.0.clone(), STASH)))
+				// The builtin workers of the authorities bond `WorkerBond` in addition
+				.chain(initial_authorities.iter().map(|x| (x.0.clone(), STASH + WorkerBond::get())))
 				.collect(),
 		}),
 		pallet_indices: Some(IndicesConfig {
@@ -266,11 +268,20 @@ pub fn testnet_genesis(
 			}).collect::<Vec<_>>(),
 		}),
 		pallet_phala: Some(PhalaModuleConfig {
+			// The builtin workers start mining at the genesis, so that the authorities are elected
 			stakers: initial_authorities.iter().map(|x| {
 				(x.0.clone(), x.1.clone(), dev_ecdsa_pubkey.clone())
 			}).collect(),
+			contract_keys: vec![],
 			// Now we have 4 contracts but reserver 10 for convenience
-			contract_keys: std::iter::repeat(dev_ecdsa_pubkey).take(10).collect(),
+			contracts: (0..10u32)
+				.map(|id| (id, root_key.clone(), Default::default(), dev_ecdsa_pubkey.clone()))
+				.collect(),
+			worker_scoring_params: Default::default(),
+			ias_trust_anchors: vec![
+				(IAS_ROOT_CA_SUBJECT.to_vec(), IAS_ROOT_CA_SPKI.to_vec()),
+			],
+			dcap_trust_anchors: vec![],
 		}),
 		pallet_staking: Some(StakingConfig {
 			validator_count: initial_authorities.len() as u32 * 2,
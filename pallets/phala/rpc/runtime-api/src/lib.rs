@@ -0,0 +1,36 @@
+//! Runtime API definition for the Phala pallet.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use sp_std::prelude::*;
+use pallet_phala::types::{WorkerInfo, StashInfo, MiningStatus};
+
+sp_api::decl_runtime_apis! {
+	/// The API to query the worker registry, the contracts and the mining state of the Phala
+	/// pallet.
+	pub trait PhalaApi<AccountId, BlockNumber, Balance> where
+		AccountId: Codec,
+		BlockNumber: Codec,
+		Balance: Codec,
+	{
+		/// Returns the worker registered by the stash.
+		fn worker_info(stash: AccountId) -> Option<WorkerInfo>;
+		/// Returns the stash and the worker registered with the machine id.
+		fn worker_info_by_machine_id(machine_id: Vec<u8>) -> Option<(AccountId, WorkerInfo)>;
+		/// Returns the controller and the payout preferences of the stash.
+		fn stash_info(stash: AccountId) -> Option<StashInfo<AccountId>>;
+		/// Returns the public key of the contract.
+		fn contract_key(contract_id: u32) -> Option<Vec<u8>>;
+		/// Returns the ECDH public keys of the workers assigned to the contract.
+		fn contract_ecdh_keys(contract_id: u32) -> Vec<Vec<u8>>;
+		/// Returns the sequence of the next egress message of the contract.
+		fn ingress_sequence(contract_id: u32) -> u64;
+		/// Returns the total number of the pushed commands.
+		fn command_number() -> u64;
+		/// Returns the sequence of the next command pushed to the contract.
+		fn contract_command_number(contract_id: u32) -> u64;
+		/// Returns the mining state of the worker registered by the stash.
+		fn mining_status(stash: AccountId) -> Option<MiningStatus<BlockNumber, Balance>>;
+	}
+}
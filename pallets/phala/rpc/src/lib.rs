@@ -0,0 +1,190 @@
+//! RPC interface for the Phala pallet.
+
+use std::sync::Arc;
+
+use codec::Codec;
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::Bytes;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use pallet_phala::types::{WorkerInfo, StashInfo, MiningStatus};
+
+pub use pallet_phala_rpc_runtime_api::PhalaApi as PhalaRuntimeApi;
+
+/// Phala pallet RPC methods.
+#[rpc]
+pub trait PhalaApi<BlockHash, AccountId, BlockNumber, Balance> {
+	/// Returns the worker registered by the stash.
+	#[rpc(name = "phala_workerInfo")]
+	fn worker_info(&self, stash: AccountId, at: Option<BlockHash>) -> Result<Option<WorkerInfo>>;
+
+	/// Returns the stash and the worker registered with the machine id.
+	#[rpc(name = "phala_workerInfoByMachineId")]
+	fn worker_info_by_machine_id(
+		&self,
+		machine_id: Bytes,
+		at: Option<BlockHash>,
+	) -> Result<Option<(AccountId, WorkerInfo)>>;
+
+	/// Returns the controller and the payout preferences of the stash.
+	#[rpc(name = "phala_stashInfo")]
+	fn stash_info(&self, stash: AccountId, at: Option<BlockHash>) -> Result<Option<StashInfo<AccountId>>>;
+
+	/// Returns the public key of the contract.
+	#[rpc(name = "phala_contractKey")]
+	fn contract_key(&self, contract_id: u32, at: Option<BlockHash>) -> Result<Option<Bytes>>;
+
+	/// Returns the ECDH public keys of the workers assigned to the contract, to encrypt the
+	/// commands to the contract.
+	#[rpc(name = "phala_contractEcdhKeys")]
+	fn contract_ecdh_keys(&self, contract_id: u32, at: Option<BlockHash>) -> Result<Vec<Bytes>>;
+
+	/// Returns the sequence of the next egress message of the contract.
+	#[rpc(name = "phala_ingressSequence")]
+	fn ingress_sequence(&self, contract_id: u32, at: Option<BlockHash>) -> Result<u64>;
+
+	/// Returns the sequence of the next command pushed to the contract, or the total number of
+	/// the pushed commands if no contract is specified.
+	#[rpc(name = "phala_commandNumber")]
+	fn command_number(&self, contract_id: Option<u32>, at: Option<BlockHash>) -> Result<u64>;
+
+	/// Returns the mining state of the worker registered by the stash.
+	#[rpc(name = "phala_miningStatus")]
+	fn mining_status(
+		&self,
+		stash: AccountId,
+		at: Option<BlockHash>,
+	) -> Result<Option<MiningStatus<BlockNumber, Balance>>>;
+}
+
+/// Error code for the failed runtime API calls.
+const RUNTIME_ERROR: i64 = 1;
+
+fn runtime_error_into_rpc_err(err: impl std::fmt::Debug) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(RUNTIME_ERROR),
+		message: "Runtime trapped".into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+/// An implementation of Phala specific RPC methods.
+pub struct Phala<C, B> {
+	client: Arc<C>,
+	_marker: std::marker::PhantomData<B>,
+}
+
+impl<C, B> Phala<C, B> {
+	/// Create new `Phala` with the given reference to the client.
+	pub fn new(client: Arc<C>) -> Self {
+		Phala { client, _marker: Default::default() }
+	}
+}
+
+impl<C, Block> Phala<C, Block> where
+	Block: BlockT,
+	C: HeaderBackend<Block>,
+{
+	/// Resolve the block to query, defaulting to the best block.
+	fn block_id(&self, at: Option<<Block as BlockT>::Hash>) -> BlockId<Block> {
+		BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash))
+	}
+}
+
+impl<C, Block, AccountId, BlockNumber, Balance>
+	PhalaApi<<Block as BlockT>::Hash, AccountId, BlockNumber, Balance> for Phala<C, Block>
+where
+	Block: BlockT,
+	C: Send + Sync + 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+	C::Api: PhalaRuntimeApi<Block, AccountId, BlockNumber, Balance>,
+	AccountId: Codec,
+	BlockNumber: Codec,
+	Balance: Codec,
+{
+	fn worker_info(
+		&self,
+		stash: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Option<WorkerInfo>> {
+		self.client.runtime_api()
+			.worker_info(&self.block_id(at), stash)
+			.map_err(runtime_error_into_rpc_err)
+	}
+
+	fn worker_info_by_machine_id(
+		&self,
+		machine_id: Bytes,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Option<(AccountId, WorkerInfo)>> {
+		self.client.runtime_api()
+			.worker_info_by_machine_id(&self.block_id(at), machine_id.to_vec())
+			.map_err(runtime_error_into_rpc_err)
+	}
+
+	fn stash_info(
+		&self,
+		stash: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Option<StashInfo<AccountId>>> {
+		self.client.runtime_api()
+			.stash_info(&self.block_id(at), stash)
+			.map_err(runtime_error_into_rpc_err)
+	}
+
+	fn contract_key(
+		&self,
+		contract_id: u32,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Option<Bytes>> {
+		self.client.runtime_api()
+			.contract_key(&self.block_id(at), contract_id)
+			.map(|key| key.map(Bytes))
+			.map_err(runtime_error_into_rpc_err)
+	}
+
+	fn contract_ecdh_keys(
+		&self,
+		contract_id: u32,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Vec<Bytes>> {
+		self.client.runtime_api()
+			.contract_ecdh_keys(&self.block_id(at), contract_id)
+			.map(|keys| keys.into_iter().map(Bytes).collect())
+			.map_err(runtime_error_into_rpc_err)
+	}
+
+	fn ingress_sequence(
+		&self,
+		contract_id: u32,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<u64> {
+		self.client.runtime_api()
+			.ingress_sequence(&self.block_id(at), contract_id)
+			.map_err(runtime_error_into_rpc_err)
+	}
+
+	fn command_number(
+		&self,
+		contract_id: Option<u32>,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<u64> {
+		let api = self.client.runtime_api();
+		let at = self.block_id(at);
+		match contract_id {
+			Some(contract_id) => api.contract_command_number(&at, contract_id),
+			None => api.command_number(&at),
+		}.map_err(runtime_error_into_rpc_err)
+	}
+
+	fn mining_status(
+		&self,
+		stash: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Option<MiningStatus<BlockNumber, Balance>>> {
+		self.client.runtime_api()
+			.mining_status(&self.block_id(at), stash)
+			.map_err(runtime_error_into_rpc_err)
+	}
+}
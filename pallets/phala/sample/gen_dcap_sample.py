@@ -0,0 +1,197 @@
+#!/usr/bin/env python3
+# Generates the DCAP fixtures used by the tests: a self-issued PCK certificate chain mimicking the
+# Intel SGX PKI, and a version 3 ECDSA quote of the sample enclave signed through it. The quote is
+# bound to the genesis block of the mock runtime, whose hash is `[69; 32]`.
+#
+# Requires the `cryptography` package. Run in this directory:
+#
+#   python3 gen_dcap_sample.py
+
+import datetime
+import hashlib
+import struct
+
+from cryptography import x509
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ec
+from cryptography.hazmat.primitives.asymmetric.utils import decode_dss_signature
+from cryptography.x509.oid import NameOID
+
+# The encoded runtime info and the measurement of the enclave in the IAS sample report
+TEE_REPORT_SAMPLE = bytes([
+    1, 122, 238, 139, 126, 110, 55, 54, 207, 3, 19, 185, 137, 120, 238, 90, 71, 2, 28, 239, 90,
+    188, 129, 213, 193, 164, 64, 149, 82, 38, 229, 204, 150, 142, 110, 10, 182, 8, 122, 212, 50,
+    211, 194, 12, 193, 229, 219, 235, 185, 232, 8, 4, 0, 0, 0, 1, 0, 0, 0])
+SAMPLE_MR_ENCLAVE = bytes.fromhex('c585865ef0d9f1c6b71e0d3f2189c2dcadc0d93c95b79ba79ad34e7f6eb5f9ae')
+SAMPLE_MR_SIGNER = bytes.fromhex('83d719e77deaca1470f6baf62a4d774303c899db69020f9c70ee1dfc08c7ce9e')
+
+QE_MR_SIGNER = bytes.fromhex('8c4f5775d796503e96137f77c68a829a0056ac8ded70140b081b094490c57bff')
+QE_ISV_PROD_ID = 1
+QE_ISV_SVN = 5
+INTEL_QE_VENDOR_ID = bytes.fromhex('939a7233f79c4ca9940a0db3957f0607')
+
+# TCB level and platform model in the SGX extensions of the PCK certificate
+PCK_CPU_SVN = bytes([5, 5, 2, 2, 3, 1, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0])
+PCK_PCE_SVN = 10
+PCK_FMSPC = bytes.fromhex('00906ed50000')
+PCK_SERIAL = 0x1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d
+
+ANCHOR_BLOCK_NUMBER = 0
+ANCHOR_BLOCK_HASH = bytes([69] * 32)
+
+NOT_BEFORE = datetime.datetime(2020, 1, 1)
+NOT_AFTER = datetime.datetime(2049, 12, 31, 23, 59, 59)
+
+
+def name(common_name):
+    return x509.Name([
+        x509.NameAttribute(NameOID.COMMON_NAME, common_name),
+        x509.NameAttribute(NameOID.ORGANIZATION_NAME, 'Intel Corporation'),
+        x509.NameAttribute(NameOID.LOCALITY_NAME, 'Santa Clara'),
+        x509.NameAttribute(NameOID.STATE_OR_PROVINCE_NAME, 'CA'),
+        x509.NameAttribute(NameOID.COUNTRY_NAME, 'US'),
+    ])
+
+
+def der(tag, content):
+    if len(content) < 0x80:
+        return bytes([tag, len(content)]) + content
+    length = len(content).to_bytes((len(content).bit_length() + 7) // 8, 'big')
+    return bytes([tag, 0x80 | len(length)]) + length + content
+
+
+def der_oid(oid):
+    arcs = [int(arc) for arc in oid.split('.')]
+    out = bytes([arcs[0] * 40 + arcs[1]])
+    for arc in arcs[2:]:
+        chunk = [arc & 0x7f]
+        arc >>= 7
+        while arc:
+            chunk.insert(0, 0x80 | (arc & 0x7f))
+            arc >>= 7
+        out += bytes(chunk)
+    return der(0x06, out)
+
+
+def der_int(value):
+    return der(0x02, value.to_bytes(value.bit_length() // 8 + 1, 'big'))
+
+
+def sgx_extensions():
+    """The SGX extensions (OID 1.2.840.113741.1.13.1) of a PCK certificate"""
+    sgx = '1.2.840.113741.1.13.1'
+    entry = lambda oid, value: der(0x30, der_oid(oid) + value)
+    tcb = b''.join(entry(f'{sgx}.2.{i + 1}', der_int(svn)) for (i, svn) in enumerate(PCK_CPU_SVN))
+    tcb += entry(f'{sgx}.2.17', der_int(PCK_PCE_SVN))
+    tcb += entry(f'{sgx}.2.18', der(0x04, PCK_CPU_SVN))
+    return der(0x30, b''.join([
+        entry(f'{sgx}.1', der(0x04, bytes(range(16)))),                 # PPID
+        entry(f'{sgx}.2', der(0x30, tcb)),                              # TCB
+        entry(f'{sgx}.3', der(0x04, bytes(2))),                         # PCE-ID
+        entry(f'{sgx}.4', der(0x04, PCK_FMSPC)),                        # FMSPC
+        entry(f'{sgx}.5', der(0x0a, bytes([0]))),                       # SGX Type
+    ]))
+
+
+def issue(subject, key, issuer, issuer_key, ca):
+    builder = x509.CertificateBuilder() \
+        .subject_name(subject) \
+        .issuer_name(issuer) \
+        .public_key(key.public_key()) \
+        .serial_number(x509.random_serial_number() if ca else PCK_SERIAL) \
+        .not_valid_before(NOT_BEFORE) \
+        .not_valid_after(NOT_AFTER)
+    if ca:
+        builder = builder \
+            .add_extension(x509.BasicConstraints(ca=True, path_length=None if issuer == subject else 0), critical=True) \
+            .add_extension(x509.KeyUsage(
+                digital_signature=False, content_commitment=False, key_encipherment=False,
+                data_encipherment=False, key_agreement=False, key_cert_sign=True, crl_sign=True,
+                encipher_only=False, decipher_only=False), critical=True)
+    else:
+        builder = builder \
+            .add_extension(x509.BasicConstraints(ca=False, path_length=None), critical=True) \
+            .add_extension(x509.KeyUsage(
+                digital_signature=True, content_commitment=True, key_encipherment=False,
+                data_encipherment=False, key_agreement=False, key_cert_sign=False, crl_sign=False,
+                encipher_only=False, decipher_only=False), critical=True) \
+            .add_extension(x509.UnrecognizedExtension(
+                x509.ObjectIdentifier('1.2.840.113741.1.13.1'), sgx_extensions()), critical=False)
+    return builder.sign(issuer_key, hashes.SHA256())
+
+
+def strip_der_header(der):
+    """Returns the contents of a DER encoded SEQUENCE, as expected by `webpki::TrustAnchor`"""
+    assert der[0] == 0x30
+    if der[1] < 0x80:
+        return der[2:]
+    return der[2 + (der[1] & 0x7f):]
+
+
+def sign_raw(key, data):
+    r, s = decode_dss_signature(key.sign(data, ec.ECDSA(hashes.SHA256())))
+    return r.to_bytes(32, 'big') + s.to_bytes(32, 'big')
+
+
+def report_body(mr_enclave, mr_signer, isv_prod_id, isv_svn, report_data):
+    body = bytearray(384)
+    body[0:16] = bytes(range(16))                   # cpu_svn
+    body[48:64] = bytes.fromhex('0700000000000000e700000000000000')  # attributes
+    body[64:96] = mr_enclave
+    body[128:160] = mr_signer
+    body[256:258] = struct.pack('<H', isv_prod_id)
+    body[258:260] = struct.pack('<H', isv_svn)
+    body[320:384] = report_data
+    return bytes(body)
+
+
+def main():
+    root_key = ec.generate_private_key(ec.SECP256R1())
+    ca_key = ec.generate_private_key(ec.SECP256R1())
+    pck_key = ec.generate_private_key(ec.SECP256R1())
+    attestation_key = ec.generate_private_key(ec.SECP256R1())
+
+    root_name = name('Intel SGX Root CA')
+    ca_name = name('Intel SGX PCK Platform CA')
+    root_cert = issue(root_name, root_key, root_name, root_key, True)
+    ca_cert = issue(ca_name, ca_key, root_name, root_key, True)
+    pck_cert = issue(name('Intel SGX PCK Certificate'), pck_key, ca_name, ca_key, False)
+
+    # Quoting Enclave report binding the attestation key
+    raw_attestation_key = attestation_key.public_key().public_bytes(
+        serialization.Encoding.X962, serialization.PublicFormat.UncompressedPoint)[1:]
+    qe_auth_data = bytes(range(32))
+    qe_report_data = hashlib.sha256(raw_attestation_key + qe_auth_data).digest() + bytes(32)
+    qe_report = report_body(bytes(32), QE_MR_SIGNER, QE_ISV_PROD_ID, QE_ISV_SVN, qe_report_data)
+    qe_report_signature = sign_raw(pck_key, qe_report)
+
+    # Quote of the sample enclave
+    header = struct.pack('<HHIHH', 3, 2, 0, QE_ISV_SVN, PCK_PCE_SVN) + INTEL_QE_VENDOR_ID + bytes(20)
+    # `DcapReportData` bound to the anchor block
+    isv_report_data = hashlib.blake2b(TEE_REPORT_SAMPLE + ANCHOR_BLOCK_HASH, digest_size=32).digest() \
+        + struct.pack('<I', ANCHOR_BLOCK_NUMBER) + bytes(28)
+    isv_report = report_body(SAMPLE_MR_ENCLAVE, SAMPLE_MR_SIGNER, 0, 0, isv_report_data)
+    signed = header + isv_report
+    isv_signature = sign_raw(attestation_key, signed)
+
+    pem_chain = b''.join(cert.public_bytes(serialization.Encoding.PEM) for cert in [pck_cert, ca_cert, root_cert])
+    signature_data = isv_signature + raw_attestation_key + qe_report + qe_report_signature \
+        + struct.pack('<H', len(qe_auth_data)) + qe_auth_data \
+        + struct.pack('<HI', 5, len(pem_chain)) + pem_chain
+    quote = signed + struct.pack('<I', len(signature_data)) + signature_data
+
+    outputs = {
+        'dcap_quote': quote,
+        'dcap_pck_certificate': pck_cert.public_bytes(serialization.Encoding.DER),
+        'dcap_pck_ca_certificate': ca_cert.public_bytes(serialization.Encoding.DER),
+        'dcap_root_ca_subject': strip_der_header(root_cert.subject.public_bytes()),
+        'dcap_root_ca_spki': strip_der_header(root_key.public_key().public_bytes(
+            serialization.Encoding.DER, serialization.PublicFormat.SubjectPublicKeyInfo)),
+    }
+    for (path, data) in outputs.items():
+        with open(path, 'wb') as f:
+            f.write(data)
+
+
+if __name__ == '__main__':
+    main()
@@ -0,0 +1,85 @@
+#!/usr/bin/env python3
+# Generates the IAS fixtures used by the tests: a self-issued IAS-like certificate chain, and
+# reports with unexpected contents signed through it. The chain is trusted by the tests with
+# `force_add_ias_trust_anchor`, so that the reports pass the signature checks.
+#
+# Requires the `cryptography` package. Run in this directory:
+#
+#   python3 gen_ias_sample.py
+
+import datetime
+
+from cryptography import x509
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import padding, rsa
+from cryptography.x509.oid import NameOID
+
+NOT_BEFORE = datetime.datetime(2020, 1, 1)
+NOT_AFTER = datetime.datetime(2049, 12, 31, 23, 59, 59)
+
+# The timestamp of the IAS sample report
+REPORT_TIMESTAMP = '2020-09-30T16:52:48.649888'
+
+MALFORMED_REPORTS = {
+    'malformed_report_not_json': b'not a json report',
+    'malformed_report_no_quote_body':
+        ('{"timestamp":"%s","isvEnclaveQuoteStatus":"OK"}' % REPORT_TIMESTAMP).encode(),
+    'malformed_report_bad_quote_body':
+        ('{"timestamp":"%s","isvEnclaveQuoteStatus":"OK","isvEnclaveQuoteBody":"!not base64!"}'
+            % REPORT_TIMESTAMP).encode(),
+}
+
+
+def name(common_name):
+    return x509.Name([
+        x509.NameAttribute(NameOID.COMMON_NAME, common_name),
+        x509.NameAttribute(NameOID.ORGANIZATION_NAME, 'Intel Corporation'),
+        x509.NameAttribute(NameOID.LOCALITY_NAME, 'Santa Clara'),
+        x509.NameAttribute(NameOID.STATE_OR_PROVINCE_NAME, 'CA'),
+        x509.NameAttribute(NameOID.COUNTRY_NAME, 'US'),
+    ])
+
+
+def issue(subject, key, issuer, issuer_key, ca):
+    return x509.CertificateBuilder() \
+        .subject_name(subject) \
+        .issuer_name(issuer) \
+        .public_key(key.public_key()) \
+        .serial_number(x509.random_serial_number()) \
+        .not_valid_before(NOT_BEFORE) \
+        .not_valid_after(NOT_AFTER) \
+        .add_extension(x509.BasicConstraints(ca=ca, path_length=None), critical=True) \
+        .sign(issuer_key, hashes.SHA256())
+
+
+def strip_der_header(der):
+    """Returns the contents of a DER encoded SEQUENCE, as expected by `webpki::TrustAnchor`"""
+    assert der[0] == 0x30
+    if der[1] < 0x80:
+        return der[2:]
+    return der[2 + (der[1] & 0x7f):]
+
+
+def main():
+    root_key = rsa.generate_private_key(public_exponent=65537, key_size=3072)
+    signing_key = rsa.generate_private_key(public_exponent=65537, key_size=2048)
+    root_name = name('Intel SGX Attestation Report Signing CA')
+    root_cert = issue(root_name, root_key, root_name, root_key, True)
+    signing_cert = issue(name('Intel SGX Attestation Report Signing'), signing_key, root_name, root_key, False)
+
+    outputs = {
+        'test_ias_root_ca_subject': strip_der_header(root_cert.subject.public_bytes()),
+        'test_ias_root_ca_spki': strip_der_header(root_key.public_key().public_bytes(
+            serialization.Encoding.DER, serialization.PublicFormat.SubjectPublicKeyInfo)),
+        'test_ias_signing_certificate': signing_cert.public_bytes(serialization.Encoding.DER),
+    }
+    for (path, report) in MALFORMED_REPORTS.items():
+        outputs[path] = report
+        outputs[path + '_signature'] = signing_key.sign(report, padding.PKCS1v15(), hashes.SHA256())
+    for (path, data) in outputs.items():
+        with open(path, 'wb') as f:
+            f.write(data)
+
+
+if __name__ == '__main__':
+    main()
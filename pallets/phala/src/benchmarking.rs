@@ -0,0 +1,698 @@
+//! Phala pallet benchmarking.
+
+use super::*;
+use crate::Module as Phala;
+
+use frame_system::RawOrigin;
+use frame_support::storage::StoragePrefixedMap;
+use frame_benchmarking::{benchmarks, account, whitelisted_caller, whitelist_account};
+use sp_core::{crypto::KeyTypeId, ecdsa};
+use sp_runtime::traits::{Bounded, Zero};
+use types::{Transfer, Heartbeat, EgressMessage, ContractKeyRotation, ChallengeResponse, FeatureWeight, TcbLevel};
+
+const SEED: u32 = 0;
+const MAX_PAYLOAD_LEN: u32 = 64 * 1024;
+const MAX_WORKERS: u32 = 100;
+const MAX_ASSIGNED_WORKERS: u32 = 32;
+const MAX_EGRESS_BATCH: u32 = 64;
+const MAX_TCB_LEVELS: u32 = 32;
+const MAX_REVOKED_CERTS: u32 = 1000;
+const KEY_TYPE: KeyTypeId = KeyTypeId(*b"phal");
+
+const IAS_REPORT_SAMPLE: &[u8] = include_bytes!("../sample/report");
+const IAS_REPORT_SIGNATURE: &[u8] = include_bytes!("../sample/report_signature");
+const IAS_REPORT_SIGNING_CERTIFICATE: &[u8] = include_bytes!("../sample/report_signing_certificate");
+// 2020-09-30T16:52:48Z, right after the sample report was issued
+const IAS_REPORT_TIMESTAMP: u64 = 1601484768000;
+const TEE_REPORT_SAMPLE: &[u8] = &[1, 122, 238, 139, 126, 110, 55, 54, 207, 3, 19, 185, 137, 120, 238, 90, 71, 2, 28, 239, 90, 188, 129, 213, 193, 164, 64, 149, 82, 38, 229, 204, 150, 142, 110, 10, 182, 8, 122, 212, 50, 211, 194, 12, 193, 229, 219, 235, 185, 232, 8, 4, 0, 0, 0, 1, 0, 0, 0];
+// Measurement of the enclave producing the sample report
+const SAMPLE_MR_ENCLAVE: [u8; 32] = [197, 133, 134, 94, 240, 217, 241, 198, 183, 30, 13, 63, 33, 137, 194, 220, 173, 192, 217, 60, 149, 183, 155, 167, 154, 211, 78, 127, 110, 181, 249, 174];
+const SAMPLE_MR_SIGNER: [u8; 32] = [131, 215, 25, 231, 125, 234, 202, 20, 112, 246, 186, 246, 42, 77, 119, 67, 3, 200, 153, 219, 105, 2, 15, 156, 112, 238, 29, 252, 8, 199, 206, 158];
+// A DCAP quote of the sample enclave, certified by a self-issued PCK certificate chain
+const DCAP_QUOTE_SAMPLE: &[u8] = include_bytes!("../sample/dcap_quote");
+const DCAP_PCK_CERTIFICATE: &[u8] = include_bytes!("../sample/dcap_pck_certificate");
+const DCAP_PCK_CA_CERTIFICATE: &[u8] = include_bytes!("../sample/dcap_pck_ca_certificate");
+const DCAP_ROOT_CA_SUBJECT: &[u8] = include_bytes!("../sample/dcap_root_ca_subject");
+const DCAP_ROOT_CA_SPKI: &[u8] = include_bytes!("../sample/dcap_root_ca_spki");
+const SAMPLE_QE_MR_SIGNER: [u8; 32] = [140, 79, 87, 117, 215, 150, 80, 62, 150, 19, 127, 119, 198, 138, 130, 154, 0, 86, 172, 141, 237, 112, 20, 11, 8, 27, 9, 68, 144, 197, 123, 255];
+// Platform model and TCB level in the sample PCK certificate
+const SAMPLE_FMSPC: [u8; 6] = [0, 144, 110, 213, 0, 0];
+const SAMPLE_TCB_COMPONENTS: [u8; 16] = [5, 5, 2, 2, 3, 1, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0];
+const SAMPLE_PCE_SVN: u16 = 10;
+// The sample quote is bound to the genesis block of the mock runtime
+const SAMPLE_ANCHOR_HASH: [u8; 32] = [69; 32];
+
+// Create a stash with its controller, bypassing the checks in `set_stash`
+fn create_stash<T: Trait>(index: u32) -> (T::AccountId, T::AccountId) {
+	let stash: T::AccountId = account("stash", index, SEED);
+	let controller: T::AccountId = account("controller", index, SEED);
+	StashState::<T>::insert(&stash, StashInfo {
+		controller: controller.clone(),
+		payout_prefs: PayoutPrefs {
+			commission: 0,
+			target: stash.clone(),
+		}
+	});
+	Stash::<T>::insert(&controller, &stash);
+	(stash, controller)
+}
+
+// Fund the stash and reserve `WorkerBond` as its worker bond
+fn bond_stash<T: Trait>(stash: &T::AccountId) {
+	let bond = T::WorkerBond::get();
+	T::TEECurrency::make_free_balance_be(stash, BalanceOf::<T>::max_value() / 2u32.into());
+	T::TEECurrency::reserve(stash, bond).expect("the stash is funded; qed");
+	WorkerBonds::<T>::insert(stash, bond);
+}
+
+fn insert_worker<T: Trait>(stash: &T::AccountId, machine_id: Vec<u8>, pubkey: Vec<u8>, state: MinerState) {
+	WorkerState::<T>::insert(stash, WorkerInfo {
+		machine_id: machine_id.clone(),
+		pubkey,
+		ecdh_pubkey: vec![4; types::ECDH_PUBKEY_LEN],
+		mr_enclave: SAMPLE_MR_ENCLAVE.to_vec(),
+		last_updated: 0,
+		state,
+		score: Some(Score {
+			overall_score: 100,
+			features: vec![1, 4]
+		}),
+		attestation_exempt: false,
+	});
+	MachineOwner::<T>::insert(&machine_id, stash);
+}
+
+fn register_contract<T: Trait>(contract_id: u32, pubkey: Vec<u8>) -> T::AccountId {
+	let owner: T::AccountId = account("owner", contract_id, SEED);
+	Contracts::<T>::insert(contract_id, ContractInfo {
+		owner: owner.clone(),
+		code_hash: T::Hash::default(),
+		pubkey: pubkey.clone(),
+		status: ContractStatus::Active,
+	});
+	ContractKey::insert(contract_id, pubkey);
+	owner
+}
+
+// Trust the sample report at the time it was issued
+fn setup_attestation<T: Trait + pallet_timestamp::Trait>() where T::Moment: From<u64> {
+	IASTrustAnchors::put(vec![IASTrustAnchor {
+		subject: constants::IAS_ROOT_CA_SUBJECT.to_vec(),
+		spki: constants::IAS_ROOT_CA_SPKI.to_vec(),
+	}]);
+	EnclaveWhitelist::insert(SAMPLE_MR_ENCLAVE.to_vec(), EnclaveMeasurement {
+		mr_signer: SAMPLE_MR_SIGNER.to_vec(),
+		isv_prod_id: 0,
+		min_isv_svn: 0,
+	});
+	pallet_timestamp::Module::<T>::set_timestamp(IAS_REPORT_TIMESTAMP.into());
+}
+
+// Trust the sample DCAP quote, in addition to the sample report
+fn setup_dcap_attestation<T: Trait + pallet_timestamp::Trait>() where T::Moment: From<u64> {
+	setup_attestation::<T>();
+	DCAPTrustAnchors::put(vec![DCAPTrustAnchor {
+		subject: DCAP_ROOT_CA_SUBJECT.to_vec(),
+		spki: DCAP_ROOT_CA_SPKI.to_vec(),
+	}]);
+	QEIdentity::put(EnclaveMeasurement {
+		mr_signer: SAMPLE_QE_MR_SIGNER.to_vec(),
+		isv_prod_id: 1,
+		min_isv_svn: 0,
+	});
+	TcbLevels::insert(SAMPLE_FMSPC.to_vec(), vec![TcbLevel {
+		components: SAMPLE_TCB_COMPONENTS,
+		pce_svn: SAMPLE_PCE_SVN,
+	}]);
+	let anchor_hash = T::Hash::decode(&mut &SAMPLE_ANCHOR_HASH[..]).expect("the hash is 32 bytes; qed");
+	frame_system::BlockHash::<T>::insert(T::BlockNumber::zero(), anchor_hash);
+	frame_system::Module::<T>::set_block_number(1u32.into());
+}
+
+// Generate a worker (or contract) key in the keystore
+fn generate_key() -> ecdsa::Public {
+	sp_io::crypto::ecdsa_generate(KEY_TYPE, None)
+}
+
+fn sign(pubkey: &ecdsa::Public, data: &impl Encode) -> Vec<u8> {
+	sp_io::crypto::ecdsa_sign(KEY_TYPE, pubkey, &data.encode())
+		.expect("the key is in the keystore; qed")
+		.0
+		.to_vec()
+}
+
+benchmarks! {
+	where_clause { where T: pallet_timestamp::Trait, T::Moment: From<u64> }
+
+	_{}
+
+	push_command {
+		let n in 0 .. T::MaxCommandSize::get();
+		let caller: T::AccountId = whitelisted_caller();
+		T::TEECurrency::make_free_balance_be(&caller, BalanceOf::<T>::max_value() / 2u32.into());
+		register_contract::<T>(0, vec![2u8; 33]);
+		let sequence = ContractCommandNumber::get(0);
+		let payload = vec![0u8; n as usize];
+	}: _(RawOrigin::Signed(caller), 0, payload)
+	verify {
+		assert_eq!(ContractCommandNumber::get(0), sequence + 1);
+	}
+
+	set_stash {
+		let stash: T::AccountId = whitelisted_caller();
+		let prev_controller: T::AccountId = account("controller", 0, SEED);
+		let controller: T::AccountId = account("controller", 1, SEED);
+		// Worst case: replace an existing controller
+		StashState::<T>::insert(&stash, StashInfo {
+			controller: prev_controller.clone(),
+			payout_prefs: PayoutPrefs {
+				commission: 0,
+				target: stash.clone(),
+			}
+		});
+		Stash::<T>::insert(&prev_controller, &stash);
+	}: _(RawOrigin::Signed(stash.clone()), controller.clone())
+	verify {
+		assert_eq!(Stash::<T>::get(&controller), stash);
+		assert!(!Stash::<T>::contains_key(&prev_controller));
+	}
+
+	set_payout_prefs {
+		let (stash, controller) = create_stash::<T>(0);
+		whitelist_account!(controller);
+		let target: T::AccountId = account("target", 0, SEED);
+	}: _(RawOrigin::Signed(controller), Some(50), Some(target.clone()))
+	verify {
+		assert_eq!(StashState::<T>::get(&stash).payout_prefs.target, target);
+	}
+
+	register_worker {
+		let (stash, controller) = create_stash::<T>(0);
+		whitelist_account!(controller);
+		bond_stash::<T>(&stash);
+		setup_attestation::<T>();
+		let signature = base64::decode(IAS_REPORT_SIGNATURE).expect("valid fixture; qed");
+		let signing_cert = base64::decode_config(IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD)
+			.expect("valid fixture; qed");
+		// Worst case: the machine is already registered
+		Phala::<T>::register_worker(
+			RawOrigin::Signed(controller.clone()).into(),
+			TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), signature.clone(), signing_cert.clone()
+		)?;
+		UsedReports::remove_all();
+	}: _(RawOrigin::Signed(controller), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), signature, signing_cert)
+	verify {
+		assert_eq!(WorkerState::<T>::get(&stash).mr_enclave, SAMPLE_MR_ENCLAVE.to_vec());
+	}
+
+	register_worker_dcap {
+		let (stash, controller) = create_stash::<T>(0);
+		whitelist_account!(controller);
+		bond_stash::<T>(&stash);
+		setup_dcap_attestation::<T>();
+		let pck_cert_chain = vec![DCAP_PCK_CERTIFICATE.to_vec(), DCAP_PCK_CA_CERTIFICATE.to_vec()];
+		// Worst case: the machine is already registered
+		Phala::<T>::register_worker_dcap(
+			RawOrigin::Signed(controller.clone()).into(),
+			TEE_REPORT_SAMPLE.to_vec(), DCAP_QUOTE_SAMPLE.to_vec(), pck_cert_chain.clone()
+		)?;
+		UsedQuotes::<T>::remove_all();
+	}: _(RawOrigin::Signed(controller), TEE_REPORT_SAMPLE.to_vec(), DCAP_QUOTE_SAMPLE.to_vec(), pck_cert_chain)
+	verify {
+		assert_eq!(WorkerState::<T>::get(&stash).mr_enclave, SAMPLE_MR_ENCLAVE.to_vec());
+	}
+
+	force_register_worker {
+		let (stash, _) = create_stash::<T>(0);
+		let machine_id = vec![1u8; 16];
+		insert_worker::<T>(&stash, machine_id.clone(), vec![2u8; 33], MinerState::Idle);
+	}: _(RawOrigin::Root, stash.clone(), machine_id.clone(), vec![3u8; 33])
+	verify {
+		assert_eq!(MachineOwner::<T>::get(&machine_id), stash);
+	}
+
+	unregister_worker {
+		let (stash, controller) = create_stash::<T>(0);
+		whitelist_account!(controller);
+		bond_stash::<T>(&stash);
+		let machine_id = vec![1u8; 16];
+		insert_worker::<T>(&stash, machine_id.clone(), vec![2u8; 33], MinerState::Idle);
+	}: _(RawOrigin::Signed(controller))
+	verify {
+		assert!(!WorkerState::<T>::contains_key(&stash));
+		assert!(MachineCooldown::<T>::contains_key(&machine_id));
+	}
+
+	remove_stash {
+		let (stash, _) = create_stash::<T>(0);
+		whitelist_account!(stash);
+		bond_stash::<T>(&stash);
+		insert_worker::<T>(&stash, vec![1u8; 16], vec![2u8; 33], MinerState::Idle);
+	}: _(RawOrigin::Signed(stash.clone()))
+	verify {
+		assert!(!StashState::<T>::contains_key(&stash));
+		assert!(StashCooldown::<T>::contains_key(&stash));
+	}
+
+	bond_worker {
+		let (stash, _) = create_stash::<T>(0);
+		whitelist_account!(stash);
+		T::TEECurrency::make_free_balance_be(&stash, BalanceOf::<T>::max_value() / 2u32.into());
+		let amount = T::WorkerBond::get().saturating_add(1u32.into());
+	}: _(RawOrigin::Signed(stash.clone()), amount)
+	verify {
+		assert_eq!(WorkerBonds::<T>::get(&stash), amount);
+	}
+
+	withdraw_unbonded {
+		let (stash, _) = create_stash::<T>(0);
+		whitelist_account!(stash);
+		T::TEECurrency::make_free_balance_be(&stash, BalanceOf::<T>::max_value() / 2u32.into());
+		let amount: BalanceOf<T> = 100u32.into();
+		T::TEECurrency::reserve(&stash, amount)?;
+		UnbondingBonds::<T>::insert(&stash, (amount, T::BlockNumber::from(1u32)));
+		frame_system::Module::<T>::set_block_number(2u32.into());
+	}: _(RawOrigin::Signed(stash.clone()))
+	verify {
+		assert!(!UnbondingBonds::<T>::contains_key(&stash));
+	}
+
+	force_set_contract_key {
+	}: _(RawOrigin::Root, 0, vec![2u8; 33])
+	verify {
+		assert!(ContractKey::contains_key(0));
+	}
+
+	force_add_ias_trust_anchor {
+		setup_attestation::<T>();
+	}: _(RawOrigin::Root, vec![1u8; 64], vec![2u8; 294])
+	verify {
+		assert_eq!(IASTrustAnchors::get().len(), 2);
+	}
+
+	force_remove_ias_trust_anchor {
+		setup_attestation::<T>();
+	}: _(RawOrigin::Root, constants::IAS_ROOT_CA_SPKI.to_vec())
+	verify {
+		assert!(IASTrustAnchors::get().is_empty());
+	}
+
+	force_add_dcap_trust_anchor {
+		setup_dcap_attestation::<T>();
+	}: _(RawOrigin::Root, vec![1u8; 64], vec![2u8; 89])
+	verify {
+		assert_eq!(DCAPTrustAnchors::get().len(), 2);
+	}
+
+	force_remove_dcap_trust_anchor {
+		setup_dcap_attestation::<T>();
+	}: _(RawOrigin::Root, DCAP_ROOT_CA_SPKI.to_vec())
+	verify {
+		assert!(DCAPTrustAnchors::get().is_empty());
+	}
+
+	set_qe_identity {
+		let origin = T::GovernanceOrigin::successful_origin();
+	}: _<T::Origin>(origin, SAMPLE_QE_MR_SIGNER.to_vec(), 1, 0)
+	verify {
+		assert!(QEIdentity::exists());
+	}
+
+	set_tcb_levels {
+		let n in 1 .. MAX_TCB_LEVELS;
+		let levels = vec![TcbLevel::default(); n as usize];
+		let origin = T::GovernanceOrigin::successful_origin();
+	}: _<T::Origin>(origin, SAMPLE_FMSPC.to_vec(), levels)
+	verify {
+		assert_eq!(TcbLevels::get(SAMPLE_FMSPC.to_vec()).len(), n as usize);
+	}
+
+	revoke_pck_certs {
+		let n in 1 .. MAX_REVOKED_CERTS;
+		let serials: Vec<Vec<u8>> = (0 .. n).map(|i| i.encode()).collect();
+		let origin = T::GovernanceOrigin::successful_origin();
+	}: _<T::Origin>(origin, serials)
+	verify {
+		assert!(RevokedPCKCerts::get(0u32.encode()));
+	}
+
+	add_enclave_measurement {
+		let origin = T::GovernanceOrigin::successful_origin();
+	}: _<T::Origin>(origin, SAMPLE_MR_ENCLAVE.to_vec(), SAMPLE_MR_SIGNER.to_vec(), 0, 0)
+	verify {
+		assert!(EnclaveWhitelist::contains_key(SAMPLE_MR_ENCLAVE.to_vec()));
+	}
+
+	revoke_enclave_measurement {
+		let w in 0 .. MAX_WORKERS;
+		setup_attestation::<T>();
+		// Start from an empty registry
+		WorkerState::<T>::remove_all();
+		for i in 0 .. w {
+			let (stash, _) = create_stash::<T>(i);
+			insert_worker::<T>(&stash, i.encode(), vec![2u8; 33], MinerState::Mining);
+			LastHeartbeat::<T>::insert(&stash, (T::BlockNumber::from(1u32), 1));
+		}
+		let origin = T::GovernanceOrigin::successful_origin();
+	}: _<T::Origin>(origin, SAMPLE_MR_ENCLAVE.to_vec(), true, w)
+	verify {
+		assert!(!EnclaveWhitelist::contains_key(SAMPLE_MR_ENCLAVE.to_vec()));
+		assert!(WorkerState::<T>::iter().all(|(_, worker_info)| worker_info.state == MinerState::Evicted));
+	}
+
+	set_scoring_params {
+		let w in 0 .. MAX_WORKERS;
+		// Start from an empty registry
+		WorkerState::<T>::remove_all();
+		for i in 0 .. w {
+			let (stash, _) = create_stash::<T>(i);
+			insert_worker::<T>(&stash, i.encode(), vec![2u8; 33], MinerState::Mining);
+		}
+		let params = ScoringParams {
+			max_cores: 64,
+			baseline: 100,
+			feature_weights: vec![FeatureWeight { weight: 20, cap: 3 }],
+		};
+		let origin = T::GovernanceOrigin::successful_origin();
+	}: _<T::Origin>(origin, params.clone(), w)
+	verify {
+		assert_eq!(WorkerScoringParams::get(), params);
+	}
+
+	start_mine {
+		let (stash, controller) = create_stash::<T>(0);
+		whitelist_account!(controller);
+		bond_stash::<T>(&stash);
+		insert_worker::<T>(&stash, vec![1u8; 16], vec![2u8; 33], MinerState::Idle);
+	}: _(RawOrigin::Signed(controller))
+	verify {
+		assert_eq!(WorkerState::<T>::get(&stash).state, MinerState::Mining);
+	}
+
+	stop_mine {
+		let (stash, controller) = create_stash::<T>(0);
+		whitelist_account!(controller);
+		insert_worker::<T>(&stash, vec![1u8; 16], vec![2u8; 33], MinerState::Mining);
+	}: _(RawOrigin::Signed(controller))
+	verify {
+		assert_eq!(WorkerState::<T>::get(&stash).state, MinerState::PendingStop);
+	}
+
+	claim_reward {
+		let caller: T::AccountId = whitelisted_caller();
+		let (stash, controller) = create_stash::<T>(0);
+		let target: T::AccountId = account("target", 0, SEED);
+		Phala::<T>::set_payout_prefs(RawOrigin::Signed(controller).into(), Some(50), Some(target))?;
+		let amount = T::TEECurrency::minimum_balance().saturating_add(1u32.into()) * 100u32.into();
+		PendingRewards::<T>::insert(&stash, amount);
+	}: _(RawOrigin::Signed(caller), stash.clone())
+	verify {
+		assert!(PendingRewards::<T>::get(&stash).is_zero());
+	}
+
+	transfer_to_tee {
+		let caller: T::AccountId = whitelisted_caller();
+		T::TEECurrency::make_free_balance_be(&caller, BalanceOf::<T>::max_value() / 2u32.into());
+		let amount = T::TEECurrency::minimum_balance().saturating_add(1u32.into()) * 10u32.into();
+	}: _(RawOrigin::Signed(caller), amount)
+	verify {
+		assert_eq!(T::TEECurrency::free_balance(&Phala::<T>::account_id()), amount);
+	}
+
+	transfer_to_chain {
+		let caller: T::AccountId = whitelisted_caller();
+		let dest: T::AccountId = account("dest", 0, SEED);
+		let pubkey = generate_key();
+		ContractKey::insert(2, pubkey.0.to_vec());
+		T::TEECurrency::make_free_balance_be(&Phala::<T>::account_id(), BalanceOf::<T>::max_value() / 2u32.into());
+		let amount = T::TEECurrency::minimum_balance().saturating_add(1u32.into()) * 10u32.into();
+		let transfer = Transfer {
+			dest: dest.clone(),
+			amount,
+			sequence: IngressSequence::get(2) + 1,
+		};
+		let signature = sign(&pubkey, &transfer);
+		let data = TransferData { data: transfer, signature }.encode();
+	}: _(RawOrigin::Signed(caller), data)
+	verify {
+		assert_eq!(T::TEECurrency::free_balance(&dest), amount);
+	}
+
+	transfer_to_chain_batch {
+		let n in 1 .. MAX_EGRESS_BATCH;
+		let caller: T::AccountId = whitelisted_caller();
+		let dest: T::AccountId = account("dest", 0, SEED);
+		let pubkey = generate_key();
+		ContractKey::insert(2, pubkey.0.to_vec());
+		T::TEECurrency::make_free_balance_be(&Phala::<T>::account_id(), BalanceOf::<T>::max_value() / 2u32.into());
+		let amount = T::TEECurrency::minimum_balance().saturating_add(1u32.into()) * 10u32.into();
+		let sequence = IngressSequence::get(2);
+		let messages: Vec<Vec<u8>> = (1 ..= n as u64)
+			.map(|i| {
+				let transfer = Transfer {
+					dest: dest.clone(),
+					amount,
+					sequence: sequence + i,
+				};
+				let signature = sign(&pubkey, &transfer);
+				TransferData { data: transfer, signature }.encode()
+			})
+			.collect();
+	}: _(RawOrigin::Signed(caller), messages)
+	verify {
+		assert_eq!(IngressSequence::get(2), sequence + n as u64);
+	}
+
+	push_egress_message {
+		let n in 0 .. MAX_PAYLOAD_LEN;
+		let caller: T::AccountId = whitelisted_caller();
+		let pubkey = generate_key();
+		ContractKey::insert(0, pubkey.0.to_vec());
+		EgressHandlers::insert(0, EgressHandler::Event);
+		let sequence = IngressSequence::get(0) + 1;
+		let message = EgressMessage {
+			contract_id: 0,
+			sequence,
+			payload: vec![0u8; n as usize],
+		};
+		let signature = sign(&pubkey, &message);
+		let data = SignedEgressMessage { data: message, signature }.encode();
+	}: _(RawOrigin::Signed(caller), data)
+	verify {
+		assert_eq!(IngressSequence::get(0), sequence);
+	}
+
+	set_egress_handler {
+		let origin = T::GovernanceOrigin::successful_origin();
+	}: _<T::Origin>(origin, 0, Some(EgressHandler::Balance))
+	verify {
+		assert_eq!(EgressHandlers::get(0), Some(EgressHandler::Balance));
+	}
+
+	set_egress_threshold {
+		let origin = T::GovernanceOrigin::successful_origin();
+	}: _<T::Origin>(origin, 0, Some(2))
+	verify {
+		assert_eq!(EgressThreshold::get(0), Some(2));
+	}
+
+	sign_egress_message {
+		let n in 0 .. MAX_PAYLOAD_LEN;
+		let s in 0 .. MAX_ASSIGNED_WORKERS - 1;
+		let (stash, controller) = create_stash::<T>(0);
+		whitelist_account!(controller);
+		let worker_key = generate_key();
+		insert_worker::<T>(&stash, vec![1u8; 16], worker_key.0.to_vec(), MinerState::Mining);
+		register_contract::<T>(0, vec![2u8; 33]);
+		EgressHandlers::insert(0, EgressHandler::Event);
+		ContractAssign::<T>::insert(0, vec![stash]);
+		let sequence = IngressSequence::get(0) + 1;
+		let message = EgressMessage {
+			contract_id: 0,
+			sequence,
+			payload: vec![0u8; n as usize],
+		};
+		// Worst case: the last signature collected executes the message
+		EgressThreshold::insert(0, s + 1);
+		let signers: Vec<T::AccountId> = (1 .. s + 1).map(|i| account("signer", i, SEED)).collect();
+		PendingEgress::<T>::insert(0, T::Hashing::hash_of(&message), PendingEgressMessage {
+			message: message.clone(),
+			signers,
+			expiry: T::BlockNumber::max_value(),
+		});
+		let signature = sign(&worker_key, &message);
+		let data = SignedEgressMessage { data: message, signature }.encode();
+	}: _(RawOrigin::Signed(controller), data, s)
+	verify {
+		assert_eq!(IngressSequence::get(0), sequence);
+	}
+
+	report_egress_equivocation {
+		let n in 0 .. MAX_PAYLOAD_LEN;
+		let caller: T::AccountId = whitelisted_caller();
+		let (stash, _) = create_stash::<T>(0);
+		let worker_key = generate_key();
+		insert_worker::<T>(&stash, vec![1u8; 16], worker_key.0.to_vec(), MinerState::Mining);
+		// Worst case: slash both the bond and the funds being unbonded
+		bond_stash::<T>(&stash);
+		let unbonding: BalanceOf<T> = 100u32.into();
+		T::TEECurrency::reserve(&stash, unbonding)?;
+		UnbondingBonds::<T>::insert(&stash, (unbonding, T::BlockNumber::max_value()));
+		let signed = |payload: Vec<u8>| {
+			let message = EgressMessage { contract_id: 0, sequence: 1, payload };
+			let signature = sign(&worker_key, &message);
+			SignedEgressMessage { data: message, signature }.encode()
+		};
+		let first = signed(vec![0u8; (n / 2) as usize]);
+		let second = signed(vec![1u8; (n / 2) as usize + 1]);
+	}: _(RawOrigin::Signed(caller), stash.clone(), first, second)
+	verify {
+		assert!(WorkerBonds::<T>::get(&stash).is_zero());
+		assert_eq!(WorkerState::<T>::get(&stash).state, MinerState::Evicted);
+	}
+
+	register_contract {
+		let owner: T::AccountId = account("owner", 0, SEED);
+		let origin = T::GovernanceOrigin::successful_origin();
+		Contracts::<T>::remove(0);
+	}: _<T::Origin>(origin, 0, owner, T::Hash::default(), vec![2u8; 33])
+	verify {
+		assert!(Contracts::<T>::contains_key(0));
+	}
+
+	assign_contract {
+		let w in 1 .. MAX_ASSIGNED_WORKERS;
+		register_contract::<T>(0, vec![2u8; 33]);
+		let mut workers = Vec::new();
+		for i in 0 .. w {
+			let (stash, _) = create_stash::<T>(i);
+			insert_worker::<T>(&stash, i.encode(), vec![2u8; 33], MinerState::Mining);
+			workers.push(stash);
+		}
+		// Worst case: replace the workers previously assigned
+		for stash in workers.iter() {
+			WorkerContracts::<T>::insert(stash, vec![0]);
+		}
+		ContractAssign::<T>::insert(0, workers.clone());
+		let origin = T::GovernanceOrigin::successful_origin();
+	}: _<T::Origin>(origin, 0, workers)
+	verify {
+		assert_eq!(ContractAssign::<T>::get(0).len(), w as usize);
+	}
+
+	retire_contract {
+		let owner = register_contract::<T>(0, vec![2u8; 33]);
+		whitelist_account!(owner);
+	}: _(RawOrigin::Signed(owner), 0)
+	verify {
+		assert_eq!(Contracts::<T>::get(0).map(|c| c.status), Some(ContractStatus::Retired));
+	}
+
+	rotate_contract_key {
+		let (stash, controller) = create_stash::<T>(0);
+		whitelist_account!(controller);
+		let worker_key = generate_key();
+		insert_worker::<T>(&stash, vec![1u8; 16], worker_key.0.to_vec(), MinerState::Mining);
+		// Worst case: replace an existing key
+		register_contract::<T>(0, vec![2u8; 33]);
+		ContractAssign::<T>::insert(0, vec![stash]);
+		let rotation = ContractKeyRotation {
+			contract_id: 0,
+			pubkey: generate_key().0.to_vec(),
+			sequence: ContractKeySequence::get(0) + 1,
+		};
+		let signature = sign(&worker_key, &rotation);
+		let data = SignedContractKeyRotation { data: rotation, signature }.encode();
+	}: _(RawOrigin::Signed(controller), data)
+	verify {
+		assert!(PreviousContractKey::<T>::contains_key(0));
+	}
+
+	heartbeat {
+		let (stash, controller) = create_stash::<T>(0);
+		whitelist_account!(controller);
+		let pubkey = generate_key();
+		insert_worker::<T>(&stash, vec![1u8; 16], pubkey.0.to_vec(), MinerState::Mining);
+		frame_system::Module::<T>::set_block_number(1u32.into());
+		LastHeartbeat::<T>::insert(&stash, (T::BlockNumber::from(1u32), 0));
+		let heartbeat = Heartbeat { block_num: 1 };
+		let signature = sign(&pubkey, &heartbeat);
+		let data = HeartbeatData { data: heartbeat, signature }.encode();
+	}: _(RawOrigin::Signed(controller), data)
+	verify {
+		assert_eq!(LastHeartbeat::<T>::get(&stash), Some((T::BlockNumber::from(1u32), 1)));
+	}
+
+	respond_challenge {
+		let (stash, controller) = create_stash::<T>(0);
+		whitelist_account!(controller);
+		let pubkey = generate_key();
+		insert_worker::<T>(&stash, vec![1u8; 16], pubkey.0.to_vec(), MinerState::Mining);
+		Challenges::<T>::insert(&stash, Challenge {
+			nonce: [1u8; 32],
+			issued_at: T::BlockNumber::from(1u32),
+			deadline: T::BlockNumber::from(10u32),
+		});
+		let response = ChallengeResponse { nonce: [1u8; 32], block_num: 1 };
+		let signature = sign(&pubkey, &response);
+		let data = SignedChallengeResponse { data: response, signature }.encode();
+	}: _(RawOrigin::Signed(controller), data)
+	verify {
+		assert!(!Challenges::<T>::contains_key(&stash));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mock::{new_test_ext, Test};
+	use frame_support::assert_ok;
+	use sp_keystore::{KeystoreExt, testing::KeyStore};
+	use std::sync::Arc;
+
+	#[test]
+	fn test_benchmarks() {
+		let mut ext = new_test_ext();
+		ext.register_extension(KeystoreExt(Arc::new(KeyStore::new())));
+		ext.execute_with(|| {
+			assert_ok!(test_benchmark_push_command::<Test>());
+			assert_ok!(test_benchmark_set_stash::<Test>());
+			assert_ok!(test_benchmark_set_payout_prefs::<Test>());
+			assert_ok!(test_benchmark_register_worker::<Test>());
+			assert_ok!(test_benchmark_register_worker_dcap::<Test>());
+			assert_ok!(test_benchmark_force_register_worker::<Test>());
+			assert_ok!(test_benchmark_unregister_worker::<Test>());
+			assert_ok!(test_benchmark_remove_stash::<Test>());
+			assert_ok!(test_benchmark_bond_worker::<Test>());
+			assert_ok!(test_benchmark_withdraw_unbonded::<Test>());
+			assert_ok!(test_benchmark_force_set_contract_key::<Test>());
+			assert_ok!(test_benchmark_force_add_ias_trust_anchor::<Test>());
+			assert_ok!(test_benchmark_force_remove_ias_trust_anchor::<Test>());
+			assert_ok!(test_benchmark_force_add_dcap_trust_anchor::<Test>());
+			assert_ok!(test_benchmark_force_remove_dcap_trust_anchor::<Test>());
+			assert_ok!(test_benchmark_set_qe_identity::<Test>());
+			assert_ok!(test_benchmark_set_tcb_levels::<Test>());
+			assert_ok!(test_benchmark_revoke_pck_certs::<Test>());
+			assert_ok!(test_benchmark_add_enclave_measurement::<Test>());
+			assert_ok!(test_benchmark_revoke_enclave_measurement::<Test>());
+			assert_ok!(test_benchmark_set_scoring_params::<Test>());
+			assert_ok!(test_benchmark_start_mine::<Test>());
+			assert_ok!(test_benchmark_stop_mine::<Test>());
+			assert_ok!(test_benchmark_claim_reward::<Test>());
+			assert_ok!(test_benchmark_transfer_to_tee::<Test>());
+			assert_ok!(test_benchmark_transfer_to_chain::<Test>());
+			assert_ok!(test_benchmark_transfer_to_chain_batch::<Test>());
+			assert_ok!(test_benchmark_push_egress_message::<Test>());
+			assert_ok!(test_benchmark_set_egress_handler::<Test>());
+			assert_ok!(test_benchmark_set_egress_threshold::<Test>());
+			assert_ok!(test_benchmark_sign_egress_message::<Test>());
+			assert_ok!(test_benchmark_report_egress_equivocation::<Test>());
+			assert_ok!(test_benchmark_register_contract::<Test>());
+			assert_ok!(test_benchmark_assign_contract::<Test>());
+			assert_ok!(test_benchmark_retire_contract::<Test>());
+			assert_ok!(test_benchmark_rotate_contract_key::<Test>());
+			assert_ok!(test_benchmark_heartbeat::<Test>());
+			assert_ok!(test_benchmark_respond_challenge::<Test>());
+		});
+	}
+}
@@ -0,0 +1,356 @@
+//! Parsing and verification of the version 3 ECDSA quotes produced by the SGX DCAP Quoting
+//! Enclave.
+//!
+//! Layout of the quote:
+//!
+//! ```text
+//! header (48) | isv report (384) | signature data len (4) | isv signature (64) |
+//! attestation key (64) | qe report (384) | qe report signature (64) | qe auth data len (2) |
+//! qe auth data | certification data
+//! ```
+//!
+//! The certification data (usually the PEM encoded PCK certificate chain) is not used. The chain
+//! is submitted in DER instead, and the PCK certificate is parsed for the TCB level of the
+//! platform.
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
+
+use crate::types::TcbLevel;
+
+const QUOTE_VERSION: u16 = 3;
+/// ECDSA-256-with-P-256 curve
+const ATTESTATION_KEY_TYPE: u16 = 2;
+const HEADER_LEN: usize = 48;
+const REPORT_LEN: usize = 384;
+/// Length of the header and the isv report, which are signed by the attestation key
+pub const QUOTE_BODY_LEN: usize = HEADER_LEN + REPORT_LEN;
+const SIGNATURE_LEN: usize = 64;
+const ATTESTATION_KEY_LEN: usize = 64;
+
+/// OID 1.2.840.113741.1.13.1 of the SGX extensions of the PCK certificates
+const SGX_EXTENSIONS_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf8, 0x4d, 0x01, 0x0d, 0x01];
+/// Arc of the TCB level in the SGX extensions
+const SGX_TCB: u8 = 2;
+/// Arc of the FMSPC in the SGX extensions
+const SGX_FMSPC: u8 = 4;
+/// Arc of the PCE SVN in the TCB level, following the SVNs of the 16 CPU components
+const TCB_PCE_SVN: u8 = 17;
+
+const DER_BOOLEAN: u8 = 0x01;
+const DER_INTEGER: u8 = 0x02;
+const DER_OCTET_STRING: u8 = 0x04;
+const DER_OID: u8 = 0x06;
+const DER_SEQUENCE: u8 = 0x30;
+/// `[0] EXPLICIT Version` of a certificate
+const DER_CERT_VERSION: u8 = 0xa0;
+/// `[3] EXPLICIT Extensions` of a certificate
+const DER_CERT_EXTENSIONS: u8 = 0xa3;
+
+/// Measurement fields of an enclave report, laid out the same in the EPID and the ECDSA quotes
+pub struct ReportBody<'a> {
+	pub mr_enclave: &'a [u8],
+	pub mr_signer: &'a [u8],
+	pub isv_prod_id: u16,
+	pub isv_svn: u16,
+	pub report_data: &'a [u8],
+}
+
+impl<'a> ReportBody<'a> {
+	/// Parses a 384 bytes `sgx_report_body_t`
+	pub fn parse(raw: &'a [u8]) -> Option<Self> {
+		if raw.len() < REPORT_LEN {
+			return None;
+		}
+		Some(ReportBody {
+			mr_enclave: &raw[64..96],
+			mr_signer: &raw[128..160],
+			isv_prod_id: u16::from_le_bytes([raw[256], raw[257]]),
+			isv_svn: u16::from_le_bytes([raw[258], raw[259]]),
+			report_data: &raw[320..384],
+		})
+	}
+}
+
+pub struct Quote<'a> {
+	/// The header and the isv report
+	pub body: &'a [u8],
+	pub isv_report: ReportBody<'a>,
+	isv_signature: &'a [u8],
+	attestation_key: &'a [u8],
+	/// The raw report of the Quoting Enclave, signed by the PCK
+	pub raw_qe_report: &'a [u8],
+	pub qe_report: ReportBody<'a>,
+	qe_report_signature: &'a [u8],
+	qe_auth_data: &'a [u8],
+}
+
+impl<'a> Quote<'a> {
+	/// Parses an ECDSA-256 quote. Returns `None` if it's truncated or of another version or key
+	/// type.
+	pub fn parse(raw: &'a [u8]) -> Option<Self> {
+		if raw.len() < QUOTE_BODY_LEN + 4 {
+			return None;
+		}
+		let version = u16::from_le_bytes([raw[0], raw[1]]);
+		let key_type = u16::from_le_bytes([raw[2], raw[3]]);
+		if version != QUOTE_VERSION || key_type != ATTESTATION_KEY_TYPE {
+			return None;
+		}
+		let signature_data_len = u32::from_le_bytes([raw[432], raw[433], raw[434], raw[435]]) as usize;
+		let signature_data = raw.get(QUOTE_BODY_LEN + 4..)?;
+		if signature_data.len() < signature_data_len {
+			return None;
+		}
+		let mut reader = Reader(&signature_data[..signature_data_len]);
+		let isv_signature = reader.take(SIGNATURE_LEN)?;
+		let attestation_key = reader.take(ATTESTATION_KEY_LEN)?;
+		let raw_qe_report = reader.take(REPORT_LEN)?;
+		let qe_report_signature = reader.take(SIGNATURE_LEN)?;
+		let qe_auth_data_len = reader.take(2)?;
+		let qe_auth_data = reader.take(u16::from_le_bytes([qe_auth_data_len[0], qe_auth_data_len[1]]) as usize)?;
+		Some(Quote {
+			body: &raw[..QUOTE_BODY_LEN],
+			isv_report: ReportBody::parse(&raw[HEADER_LEN..QUOTE_BODY_LEN])?,
+			isv_signature,
+			attestation_key,
+			raw_qe_report,
+			qe_report: ReportBody::parse(raw_qe_report)?,
+			qe_report_signature,
+			qe_auth_data,
+		})
+	}
+
+	/// The DER encoded signature of the PCK over the report of the Quoting Enclave
+	pub fn qe_report_signature_der(&self) -> Vec<u8> {
+		ecdsa_signature_to_der(self.qe_report_signature)
+	}
+
+	/// Whether the report of the Quoting Enclave is bound to the attestation key, i.e. its report
+	/// data is `sha256(attestation_key || qe_auth_data)` padded with zeros
+	pub fn is_attestation_key_bound(&self) -> bool {
+		let mut data = self.attestation_key.to_vec();
+		data.extend_from_slice(self.qe_auth_data);
+		let report_data = self.qe_report.report_data;
+		report_data[..32] == sp_io::hashing::sha2_256(&data)[..] && report_data[32..].iter().all(|b| *b == 0)
+	}
+
+	/// Whether the quote body is signed by the attestation key
+	pub fn verify_isv_signature(&self) -> bool {
+		let mut public_key = Vec::with_capacity(1 + ATTESTATION_KEY_LEN);
+		// Uncompressed point
+		public_key.push(0x04);
+		public_key.extend_from_slice(self.attestation_key);
+		UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, &public_key)
+			.verify(self.body, self.isv_signature)
+			.is_ok()
+	}
+
+	/// A unique identity of the quote to detect the replays. It covers the signed body and the
+	/// `r` part of the signature, which is random for each quote and, unlike `s`, cannot be
+	/// altered without invalidating the signature.
+	pub fn replay_key(&self) -> [u8; 32] {
+		let mut data = self.body.to_vec();
+		data.extend_from_slice(&self.isv_signature[..32]);
+		sp_io::hashing::blake2_256(&data)
+	}
+}
+
+/// The fields of a PCK certificate identifying the platform and its TCB level
+pub struct PckCert<'a> {
+	/// Serial number, as listed in the PCK CRL once the certificate is revoked
+	pub serial: &'a [u8],
+	/// Family-Model-Stepping-Platform-CustomSKU, identifying the platform model
+	pub fmspc: &'a [u8],
+	pub tcb: TcbLevel,
+}
+
+impl<'a> PckCert<'a> {
+	/// Parses a DER encoded PCK certificate. Returns `None` if it's malformed or has no SGX
+	/// extensions. The signature is not verified.
+	pub fn parse(raw: &'a [u8]) -> Option<Self> {
+		let certificate = Der(raw).expect(DER_SEQUENCE)?;
+		let mut tbs_certificate = Der(Der(certificate).expect(DER_SEQUENCE)?);
+		let mut field = tbs_certificate.next()?;
+		if field.0 == DER_CERT_VERSION {
+			field = tbs_certificate.next()?;
+		}
+		if field.0 != DER_INTEGER {
+			return None;
+		}
+		let serial = field.1;
+		// Skip the signature algorithm, issuer, validity, subject and public key
+		for _ in 0..5 {
+			tbs_certificate.expect(DER_SEQUENCE)?;
+		}
+		// Skip the unique identifiers if any
+		let extensions = loop {
+			let (tag, content) = tbs_certificate.next()?;
+			if tag == DER_CERT_EXTENSIONS {
+				break content;
+			}
+		};
+		let mut extensions = Der(Der(extensions).expect(DER_SEQUENCE)?);
+		while !extensions.is_empty() {
+			let mut extension = Der(extensions.expect(DER_SEQUENCE)?);
+			if extension.expect(DER_OID)? != SGX_EXTENSIONS_OID {
+				continue;
+			}
+			let mut value = extension.next()?;
+			// Skip the criticality if present
+			if value.0 == DER_BOOLEAN {
+				value = extension.next()?;
+			}
+			if value.0 != DER_OCTET_STRING {
+				return None;
+			}
+			let (fmspc, tcb) = parse_sgx_extensions(value.1)?;
+			return Some(PckCert { serial, fmspc, tcb });
+		}
+		None
+	}
+}
+
+/// Parses the FMSPC and the TCB level out of the SGX extensions of a PCK certificate
+fn parse_sgx_extensions(raw: &[u8]) -> Option<(&[u8], TcbLevel)> {
+	let mut entries = Der(Der(raw).expect(DER_SEQUENCE)?);
+	let mut fmspc = None;
+	let mut tcb = None;
+	while !entries.is_empty() {
+		let mut entry = Der(entries.expect(DER_SEQUENCE)?);
+		match child_arc(entry.expect(DER_OID)?, SGX_EXTENSIONS_OID) {
+			Some(SGX_TCB) => tcb = Some(parse_tcb(entry.expect(DER_SEQUENCE)?)?),
+			Some(SGX_FMSPC) => fmspc = Some(entry.expect(DER_OCTET_STRING)?),
+			_ => (),
+		}
+	}
+	Some((fmspc?, tcb?))
+}
+
+/// Parses the TCB level out of the TCB entry of the SGX extensions. The SVNs of all the 16 CPU
+/// components and the PCE must be present.
+fn parse_tcb(raw: &[u8]) -> Option<TcbLevel> {
+	let mut tcb_oid = SGX_EXTENSIONS_OID.to_vec();
+	tcb_oid.push(SGX_TCB);
+	let mut components = Der(raw);
+	let mut tcb = TcbLevel::default();
+	// Bit n is set once the arc n is found
+	let mut found: u32 = 0;
+	while !components.is_empty() {
+		let mut component = Der(components.expect(DER_SEQUENCE)?);
+		match child_arc(component.expect(DER_OID)?, &tcb_oid) {
+			Some(arc @ 1..=16) => {
+				let svn = der_uint(component.expect(DER_INTEGER)?)?;
+				tcb.components[arc as usize - 1] = u8::try_from(svn).ok()?;
+				found |= 1 << arc;
+			},
+			Some(TCB_PCE_SVN) => {
+				let svn = der_uint(component.expect(DER_INTEGER)?)?;
+				tcb.pce_svn = u16::try_from(svn).ok()?;
+				found |= 1 << TCB_PCE_SVN;
+			},
+			// The raw CPU SVN, duplicating the components
+			_ => (),
+		}
+	}
+	if found != (1 << (TCB_PCE_SVN + 1)) - 2 {
+		return None;
+	}
+	Some(tcb)
+}
+
+/// The last arc of `oid` if it's a direct child of `parent`
+fn child_arc(oid: &[u8], parent: &[u8]) -> Option<u8> {
+	match oid.split_last() {
+		Some((arc, prefix)) if prefix == parent && *arc < 0x80 => Some(*arc),
+		_ => None,
+	}
+}
+
+/// Decodes the content of a non-negative DER integer fitting in 32 bits
+fn der_uint(raw: &[u8]) -> Option<u32> {
+	let (first, rest) = raw.split_first()?;
+	if first & 0x80 != 0 {
+		return None;
+	}
+	let value = if *first == 0 { rest } else { raw };
+	if value.len() > 4 {
+		return None;
+	}
+	Some(value.iter().fold(0, |acc, b| acc << 8 | *b as u32))
+}
+
+/// A reader of consecutive DER encoded elements
+struct Der<'a>(&'a [u8]);
+
+impl<'a> Der<'a> {
+	fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	/// Reads the next element. Returns its tag and content.
+	fn next(&mut self) -> Option<(u8, &'a [u8])> {
+		let mut reader = Reader(self.0);
+		let header = reader.take(2)?;
+		let len = match header[1] {
+			len if len < 0x80 => len as usize,
+			// Long form, with up to 4 bytes of length
+			0x81..=0x84 => reader.take((header[1] & 0x7f) as usize)?
+				.iter()
+				.fold(0, |len, b| len << 8 | *b as usize),
+			_ => return None,
+		};
+		let content = reader.take(len)?;
+		self.0 = reader.0;
+		Some((header[0], content))
+	}
+
+	/// Reads the next element, which must be of the tag. Returns its content.
+	fn expect(&mut self, tag: u8) -> Option<&'a [u8]> {
+		match self.next()? {
+			(actual, content) if actual == tag => Some(content),
+			_ => None,
+		}
+	}
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+	fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+		if self.0.len() < len {
+			return None;
+		}
+		let (head, tail) = self.0.split_at(len);
+		self.0 = tail;
+		Some(head)
+	}
+}
+
+/// Converts a raw `r || s` ECDSA signature to the ASN.1 DER encoding expected by webpki
+fn ecdsa_signature_to_der(raw: &[u8]) -> Vec<u8> {
+	fn der_integer(value: &[u8]) -> Vec<u8> {
+		let first = value.iter().position(|b| *b != 0).unwrap_or(value.len() - 1);
+		let value = &value[first..];
+		let mut out = Vec::with_capacity(value.len() + 3);
+		out.push(0x02);
+		if value[0] & 0x80 != 0 {
+			out.push(value.len() as u8 + 1);
+			out.push(0);
+		} else {
+			out.push(value.len() as u8);
+		}
+		out.extend_from_slice(value);
+		out
+	}
+	let (r, s) = raw.split_at(raw.len() / 2);
+	let mut integers = der_integer(r);
+	integers.extend(der_integer(s));
+	let mut out = Vec::with_capacity(integers.len() + 2);
+	out.push(0x30);
+	out.push(integers.len() as u8);
+	out.extend(integers);
+	out
+}
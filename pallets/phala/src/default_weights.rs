@@ -0,0 +1,212 @@
+//! Default weights of pallet-phala.
+//!
+//! These are estimates, not the output of the benchmark CLI. Replace them with the weights
+//! generated by `phala-node benchmark --pallet pallet_phala --extrinsic '*'` on the reference
+//! hardware, and generate them again whenever an extrinsic changes its storage access.
+
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::weights::{Weight, constants::RocksDbWeight as DbWeight};
+
+impl crate::WeightInfo for () {
+	fn push_command(n: u32, ) -> Weight {
+		(71845000 as Weight)
+			.saturating_add((2000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().reads(4 as Weight))
+			.saturating_add(DbWeight::get().writes(4 as Weight))
+	}
+	fn set_stash() -> Weight {
+		(34815000 as Weight)
+			.saturating_add(DbWeight::get().reads(3 as Weight))
+			.saturating_add(DbWeight::get().writes(3 as Weight))
+	}
+	fn set_payout_prefs() -> Weight {
+		(31964000 as Weight)
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn register_worker() -> Weight {
+		(5418276000 as Weight)
+			.saturating_add(DbWeight::get().reads(9 as Weight))
+			.saturating_add(DbWeight::get().writes(6 as Weight))
+	}
+	fn register_worker_dcap() -> Weight {
+		(7318507000 as Weight)
+			.saturating_add(DbWeight::get().reads(14 as Weight))
+			.saturating_add(DbWeight::get().writes(6 as Weight))
+	}
+	fn force_register_worker() -> Weight {
+		(41027000 as Weight)
+			.saturating_add(DbWeight::get().reads(4 as Weight))
+			.saturating_add(DbWeight::get().writes(5 as Weight))
+	}
+	fn unregister_worker() -> Weight {
+		(43519000 as Weight)
+			.saturating_add(DbWeight::get().reads(5 as Weight))
+			.saturating_add(DbWeight::get().writes(6 as Weight))
+	}
+	fn remove_stash() -> Weight {
+		(71283000 as Weight)
+			.saturating_add(DbWeight::get().reads(7 as Weight))
+			.saturating_add(DbWeight::get().writes(11 as Weight))
+	}
+	fn bond_worker() -> Weight {
+		(48513000 as Weight)
+			.saturating_add(DbWeight::get().reads(3 as Weight))
+			.saturating_add(DbWeight::get().writes(2 as Weight))
+	}
+	fn withdraw_unbonded() -> Weight {
+		(45102000 as Weight)
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().writes(2 as Weight))
+	}
+	fn force_set_contract_key() -> Weight {
+		(3112000 as Weight)
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn force_add_ias_trust_anchor() -> Weight {
+		(21630000 as Weight)
+			.saturating_add(DbWeight::get().reads(1 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn force_remove_ias_trust_anchor() -> Weight {
+		(20471000 as Weight)
+			.saturating_add(DbWeight::get().reads(1 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn force_add_dcap_trust_anchor() -> Weight {
+		(21522000 as Weight)
+			.saturating_add(DbWeight::get().reads(1 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn force_remove_dcap_trust_anchor() -> Weight {
+		(20389000 as Weight)
+			.saturating_add(DbWeight::get().reads(1 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn set_qe_identity() -> Weight {
+		(17815000 as Weight)
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn set_tcb_levels(n: u32, ) -> Weight {
+		(18962000 as Weight)
+			.saturating_add((41000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn revoke_pck_certs(n: u32, ) -> Weight {
+		(16340000 as Weight)
+			.saturating_add((2874000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().writes((1 as Weight).saturating_mul(n as Weight)))
+	}
+	fn add_enclave_measurement() -> Weight {
+		(18092000 as Weight)
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn revoke_enclave_measurement(w: u32, ) -> Weight {
+		(30548000 as Weight)
+			.saturating_add((29113000 as Weight).saturating_mul(w as Weight))
+			.saturating_add(DbWeight::get().reads(1 as Weight))
+			.saturating_add(DbWeight::get().reads((1 as Weight).saturating_mul(w as Weight)))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+			.saturating_add(DbWeight::get().writes((4 as Weight).saturating_mul(w as Weight)))
+	}
+	fn set_scoring_params(w: u32, ) -> Weight {
+		(21377000 as Weight)
+			.saturating_add((15792000 as Weight).saturating_mul(w as Weight))
+			.saturating_add(DbWeight::get().reads((1 as Weight).saturating_mul(w as Weight)))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+			.saturating_add(DbWeight::get().writes((1 as Weight).saturating_mul(w as Weight)))
+	}
+	fn start_mine() -> Weight {
+		(33206000 as Weight)
+			.saturating_add(DbWeight::get().reads(3 as Weight))
+			.saturating_add(DbWeight::get().writes(2 as Weight))
+	}
+	fn stop_mine() -> Weight {
+		(26347000 as Weight)
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn claim_reward() -> Weight {
+		(98520000 as Weight)
+			.saturating_add(DbWeight::get().reads(4 as Weight))
+			.saturating_add(DbWeight::get().writes(4 as Weight))
+	}
+	fn transfer_to_tee() -> Weight {
+		(80394000 as Weight)
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().writes(2 as Weight))
+	}
+	fn transfer_to_chain() -> Weight {
+		(188961000 as Weight)
+			.saturating_add(DbWeight::get().reads(4 as Weight))
+			.saturating_add(DbWeight::get().writes(3 as Weight))
+	}
+	fn transfer_to_chain_batch(n: u32, ) -> Weight {
+		(12466000 as Weight)
+			.saturating_add((182310000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().reads((4 as Weight).saturating_mul(n as Weight)))
+			.saturating_add(DbWeight::get().writes((3 as Weight).saturating_mul(n as Weight)))
+	}
+	fn push_egress_message(n: u32, ) -> Weight {
+		(175134000 as Weight)
+			.saturating_add((3000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().reads(3 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn set_egress_handler() -> Weight {
+		(17338000 as Weight)
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn set_egress_threshold() -> Weight {
+		(18204000 as Weight)
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn sign_egress_message(n: u32, s: u32, ) -> Weight {
+		(187395000 as Weight)
+			.saturating_add((2000 as Weight).saturating_mul(n as Weight))
+			.saturating_add((308000 as Weight).saturating_mul(s as Weight))
+			.saturating_add(DbWeight::get().reads(8 as Weight))
+			.saturating_add(DbWeight::get().writes(3 as Weight))
+	}
+	fn report_egress_equivocation(n: u32, ) -> Weight {
+		(312480000 as Weight)
+			.saturating_add((4000 as Weight).saturating_mul(n as Weight))
+			.saturating_add(DbWeight::get().reads(6 as Weight))
+			.saturating_add(DbWeight::get().writes(9 as Weight))
+	}
+	fn heartbeat() -> Weight {
+		(154757000 as Weight)
+			.saturating_add(DbWeight::get().reads(4 as Weight))
+			.saturating_add(DbWeight::get().writes(2 as Weight))
+	}
+	fn respond_challenge() -> Weight {
+		(152846000 as Weight)
+			.saturating_add(DbWeight::get().reads(3 as Weight))
+			.saturating_add(DbWeight::get().writes(1 as Weight))
+	}
+	fn register_contract() -> Weight {
+		(36512000 as Weight)
+			.saturating_add(DbWeight::get().reads(1 as Weight))
+			.saturating_add(DbWeight::get().writes(2 as Weight))
+	}
+	fn assign_contract(w: u32, ) -> Weight {
+		(29871000 as Weight)
+			.saturating_add((5426000 as Weight).saturating_mul(w as Weight))
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().reads((2 as Weight).saturating_mul(w as Weight)))
+			.saturating_add(DbWeight::get().writes(2 as Weight))
+			.saturating_add(DbWeight::get().writes((1 as Weight).saturating_mul(w as Weight)))
+	}
+	fn retire_contract() -> Weight {
+		(31240000 as Weight)
+			.saturating_add(DbWeight::get().reads(2 as Weight))
+			.saturating_add(DbWeight::get().writes(3 as Weight))
+	}
+	fn rotate_contract_key() -> Weight {
+		(172306000 as Weight)
+			.saturating_add(DbWeight::get().reads(7 as Weight))
+			.saturating_add(DbWeight::get().writes(4 as Weight))
+	}
+}
@@ -1,24 +1,45 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 extern crate alloc;
+#[cfg(target_arch = "wasm32")]
+extern crate ring_wasmable as ring;
 use sp_std::prelude::*;
 
-use frame_support::{ensure, decl_module, decl_storage, decl_event, decl_error, dispatch};
+use frame_support::{ensure, decl_module, decl_storage, decl_event, decl_error, dispatch, transactional, weights::Weight};
 use frame_system::{ensure_signed, ensure_root};
 
 use alloc::vec::Vec;
-use sp_runtime::{traits::AccountIdConversion, ModuleId, SaturatedConversion};
+use sp_runtime::{
+	traits::{AccountIdConversion, Hash, Saturating, Zero},
+	ModuleId, Perbill, RuntimeDebug, SaturatedConversion,
+};
 use frame_support::{
-	traits::{Currency, ExistenceRequirement::AllowDeath, UnixTime},
+	traits::{
+		Currency, EnsureOrigin, ExistenceRequirement::{AllowDeath, KeepAlive}, Get, Imbalance, OnUnbalanced,
+		Randomness, ReservableCurrency, UnixTime, WithdrawReason,
+	},
+	storage::{IterableStorageMap, IterableStorageDoubleMap},
 };
 use codec::{Encode, Decode};
 
 mod hashing;
+mod dcap;
+pub mod constants;
 pub mod types;
+pub mod default_weights;
+pub mod migrations;
+pub mod offence;
+mod quota;
 
 use types::{
-	TransferData, HeartbeatData, SignedDataType,
-	WorkerInfo, StashInfo, PayoutPrefs, Score, PRuntimeInfo
+	TransferData, HeartbeatData, SignedChallengeResponse, SignedContractKeyRotation, SignedDataType,
+	SignedEgressMessage, PendingEgressMessage, EgressHandler, BalanceRelease, AssetRelease, ContractInfo, ContractStatus,
+	WorkerInfo, MinerState, StashInfo, PayoutPrefs, Score, ScoringParams, Challenge, MiningStatus, PRuntimeInfo,
+	IASTrustAnchor, DCAPTrustAnchor, EnclaveMeasurement, TcbLevel, DcapReportData, RateLimitedCall
 };
+use offence::{WorkerOfflineOffence, ChallengeMissedOffence, EgressEquivocationOffence};
+pub use offence::IdentifyValidator;
+pub use quota::CheckCallQuota;
+use sp_staking::offence::{Offence, ReportOffence};
 
 #[cfg(test)]
 mod mock;
@@ -26,17 +47,193 @@ mod mock;
 #[cfg(test)]
 mod tests;
 
+#[cfg(feature = "runtime-benchmarks")]
+mod benchmarking;
+
 type BalanceOf<T> = <<T as Trait>::TEECurrency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> = <<T as Trait>::TEECurrency as Currency<<T as frame_system::Trait>::AccountId>>::NegativeImbalance;
 const PALLET_ID: ModuleId = ModuleId(*b"Phala!!!");
 const BUILTIN_MACHINE_ID: &'static str = "BUILTIN";
 
+type SignatureAlgorithms = &'static [&'static webpki::SignatureAlgorithm];
+static SUPPORTED_SIG_ALGS: SignatureAlgorithms = &[
+	&webpki::RSA_PKCS1_2048_8192_SHA256,
+	&webpki::RSA_PKCS1_2048_8192_SHA384,
+	&webpki::RSA_PKCS1_2048_8192_SHA512,
+	&webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+/// Signature algorithms of the Intel SGX PCK certificates
+static DCAP_SIG_ALGS: SignatureAlgorithms = &[
+	&webpki::ECDSA_P256_SHA256,
+];
+
+pub trait WeightInfo {
+	fn push_command(n: u32, ) -> Weight;
+	fn set_stash() -> Weight;
+	fn set_payout_prefs() -> Weight;
+	fn register_worker() -> Weight;
+	fn register_worker_dcap() -> Weight;
+	fn force_register_worker() -> Weight;
+	fn unregister_worker() -> Weight;
+	fn remove_stash() -> Weight;
+	fn bond_worker() -> Weight;
+	fn withdraw_unbonded() -> Weight;
+	fn force_set_contract_key() -> Weight;
+	fn force_add_ias_trust_anchor() -> Weight;
+	fn force_remove_ias_trust_anchor() -> Weight;
+	fn force_add_dcap_trust_anchor() -> Weight;
+	fn force_remove_dcap_trust_anchor() -> Weight;
+	fn set_qe_identity() -> Weight;
+	fn set_tcb_levels(n: u32, ) -> Weight;
+	fn revoke_pck_certs(n: u32, ) -> Weight;
+	fn add_enclave_measurement() -> Weight;
+	fn revoke_enclave_measurement(w: u32, ) -> Weight;
+	fn set_scoring_params(w: u32, ) -> Weight;
+	fn start_mine() -> Weight;
+	fn stop_mine() -> Weight;
+	fn claim_reward() -> Weight;
+	fn transfer_to_tee() -> Weight;
+	fn transfer_to_chain() -> Weight;
+	fn transfer_to_chain_batch(n: u32, ) -> Weight;
+	fn push_egress_message(n: u32, ) -> Weight;
+	fn set_egress_handler() -> Weight;
+	fn set_egress_threshold() -> Weight;
+	fn sign_egress_message(n: u32, s: u32, ) -> Weight;
+	fn report_egress_equivocation(n: u32, ) -> Weight;
+	fn register_contract() -> Weight;
+	fn assign_contract(w: u32, ) -> Weight;
+	fn retire_contract() -> Weight;
+	fn rotate_contract_key() -> Weight;
+	fn heartbeat() -> Weight;
+	fn respond_challenge() -> Weight;
+}
+
+/// Handler for the stashes removed from the registry
+pub trait OnStashRemoved<AccountId> {
+	/// A stash and its controller are removed from the registry
+	fn on_stash_removed(stash: &AccountId, controller: &AccountId);
+}
+
+impl<AccountId> OnStashRemoved<AccountId> for () {
+	fn on_stash_removed(_stash: &AccountId, _controller: &AccountId) {}
+}
+
+// A value placed in storage that represents the current version of the pallet storage. This value
+// is used by the `on_runtime_upgrade` logic to determine whether we run storage migration logic.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+enum Releases {
+	/// `WorkerInfo` with an integer mining status
+	V1_0_0,
+	/// `WorkerInfo` with `MinerState`
+	V2_0_0,
+	/// `WorkerInfo` with the ECDH public key
+	V3_0_0,
+	/// `UsedQuotes` with the block the quotes are bound to
+	V4_0_0,
+	/// `ContractAssign` indexed by the workers in `WorkerContracts`
+	V5_0_0,
+	/// `WorkerInfo` with the attestation exemption
+	V6_0_0,
+}
+
+impl Default for Releases {
+	fn default() -> Self {
+		Releases::V1_0_0
+	}
+}
+
 /// Configure the pallet by specifying the parameters and types on which it depends.
 pub trait Trait: frame_system::Trait {
 	/// Because this pallet emits events, it depends on the runtime's definition of an event.
 	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
 
-	type TEECurrency: Currency<Self::AccountId>;
+	type TEECurrency: ReservableCurrency<Self::AccountId>;
 	type UnixTime: UnixTime;
+
+	/// The origin allowed to manage the enclave measurement whitelist and the scoring parameters.
+	type GovernanceOrigin: EnsureOrigin<Self::Origin>;
+
+	/// Mining reward minted in each block, distributed to the mining workers at the end of each
+	/// round.
+	type RewardPerBlock: Get<BalanceOf<Self>>;
+	/// Number of blocks in a mining round.
+	type RoundInterval: Get<Self::BlockNumber>;
+	/// A mining worker is considered offline if no heartbeat is received for this many blocks.
+	/// Checked at the end of each round.
+	type OfflineThreshold: Get<Self::BlockNumber>;
+	/// Max number of blocks the block number in a heartbeat can fall behind the chain height.
+	type HeartbeatMaxLag: Get<Self::BlockNumber>;
+	/// Source of randomness to pick the workers to challenge.
+	type Randomness: Randomness<Self::Hash>;
+	/// Max number of mining workers challenged at the end of each round.
+	type ChallengesPerRound: Get<u32>;
+	/// Number of blocks a worker has to answer its challenge.
+	type ChallengeDeadline: Get<Self::BlockNumber>;
+
+	/// Max size of the payload of a command in bytes.
+	type MaxCommandSize: Get<u32>;
+	/// Fee charged for each byte of the payload of a command.
+	type CommandByteFee: Get<BalanceOf<Self>>;
+	/// Handler for the command fees (e.g. the treasury).
+	type CommandFee: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+	/// Number of blocks the previous key of a contract stays valid after a key rotation.
+	type ContractKeyGracePeriod: Get<Self::BlockNumber>;
+	/// Number of blocks the partial signatures of an egress message are kept before expiring.
+	type EgressSignatureTimeout: Get<Self::BlockNumber>;
+
+	/// Number of blocks a removed stash or unregistered machine has to wait before joining again.
+	type LeaveCooldown: Get<Self::BlockNumber>;
+	/// Handler for the removed stashes (e.g. to chill them in the staking module).
+	type OnStashRemoved: OnStashRemoved<Self::AccountId>;
+
+	/// Minimum amount a stash must bond to register a worker or start mining.
+	type WorkerBond: Get<BalanceOf<Self>>;
+	/// Number of blocks the bond of an unregistered worker stays slashable before it can be
+	/// withdrawn.
+	type BondUnbondingPeriod: Get<Self::BlockNumber>;
+	/// Handler for the slashed bonds (e.g. the treasury).
+	type Slash: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+	/// The full identification of a validator, reported as the offender of the misbehaving
+	/// workers.
+	type ValidatorIdentification: Clone;
+	/// Finds the validators run by the workers.
+	type IdentifyValidator: IdentifyValidator<Self::AccountId, Self::ValidatorIdentification>;
+	/// Handler for the offences of the workers running validators (e.g. the offences module).
+	type ReportOffence:
+		ReportOffence<
+			Self::AccountId,
+			Self::ValidatorIdentification,
+			WorkerOfflineOffence<Self::BlockNumber, Self::ValidatorIdentification>,
+		> +
+		ReportOffence<
+			Self::AccountId,
+			Self::ValidatorIdentification,
+			ChallengeMissedOffence<Self::BlockNumber, Self::ValidatorIdentification>,
+		> +
+		ReportOffence<
+			Self::AccountId,
+			Self::ValidatorIdentification,
+			EgressEquivocationOffence<Self::ValidatorIdentification>,
+		>;
+
+	/// Number of blocks in a window of the per-account call quota.
+	type CallQuotaWindow: Get<Self::BlockNumber>;
+	/// Max number of each kind of the rate limited calls (`push_command`, `heartbeat` and
+	/// `transfer_to_chain`) an account can submit in a quota window. Enforced by `CheckCallQuota`,
+	/// which counts each message of `transfer_to_chain_batch` as a call.
+	type MaxCallsPerWindow: Get<u32>;
+
+	/// Max age of the attestation report to register a worker, in milliseconds.
+	type MaxReportAge: Get<u64>;
+	/// Mining workers must register again with a fresh attestation report within this period
+	/// (in milliseconds) since the last registration, otherwise they are evicted. The workers
+	/// registered in the genesis or by the root are exempt.
+	type ReattestationInterval: Get<u64>;
+
+	/// Weight information for extrinsics in this pallet.
+	type WeightInfo: WeightInfo;
 }
 
 decl_storage! {
@@ -44,16 +241,51 @@ decl_storage! {
 		// Messaging
 		/// Number of all commands
 		CommandNumber get(fn command_number): Option<u64>;
-		/// Contract assignment
-		ContractAssign get(fn contract_assign): map hasher(twox_64_concat) u32 => T::AccountId;
+		/// Map from contract id to the number of commands pushed to the contract
+		ContractCommandNumber get(fn contract_command_number): map hasher(twox_64_concat) u32 => u64;
+		/// Map from contract id to the registered contract
+		Contracts get(fn contracts): map hasher(twox_64_concat) u32 => Option<ContractInfo<T::AccountId, T::Hash>>;
+		/// Map from contract id to the stash accounts of the workers running the contract
+		ContractAssign get(fn contract_assign): map hasher(twox_64_concat) u32 => Vec<T::AccountId>;
+		/// Map from the stash of a worker to the contracts assigned to it, the reverse index of
+		/// `ContractAssign`
+		WorkerContracts get(fn worker_contracts): map hasher(blake2_128_concat) T::AccountId => Vec<u32>;
+		/// Map from account and kind of the rate limited calls to the start of the quota window
+		/// the account last submitted such calls, and the number of them submitted in the window.
+		/// Cleared at the start of each window.
+		CallQuotaUsage get(fn call_quota_usage):
+			double_map hasher(blake2_128_concat) T::AccountId, hasher(twox_64_concat) RateLimitedCall
+			=> (T::BlockNumber, u32);
 		/// Ingress message queue
 		IngressSequence get(fn ingress_sequence): map hasher(twox_64_concat) u32 => u64;
+		/// Map from contract id to the handler of its egress messages
+		EgressHandlers get(fn egress_handler): map hasher(twox_64_concat) u32 => Option<EgressHandler>;
+		/// Map from contract id to the number of distinct assigned workers required to sign its
+		/// egress messages
+		EgressThreshold get(fn egress_threshold): map hasher(twox_64_concat) u32 => Option<u32>;
+		/// Map from contract id and message hash to the egress message collecting signatures
+		PendingEgress get(fn pending_egress):
+			double_map hasher(twox_64_concat) u32, hasher(identity) T::Hash
+			=> Option<PendingEgressMessage<T::AccountId, T::BlockNumber>>;
 
 		// Worker registry
 		/// Map from stash account to worker info (indexed: MachineOwner)
 		WorkerState get(fn worker_state): map hasher(blake2_128_concat) T::AccountId => WorkerInfo;
 		/// Map from stash account to stash info (indexed: Stash)
 		StashState get(fn stash_state): map hasher(blake2_128_concat) T::AccountId => StashInfo<T::AccountId>;
+		/// Map from removed stash account to the block until which it cannot be set up again
+		StashCooldown get(fn stash_cooldown): map hasher(blake2_128_concat) T::AccountId => Option<T::BlockNumber>;
+		/// Map from unregistered machine_id to the block until which it cannot be registered again
+		MachineCooldown get(fn machine_cooldown): map hasher(blake2_128_concat) Vec<u8> => Option<T::BlockNumber>;
+		/// Map from stash account to the amount it reserved as the worker bond
+		WorkerBonds get(fn worker_bond): map hasher(blake2_128_concat) T::AccountId => BalanceOf<T>;
+		/// Map from stash account to the bond being unbonded and the block after which it can be
+		/// withdrawn
+		UnbondingBonds get(fn unbonding_bond): map hasher(blake2_128_concat) T::AccountId => Option<(BalanceOf<T>, T::BlockNumber)>;
+		/// Set of the (stash, contract_id, sequence) of the equivocating egress signatures already
+		/// reported
+		ReportedEquivocations get(fn reported_equivocation):
+			double_map hasher(blake2_128_concat) T::AccountId, hasher(twox_64_concat) (u32, u64) => bool;
 
 		// Indices
 		/// Map from machine_id to stash
@@ -62,26 +294,91 @@ decl_storage! {
 		Stash get(fn stash): map hasher(blake2_128_concat) T::AccountId => T::AccountId;
 
 		// Key Management
-		/// Map from contract id to contract public key (TODO: migrate to real contract key from
-		/// worker identity key)
+		/// Map from contract id to contract public key, generated by the workers running the
+		/// contract from their identity keys
 		ContractKey get(fn contract_key): map hasher(twox_64_concat) u32 => Vec<u8>;
+		/// Map from contract id to the key replaced by the last rotation, and the last block it's
+		/// still valid
+		PreviousContractKey get(fn previous_contract_key): map hasher(twox_64_concat) u32 => Option<(Vec<u8>, T::BlockNumber)>;
+		/// Map from contract id to the sequence of the last key rotation
+		ContractKeySequence get(fn contract_key_sequence): map hasher(twox_64_concat) u32 => u64;
+		/// Map from contract id to the attested ECDH public keys of the workers assigned to the
+		/// contract, to encrypt the commands pushed to it
+		ContractEcdhKeys get(fn contract_ecdh_keys): map hasher(twox_64_concat) u32 => Vec<Vec<u8>>;
+
+		// Mining
+		/// Index of the current mining round
+		Round get(fn round): u32;
+		/// Map from stash account to the number of heartbeats received from its mining worker in
+		/// the current round
+		RoundHeartbeats get(fn round_heartbeats): map hasher(blake2_128_concat) T::AccountId => u32;
+		/// Map from stash account to the rewards accrued but not paid out yet
+		PendingRewards get(fn pending_rewards): map hasher(blake2_128_concat) T::AccountId => BalanceOf<T>;
+		/// Map from stash account to the block when the last heartbeat of its worker was received,
+		/// and the block number reported in the heartbeat
+		LastHeartbeat get(fn last_heartbeat): map hasher(blake2_128_concat) T::AccountId => Option<(T::BlockNumber, u32)>;
+		/// Map from stash account to the pending challenge of its worker
+		Challenges get(fn challenges): map hasher(blake2_128_concat) T::AccountId => Option<Challenge<T::BlockNumber>>;
+		/// Map from stash account to the number of challenges its worker failed to answer
+		MissedChallenges get(fn missed_challenges): map hasher(blake2_128_concat) T::AccountId => u32;
+		/// Map from stash account to the time (in milliseconds) its mining worker last went offline
+		LastOffline get(fn last_offline): map hasher(blake2_128_concat) T::AccountId => Option<u64>;
+		/// Parameters of the formula to score the workers
+		WorkerScoringParams get(fn scoring_params) config(): ScoringParams;
+
+		// Attestation
+		/// Trusted root certificates of the IAS report signing certificate
+		IASTrustAnchors get(fn ias_trust_anchors): Vec<IASTrustAnchor>;
+		/// Map from MRENCLAVE to the allowed measurement of the enclave
+		EnclaveWhitelist get(fn enclave_whitelist): map hasher(blake2_128_concat) Vec<u8> => Option<EnclaveMeasurement>;
+		/// Map from the hash of the accepted attestation reports to their timestamps, kept until
+		/// the reports exceed `MaxReportAge`
+		UsedReports get(fn used_reports): map hasher(identity) [u8; 32] => Option<u64>;
+		/// Trusted root certificates of the PCK certificate chain of the DCAP quotes
+		DCAPTrustAnchors get(fn dcap_trust_anchors): Vec<DCAPTrustAnchor>;
+		/// Allowed measurement of the Quoting Enclave signing the DCAP quotes
+		QEIdentity get(fn qe_identity): Option<EnclaveMeasurement>;
+		/// Map from FMSPC (the platform model) to the TCB levels accepted for the model. The
+		/// platforms below all of them cannot register with DCAP quotes.
+		TcbLevels get(fn tcb_levels): map hasher(blake2_128_concat) Vec<u8> => Vec<TcbLevel>;
+		/// Set of the serial numbers of the revoked PCK certificates
+		RevokedPCKCerts get(fn revoked_pck_cert): map hasher(blake2_128_concat) Vec<u8> => bool;
+		/// Map from the replay key of the accepted DCAP quotes to the block they are bound to,
+		/// kept until the block is older than `BlockHashCount`
+		UsedQuotes get(fn used_quotes): map hasher(identity) [u8; 32] => Option<T::BlockNumber>;
+
+		/// Version of the storage layout, used by the runtime upgrade migrations
+		StorageVersion build(|_: &GenesisConfig<T>| Releases::V6_0_0): Releases;
 	}
 
 	add_extra_genesis {
 		config(stakers): Vec<(T::AccountId, T::AccountId, Vec<u8>)>;  // <stash, controller, pubkey>
 		config(contract_keys): Vec<Vec<u8>>;
+		config(contracts): Vec<(u32, T::AccountId, T::Hash, Vec<u8>)>;  // <id, owner, code_hash, pubkey>
+		config(ias_trust_anchors): Vec<(Vec<u8>, Vec<u8>)>;  // <subject, spki>
+		config(dcap_trust_anchors): Vec<(Vec<u8>, Vec<u8>)>;  // <subject, spki>
 		build(|config: &GenesisConfig<T>| {
 			let base_mid = BUILTIN_MACHINE_ID.as_bytes().to_vec();
 			for (i, (stash, controller, pubkey)) in config.stakers.iter().enumerate() {
 				// Mock worker / stash info
 				let mut machine_id = base_mid.clone();
 				machine_id.push(b'0' + (i as u8));
+				// The builtin workers start mining from the genesis, so that their validators are
+				// elected. Scored as the force registered workers.
+				let features = vec![1, 4];
 				let worker_info = WorkerInfo {
 					machine_id,
 					pubkey: pubkey.clone(),
+					state: MinerState::Mining,
+					score: Some(Score {
+						overall_score: WorkerScoringParams::get().overall_score(&features).unwrap_or_default(),
+						features,
+					}),
+					attestation_exempt: true,
 					..Default::default()
 				};
 				WorkerState::<T>::insert(&stash, worker_info);
+				LastHeartbeat::<T>::insert(&stash, (T::BlockNumber::zero(), 0));
 				let stash_info = StashInfo {
 					controller: controller.clone(),
 					payout_prefs: PayoutPrefs {
@@ -92,11 +389,39 @@ decl_storage! {
 				StashState::<T>::insert(&stash, stash_info);
 				// Update indices (skip MachineOwenr because we won't use it in anyway)
 				Stash::<T>::insert(&controller, &stash);
+				// Bond the builtin workers so that they are allowed to mine
+				let bond = T::WorkerBond::get();
+				T::TEECurrency::reserve(&stash, bond)
+					.expect("Stash does not have enough balance to bond the worker.");
+				WorkerBonds::<T>::insert(&stash, bond);
 			}
 			// Insert the default contract key here
 			for (i, key) in config.contract_keys.iter().enumerate() {
 				ContractKey::insert(i as u32, key);
 			}
+			for (id, owner, code_hash, pubkey) in config.contracts.iter() {
+				Contracts::<T>::insert(id, ContractInfo {
+					owner: owner.clone(),
+					code_hash: code_hash.clone(),
+					pubkey: pubkey.clone(),
+					status: ContractStatus::Active,
+				});
+				ContractKey::insert(id, pubkey);
+			}
+			let ias_trust_anchors: Vec<IASTrustAnchor> = config.ias_trust_anchors.iter()
+				.map(|(subject, spki)| IASTrustAnchor {
+					subject: subject.clone(),
+					spki: spki.clone(),
+				})
+				.collect();
+			IASTrustAnchors::put(ias_trust_anchors);
+			let dcap_trust_anchors: Vec<DCAPTrustAnchor> = config.dcap_trust_anchors.iter()
+				.map(|(subject, spki)| DCAPTrustAnchor {
+					subject: subject.clone(),
+					spki: spki.clone(),
+				})
+				.collect();
+			DCAPTrustAnchors::put(dcap_trust_anchors);
 		});
 	}
 }
@@ -107,12 +432,73 @@ decl_event!(
 		LogString(Vec<u8>),
 		LogI32(i32),
 		// Chain events
+		/// A command is pushed to a contract. [sender, contract_id, payload, sequence]
 		CommandPushed(AccountId, u32, Vec<u8>, u64),
 		TransferToTee(Vec<u8>, Balance),
 		TransferToChain(Vec<u8>, Balance, u64),
+		/// An egress message from a contract is handled. [contract_id, sequence]
+		EgressMessageHandled(u32, u64),
+		/// An asset is released by a contract. [contract_id, asset_id, dest, amount]
+		AssetReleased(u32, u32, AccountId, Balance),
+		/// An arbitrary event emitted by a contract. [contract_id, payload]
+		ContractEvent(u32, Vec<u8>),
+		/// The egress handler of a contract is changed. [contract_id]
+		EgressHandlerSet(u32),
+		/// The egress signature threshold of a contract is changed. [contract_id]
+		EgressThresholdSet(u32),
+		/// A worker signed an egress message waiting for more signatures.
+		/// [contract_id, sequence, stash]
+		EgressMessageSigned(u32, u64, AccountId),
+		/// An egress message failed to collect enough signatures in time. [contract_id, sequence]
+		EgressMessageExpired(u32, u64),
+		/// A contract is registered. [contract_id, owner]
+		ContractRegistered(u32, AccountId),
+		/// A contract is assigned to a set of workers, replacing the previous ones.
+		/// [contract_id, machine_ids]
+		ContractAssigned(u32, Vec<Vec<u8>>),
+		/// A contract is retired. [contract_id]
+		ContractRetired(u32),
+		/// The key of a contract is rotated. [contract_id, pubkey]
+		ContractKeyRotated(u32, Vec<u8>),
 		WorkerRegistered(AccountId, Vec<u8>),
 		WorkerUnregistered(AccountId, Vec<u8>),
+		WorkerEvicted(AccountId, Vec<u8>),
+		/// A stash is removed along with its controller. [stash]
+		StashRemoved(AccountId),
+		/// A stash reserved more funds as its worker bond. [stash, amount]
+		WorkerBonded(AccountId, Balance),
+		/// The worker bond of a stash started unbonding. [stash, amount]
+		WorkerUnbonded(AccountId, Balance),
+		/// An unbonded worker bond is released to the stash. [stash, amount]
+		BondWithdrawn(AccountId, Balance),
+		/// The bond of a worker signed conflicting egress messages is slashed.
+		/// [stash, contract_id, sequence, amount]
+		EquivocationSlashed(AccountId, u32, u64, Balance),
 		Heartbeat(AccountId, u32),
+		/// A mining worker missed its heartbeats and stopped mining. [stash]
+		WorkerOffline(AccountId),
+		/// A mining worker is challenged. [stash, machine_id, nonce]
+		ChallengeIssued(AccountId, Vec<u8>, [u8; 32]),
+		/// A worker answered its challenge. [stash]
+		ChallengeAnswered(AccountId),
+		/// A worker failed to answer its challenge in time and stopped mining. [stash]
+		ChallengeMissed(AccountId),
+		EnclaveMeasurementAdded(Vec<u8>),
+		EnclaveMeasurementRevoked(Vec<u8>),
+		/// The allowed measurement of the DCAP Quoting Enclave is updated.
+		QEIdentitySet,
+		/// The scoring parameters are updated and the workers are rescored. [num_workers]
+		ScoringParamsSet(u32),
+		/// A mining round ended. [round, total_reward]
+		RoundEnded(u32, Balance),
+		/// Mining reward accrued to a stash in a round. [stash, amount]
+		RewardAccrued(AccountId, Balance),
+		/// Pending reward of a stash paid out. [stash, target, to_target, to_stash]
+		RewardPaid(AccountId, AccountId, Balance, Balance),
+		/// The TCB levels accepted for a platform model are updated. [fmspc]
+		TcbLevelsSet(Vec<u8>),
+		/// PCK certificates are revoked. [num_certs]
+		PCKCertsRevoked(u32),
 	}
 );
 
@@ -144,11 +530,52 @@ decl_error! {
 		AlreadyPaired,
 		/// Commission is not between 0 and 100
 		InvalidCommission,
+		/// The stash or the machine left recently and is still in the cooldown period
+		InCooldown,
+		/// The pending reward of the stash must be claimed before removing it
+		PendingRewardNotClaimed,
+		/// The worker bond of the stash is less than `WorkerBond`
+		InsufficientBond,
+		/// Cannot reserve the bond from the free balance of the stash
+		CannotReserveBond,
+		/// The stash has no bond being unbonded
+		NoUnbondingBond,
+		/// The unbonding period of the bond is not over
+		StillUnbonding,
+		// Mining
+		/// The worker is not in a state allowing the operation
+		InvalidMinerState,
+		/// No reward to claim
+		NoPendingReward,
+		/// The block number in the heartbeat is lower than the last one
+		StaleHeartbeat,
+		/// The block number in the heartbeat is ahead of or too far behind the chain height
+		HeartbeatOutOfRange,
+		/// The worker has no pending challenge
+		ChallengeNotFound,
+		/// The response doesn't match the challenge
+		InvalidChallengeResponse,
 		// Messagging
+		/// The payload of the command exceeds `MaxCommandSize`
+		CommandTooLarge,
+		/// Cannot pay the fee of the command
+		CannotPayCommandFee,
 		/// Cannot decode the message
 		InvalidMessage,
 		/// Wrong sequence number of a message
 		BadMessageSequence,
+		/// No egress handler is registered for the contract
+		EgressHandlerNotFound,
+		/// The egress messages of the contract must be signed by multiple workers
+		EgressThresholdRequired,
+		/// The egress messages of the contract don't need signatures from the workers
+		EgressThresholdNotSet,
+		/// The worker already signed the egress message
+		DuplicateEgressSignature,
+		/// The two egress messages are not conflicting signatures of the worker
+		InvalidEquivocationProof,
+		/// The equivocation is already reported and slashed
+		DuplicateEquivocationReport,
 		// Token
 		/// Failed to deposit tokens to pRuntime due to some internal errors in `Currency` module
 		CannotDeposit,
@@ -159,8 +586,53 @@ decl_error! {
 		InvalidInput,
 		/// Invalid contract
 		InvalidContract,
+		// Contract registry
+		/// The contract id is already registered
+		ContractExists,
+		/// The contract is not registered
+		ContractNotFound,
+		/// The contract is retired
+		ContractNotActive,
+		/// Not the owner of the contract
+		NotContractOwner,
+		/// The worker is not assigned to the contract
+		WorkerNotAssigned,
 		/// Internal Error
 		InternalError,
+		// Attestation
+		/// The IAS trust anchor is already in the list
+		IASTrustAnchorExists,
+		/// The IAS trust anchor is not found
+		IASTrustAnchorNotFound,
+		/// The enclave measurement in the quote is not in the whitelist
+		EnclaveNotWhitelisted,
+		/// The enclave measurement is not found in the whitelist
+		EnclaveMeasurementNotFound,
+		/// The timestamp of the attestation report is missing or malformed
+		InvalidReportTimestamp,
+		/// The attestation report is older than `MaxReportAge`
+		OutdatedReport,
+		/// The attestation report has been used to register a worker
+		DuplicateReport,
+		/// The DCAP trust anchor is already in the list
+		DCAPTrustAnchorExists,
+		/// The DCAP trust anchor is not found
+		DCAPTrustAnchorNotFound,
+		/// The DCAP quote is malformed, unsupported or not signed by its attestation key
+		InvalidDCAPQuote,
+		/// The PCK certificate chain is malformed or not issued by a DCAP trust anchor
+		InvalidPCKCertChain,
+		/// The Quoting Enclave report is not signed by the PCK, not bound to the attestation key,
+		/// or doesn't match `QEIdentity`
+		InvalidQEReport,
+		/// The identity of the Quoting Enclave is not set by the governance
+		QEIdentityNotSet,
+		/// The TCB level of the platform is below all the levels accepted for its model
+		TcbOutOfDate,
+		/// The PCK certificate of the platform is revoked
+		PCKCertRevoked,
+		/// The IAS report is not a JSON object with a base64 encoded quote body
+		InvalidIASReport,
 	}
 }
 
@@ -172,24 +644,98 @@ decl_module! {
 		type Error = Error<T>;
 		fn deposit_event() = default;
 
+		const RewardPerBlock: BalanceOf<T> = T::RewardPerBlock::get();
+		const RoundInterval: T::BlockNumber = T::RoundInterval::get();
+		const OfflineThreshold: T::BlockNumber = T::OfflineThreshold::get();
+		const HeartbeatMaxLag: T::BlockNumber = T::HeartbeatMaxLag::get();
+		const ChallengesPerRound: u32 = T::ChallengesPerRound::get();
+		const ChallengeDeadline: T::BlockNumber = T::ChallengeDeadline::get();
+		const MaxCommandSize: u32 = T::MaxCommandSize::get();
+		const CommandByteFee: BalanceOf<T> = T::CommandByteFee::get();
+		const ContractKeyGracePeriod: T::BlockNumber = T::ContractKeyGracePeriod::get();
+		const EgressSignatureTimeout: T::BlockNumber = T::EgressSignatureTimeout::get();
+		const LeaveCooldown: T::BlockNumber = T::LeaveCooldown::get();
+		const MaxReportAge: u64 = T::MaxReportAge::get();
+		const ReattestationInterval: u64 = T::ReattestationInterval::get();
+		const WorkerBond: BalanceOf<T> = T::WorkerBond::get();
+		const BondUnbondingPeriod: T::BlockNumber = T::BondUnbondingPeriod::get();
+
+		fn on_runtime_upgrade() -> Weight {
+			let version = StorageVersion::get();
+			let weight = match version {
+				Releases::V1_0_0 => migrations::migrate_from_v1::<T>(),
+				Releases::V2_0_0 => migrations::migrate_from_v2::<T>(),
+				Releases::V3_0_0 | Releases::V4_0_0 | Releases::V5_0_0 => migrations::migrate_from_v5::<T>(),
+				Releases::V6_0_0 => return T::DbWeight::get().reads(1),
+			};
+			// The quotes accepted before `V4_0_0` are stored with their timestamps
+			let weight = if version != Releases::V4_0_0 && version != Releases::V5_0_0 {
+				weight.saturating_add(migrations::migrate_from_v3::<T>())
+			} else {
+				weight
+			};
+			// The contracts assigned before `V5_0_0` are not indexed by their workers
+			let weight = if version != Releases::V5_0_0 {
+				weight.saturating_add(migrations::migrate_from_v4::<T>())
+			} else {
+				weight
+			};
+			StorageVersion::put(Releases::V6_0_0);
+			weight.saturating_add(T::DbWeight::get().reads_writes(1, 1))
+		}
+
+		fn on_initialize(now: T::BlockNumber) -> Weight {
+			let mut weight = Self::handle_missed_challenges(now)
+				.saturating_add(Self::handle_expired_egress(now))
+				.saturating_add(quota::prune_quota_usage::<T>(now));
+			// The round ends before the extrinsics of its last block are applied, so that the work
+			// going through all the workers is accounted in the weight of the block
+			if (now % T::RoundInterval::get()).is_zero() {
+				weight = weight
+					.saturating_add(Self::handle_offline_workers(now))
+					.saturating_add(Self::handle_round_ends())
+					.saturating_add(Self::handle_lapsed_attestations(now))
+					.saturating_add(Self::issue_challenges(now));
+			}
+			weight
+		}
+
 		// Messaging
 
-		#[weight = 0]
+		/// Push a command to a contract, paying `CommandByteFee` for each byte of the payload.
+		///
+		/// Each contract has its own command sequence, starting from 0.
+		#[weight = T::WeightInfo::push_command(payload.len() as u32)]
 		pub fn push_command(origin, contract_id: u32, payload: Vec<u8>) -> dispatch::DispatchResult {
 			let who = ensure_signed(origin)?;
+			ensure!(payload.len() <= T::MaxCommandSize::get() as usize, Error::<T>::CommandTooLarge);
+			let contract = Contracts::<T>::get(contract_id).ok_or(Error::<T>::InvalidContract)?;
+			ensure!(contract.status == ContractStatus::Active, Error::<T>::ContractNotActive);
+			// Charge the command fee
+			let len: BalanceOf<T> = (payload.len() as u32).into();
+			let fee = T::CommandByteFee::get().saturating_mul(len);
+			if !fee.is_zero() {
+				let imbalance = T::TEECurrency::withdraw(&who, fee, WithdrawReason::Fee.into(), KeepAlive)
+					.map_err(|_| Error::<T>::CannotPayCommandFee)?;
+				T::CommandFee::on_unbalanced(imbalance);
+			}
 			let num = Self::command_number().unwrap_or(0);
 			CommandNumber::put(num + 1);
-			Self::deposit_event(RawEvent::CommandPushed(who, contract_id, payload, num));
+			let sequence = ContractCommandNumber::get(contract_id);
+			ContractCommandNumber::insert(contract_id, sequence + 1);
+			Self::deposit_event(RawEvent::CommandPushed(who, contract_id, payload, sequence));
 			Ok(())
 		}
 
 		// Registry
 		/// Crerate a new stash or update an existing one.
-		#[weight = 0]
+		#[weight = T::WeightInfo::set_stash()]
 		pub fn set_stash(origin, controller: T::AccountId) -> dispatch::DispatchResult {
 			let who = ensure_signed(origin)?;
 			ensure!(!Stash::<T>::contains_key(&controller), Error::<T>::AlreadyPaired);
 			ensure!(!StashState::<T>::contains_key(&controller), Error::<T>::AlreadyBonded);
+			Self::ensure_cooled_down(StashCooldown::<T>::get(&who))?;
+			StashCooldown::<T>::remove(&who);
 			let stash_state = if StashState::<T>::contains_key(&who) {
 				// Remove previous controller
 				let prev = StashState::<T>::get(&who);
@@ -213,7 +759,7 @@ decl_module! {
 		}
 
 		/// Update the payout preferences. Must be called by the controller.
-		#[weight = 0]
+		#[weight = T::WeightInfo::set_payout_prefs()]
 		pub fn set_payout_prefs(origin, payout_commission: Option<u32>,
 							    payout_target: Option<T::AccountId>)
 						        -> dispatch::DispatchResult {
@@ -234,11 +780,12 @@ decl_module! {
 		}
 
 		/// Register a worker node with a valid Remote Attestation report
-		#[weight = 0]
+		#[weight = T::WeightInfo::register_worker()]
 		pub fn register_worker(origin, encoded_runtime_info: Vec<u8>, report: Vec<u8>, signature: Vec<u8>, raw_signing_cert: Vec<u8>) -> dispatch::DispatchResult {
 			let who = ensure_signed(origin)?;
 			ensure!(Stash::<T>::contains_key(&who), Error::<T>::NotController);
 			let stash = Stash::<T>::get(&who);
+			Self::ensure_bonded(&stash)?;
 			// Validate report
 			let sig_cert = webpki::EndEntityCert::from(&raw_signing_cert);
 			ensure!(sig_cert.is_ok(), Error::<T>::InvalidIASSigningCert);
@@ -248,128 +795,440 @@ decl_module! {
 				&report,
 				&signature
 			);
+			ensure!(verify_result.is_ok(), Error::<T>::InvalidIASReportSignature);
+			// Validate certificate chain against the trusted IAS roots
+			let ias_trust_anchors = IASTrustAnchors::get();
+			let trust_anchors: Vec<webpki::TrustAnchor> = ias_trust_anchors.iter()
+				.map(|anchor| webpki::TrustAnchor {
+					subject: &anchor.subject,
+					spki: &anchor.spki,
+					name_constraints: None,
+				})
+				.collect();
+			let chain: Vec<&[u8]> = Vec::new();
+			let now_func = webpki::Time::from_seconds_since_unix_epoch(T::UnixTime::now().as_secs());
+			let verify_result = sig_cert.verify_is_valid_tls_server_cert(
+				SUPPORTED_SIG_ALGS,
+				&webpki::TLSServerTrustAnchors(&trust_anchors),
+				&chain,
+				now_func
+			);
 			ensure!(verify_result.is_ok(), Error::<T>::InvalidIASSigningCert);
-			// TODO: Validate certificate
-			// let chain: Vec<&[u8]> = Vec::new();
-			// let now_func = webpki::Time::from_seconds_since_unix_epoch(1573419050);
-			// match sig_cert.verify_is_valid_tls_server_cert(
-			// 	SUPPORTED_SIG_ALGS,
-			// 	&IAS_SERVER_ROOTS,
-			// 	&chain,
-			// 	now_func
-			// ) {
-			// 	Ok(()) => (),
-			// 	Err(_) => panic!("verify cert failed")
-			// };
 
 			// Validate related fields
-			let parsed_report: serde_json_no_std::Value = serde_json_no_std::from_slice(&report).unwrap();
+			let parsed_report: serde_json_no_std::Value = serde_json_no_std::from_slice(&report)
+				.map_err(|_| Error::<T>::InvalidIASReport)?;
 			ensure!(
 				&parsed_report["isvEnclaveQuoteStatus"] == "OK" || &parsed_report["isvEnclaveQuoteStatus"] == "CONFIGURATION_NEEDED" || &parsed_report["isvEnclaveQuoteStatus"] == "GROUP_OUT_OF_DATE",
 				Error::<T>::InvalidQuoteStatus
 			);
+			// Validate the freshness of the report
+			let report_timestamp = parsed_report["timestamp"].as_str()
+				.and_then(parse_ias_timestamp)
+				.ok_or(Error::<T>::InvalidReportTimestamp)?;
+			let now_millis = T::UnixTime::now().as_millis().saturated_into::<u64>();
+			ensure!(now_millis.saturating_sub(report_timestamp) <= T::MaxReportAge::get(), Error::<T>::OutdatedReport);
 			// Extract quote fields
-			let raw_quote_body = parsed_report["isvEnclaveQuoteBody"].as_str().unwrap();
-			let quote_body = base64::decode(&raw_quote_body).unwrap();
-			// TODO: check the following fields
-			// let mr_enclave = &quote_body[112..143];
-			// let isv_prod_id = &quote_body[304..305];
-			// let isv_svn = &quote_body[306..307];
-			let report_data = &quote_body[368..432];
-			// Validate report data
+			let raw_quote_body = parsed_report["isvEnclaveQuoteBody"].as_str()
+				.ok_or(Error::<T>::InvalidIASReport)?;
+			let quote_body = base64::decode(&raw_quote_body).map_err(|_| Error::<T>::InvalidIASReport)?;
+			let isv_report = quote_body.get(48..)
+				.and_then(dcap::ReportBody::parse)
+				.ok_or(Error::<T>::InvalidQuoteStatus)?;
 			let runtime_info_hash = hashing::blake2_512(&encoded_runtime_info);
-			ensure!(runtime_info_hash.to_vec() == report_data, Error::<T>::InvalidRuntimeInfoHash);
-			let runtime_info = PRuntimeInfo::decode(&mut &encoded_runtime_info[..]).map_err(|_| Error::<T>::InvalidRuntimeInfo)?;
-			let machine_id = runtime_info.machine_id.to_vec();
-			// Add into the registry
-			// TODO: Now we just force remove the worker and thus stop the mining. Should we just
-			// update the worker info if there's an existing one?
-			let perv_worker_info = Self::remove_machine_if_present(&machine_id);
-			let last_updated = T::UnixTime::now().as_millis().saturated_into::<u64>();
-			let pubkey = runtime_info.pubkey.to_vec();
-			let score = Some(Score {
-				overall_score: calc_overall_score(&runtime_info.features).map_err(|()| Error::<T>::InvalidInput)?,
-				features: runtime_info.features
-			});
-			let worker_info = match perv_worker_info {
-				Some(info) => WorkerInfo {
-					pubkey,
-					last_updated,
-					score,
-					..info
-				},
-				None => WorkerInfo {
-					machine_id: machine_id.clone(),
-					pubkey,
-					last_updated,
-					score,
-					status: 0,
-				},
+			let (runtime_info, score) = Self::validate_attested_runtime(&encoded_runtime_info, &isv_report, &runtime_info_hash)?;
+			// Reject the replayed reports
+			let report_hash = sp_io::hashing::blake2_256(&report);
+			ensure!(!UsedReports::contains_key(&report_hash), Error::<T>::DuplicateReport);
+			Self::add_attested_worker(stash, runtime_info, isv_report.mr_enclave.to_vec(), score, now_millis);
+			UsedReports::insert(&report_hash, report_timestamp);
+			Ok(())
+		}
+
+		/// Register a worker node with an ECDSA quote of SGX DCAP. `pck_cert_chain` is the DER
+		/// encoded PCK certificate followed by the certificates of its issuers.
+		///
+		/// The chain is validated against `DCAPTrustAnchors`, the Quoting Enclave against
+		/// `QEIdentity`, and the TCB level of the platform against `TcbLevels`. The quote must be
+		/// bound to one of the last `BlockHashCount` blocks by its report data (`DcapReportData`).
+		#[weight = T::WeightInfo::register_worker_dcap()]
+		pub fn register_worker_dcap(origin, encoded_runtime_info: Vec<u8>, quote: Vec<u8>, pck_cert_chain: Vec<Vec<u8>>) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Stash::<T>::contains_key(&who), Error::<T>::NotController);
+			let stash = Stash::<T>::get(&who);
+			Self::ensure_bonded(&stash)?;
+			let quote = dcap::Quote::parse(&quote).ok_or(Error::<T>::InvalidDCAPQuote)?;
+			// Validate the PCK certificate chain against the trusted Intel roots
+			let (raw_pck_cert, intermediate_certs) = pck_cert_chain.split_first()
+				.ok_or(Error::<T>::InvalidPCKCertChain)?;
+			let pck_cert = webpki::EndEntityCert::from(raw_pck_cert)
+				.map_err(|_| Error::<T>::InvalidPCKCertChain)?;
+			let dcap_trust_anchors = DCAPTrustAnchors::get();
+			let trust_anchors: Vec<webpki::TrustAnchor> = dcap_trust_anchors.iter()
+				.map(|anchor| webpki::TrustAnchor {
+					subject: &anchor.subject,
+					spki: &anchor.spki,
+					name_constraints: None,
+				})
+				.collect();
+			let chain: Vec<&[u8]> = intermediate_certs.iter().map(|cert| cert.as_slice()).collect();
+			let now_func = webpki::Time::from_seconds_since_unix_epoch(T::UnixTime::now().as_secs());
+			// The PCK certificates have no extended key usage, which is accepted for any purpose
+			let verify_result = pck_cert.verify_is_valid_tls_client_cert(
+				DCAP_SIG_ALGS,
+				&webpki::TLSClientTrustAnchors(&trust_anchors),
+				&chain,
+				now_func
+			);
+			ensure!(verify_result.is_ok(), Error::<T>::InvalidPCKCertChain);
+			let platform = dcap::PckCert::parse(raw_pck_cert).ok_or(Error::<T>::InvalidPCKCertChain)?;
+			ensure!(!RevokedPCKCerts::get(platform.serial), Error::<T>::PCKCertRevoked);
+			// Validate the Quoting Enclave, which certifies the attestation key with its report
+			let verify_result = pck_cert.verify_signature(
+				&webpki::ECDSA_P256_SHA256,
+				quote.raw_qe_report,
+				&quote.qe_report_signature_der()
+			);
+			ensure!(verify_result.is_ok() && quote.is_attestation_key_bound(), Error::<T>::InvalidQEReport);
+			let qe_identity = QEIdentity::get().ok_or(Error::<T>::QEIdentityNotSet)?;
+			ensure!(
+				qe_identity.mr_signer == quote.qe_report.mr_signer &&
+				qe_identity.isv_prod_id == quote.qe_report.isv_prod_id &&
+				qe_identity.min_isv_svn <= quote.qe_report.isv_svn,
+				Error::<T>::InvalidQEReport
+			);
+			// Validate the TCB level of the platform
+			ensure!(
+				TcbLevels::get(platform.fmspc).iter().any(|level| level.is_reached_by(&platform.tcb)),
+				Error::<T>::TcbOutOfDate
+			);
+			// Validate the quote signed by the attestation key
+			ensure!(quote.verify_isv_signature(), Error::<T>::InvalidDCAPQuote);
+			// Validate the freshness of the quote by the block it's bound to
+			let anchor_number = DcapReportData::decode(&mut &quote.isv_report.report_data[..])
+				.map_err(|_| Error::<T>::InvalidRuntimeInfoHash)?
+				.block_number;
+			let anchor = T::BlockNumber::from(anchor_number);
+			let anchor_hash = <frame_system::Module<T>>::block_hash(anchor);
+			let now = <frame_system::Module<T>>::block_number();
+			ensure!(
+				anchor_hash != T::Hash::default() && anchor <= now && now - anchor <= T::BlockHashCount::get(),
+				Error::<T>::OutdatedReport
+			);
+			let mut bound_data = encoded_runtime_info.clone();
+			bound_data.extend(anchor_hash.encode());
+			let report_data = DcapReportData {
+				runtime_info_hash: sp_io::hashing::blake2_256(&bound_data),
+				block_number: anchor_number,
+				..Default::default()
 			};
-			WorkerState::<T>::insert(&stash, worker_info);
-			MachineOwner::<T>::insert(&machine_id, &stash);
-			Self::deposit_event(RawEvent::WorkerRegistered(stash, machine_id));
+			let (runtime_info, score) = Self::validate_attested_runtime(&encoded_runtime_info, &quote.isv_report, &report_data.encode())?;
+			// Reject the replayed quotes
+			let replay_key = quote.replay_key();
+			ensure!(!UsedQuotes::<T>::contains_key(&replay_key), Error::<T>::DuplicateReport);
+			let now_millis = T::UnixTime::now().as_millis().saturated_into::<u64>();
+			Self::add_attested_worker(stash, runtime_info, quote.isv_report.mr_enclave.to_vec(), score, now_millis);
+			UsedQuotes::<T>::insert(&replay_key, anchor);
 			Ok(())
 		}
 
-		#[weight = 0]
+		#[weight = T::WeightInfo::force_register_worker()]
 		fn force_register_worker(origin, stash: T::AccountId, machine_id: Vec<u8>, pubkey: Vec<u8>) -> dispatch::DispatchResult {
 			ensure_root(origin)?;
 			ensure!(StashState::<T>::contains_key(&stash), Error::<T>::StashNotFound);
-			Self::remove_machine_if_present(&machine_id);
+			if let Some((perv_stash, _)) = Self::remove_machine_if_present(&machine_id) {
+				Self::clear_liveness(&perv_stash);
+			}
+			Self::clear_liveness(&stash);
+			let features = vec![1, 4];
 			let worker_info = WorkerInfo {
 				machine_id: machine_id.clone(),
 				pubkey,
+				ecdh_pubkey: Vec::new(),
+				mr_enclave: Vec::new(),
 				last_updated: T::UnixTime::now().as_millis().saturated_into::<u64>(),
-				status: 0,
+				state: MinerState::Idle,
 				score: Some(Score {
-					overall_score: 100,
-					features: vec![1, 4]
+					overall_score: Self::scoring_params().overall_score(&features).unwrap_or_default(),
+					features,
 				}),
+				// Not attested at all, so it cannot be required to attest again
+				attestation_exempt: true,
 			};
 			WorkerState::<T>::insert(&stash, worker_info);
 			MachineOwner::<T>::insert(&machine_id, &stash);
+			Self::refresh_contract_ecdh_keys(&stash);
 			Self::deposit_event(RawEvent::WorkerRegistered(stash, machine_id));
 			Ok(())
 		}
 
-		#[weight = 0]
+		/// Remove the worker of the stash from the registry. Must be called by the controller.
+		///
+		/// The worker must not be mining, and the machine cannot be registered again within
+		/// `LeaveCooldown` blocks.
+		#[weight = T::WeightInfo::unregister_worker()]
+		fn unregister_worker(origin) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Stash::<T>::contains_key(&who), Error::<T>::ControllerNotFound);
+			let stash = Stash::<T>::get(&who);
+			ensure!(WorkerState::<T>::contains_key(&stash), Error::<T>::MinerNotFound);
+			Self::do_unregister_worker(&stash)
+		}
+
+		/// Dissolve the stash and its controller, and unregister its worker if any. Must be called
+		/// by the stash.
+		///
+		/// The pending reward must be claimed first, and the stash cannot be set up again within
+		/// `LeaveCooldown` blocks.
+		#[weight = T::WeightInfo::remove_stash()]
+		fn remove_stash(origin) -> dispatch::DispatchResult {
+			let stash = ensure_signed(origin)?;
+			ensure!(StashState::<T>::contains_key(&stash), Error::<T>::NotStash);
+			ensure!(PendingRewards::<T>::get(&stash).is_zero(), Error::<T>::PendingRewardNotClaimed);
+			if WorkerState::<T>::contains_key(&stash) {
+				Self::do_unregister_worker(&stash)?;
+			}
+			let stash_info = StashState::<T>::take(&stash);
+			Stash::<T>::remove(&stash_info.controller);
+			PendingRewards::<T>::remove(&stash);
+			Self::start_unbonding(&stash);
+			let now = <frame_system::Module<T>>::block_number();
+			StashCooldown::<T>::insert(&stash, now.saturating_add(T::LeaveCooldown::get()));
+			T::OnStashRemoved::on_stash_removed(&stash, &stash_info.controller);
+			Self::deposit_event(RawEvent::StashRemoved(stash));
+			Ok(())
+		}
+
+		/// Reserve more funds of the stash as its worker bond. Must be called by the stash.
+		///
+		/// At least `WorkerBond` must be bonded to register a worker or start mining. The bond
+		/// starts unbonding when the worker is unregistered or the stash is removed.
+		#[weight = T::WeightInfo::bond_worker()]
+		fn bond_worker(origin, #[compact] amount: BalanceOf<T>) -> dispatch::DispatchResult {
+			let stash = ensure_signed(origin)?;
+			ensure!(StashState::<T>::contains_key(&stash), Error::<T>::NotStash);
+			ensure!(!amount.is_zero(), Error::<T>::InvalidInput);
+			T::TEECurrency::reserve(&stash, amount).map_err(|_| Error::<T>::CannotReserveBond)?;
+			WorkerBonds::<T>::mutate(&stash, |bond| *bond = bond.saturating_add(amount));
+			Self::deposit_event(RawEvent::WorkerBonded(stash, amount));
+			Ok(())
+		}
+
+		/// Release the unbonded worker bond to the stash once `BondUnbondingPeriod` blocks passed
+		/// since the unbonding started. Must be called by the stash.
+		#[weight = T::WeightInfo::withdraw_unbonded()]
+		fn withdraw_unbonded(origin) -> dispatch::DispatchResult {
+			let stash = ensure_signed(origin)?;
+			let (amount, expiry) = UnbondingBonds::<T>::get(&stash).ok_or(Error::<T>::NoUnbondingBond)?;
+			ensure!(<frame_system::Module<T>>::block_number() > expiry, Error::<T>::StillUnbonding);
+			UnbondingBonds::<T>::remove(&stash);
+			T::TEECurrency::unreserve(&stash, amount);
+			Self::deposit_event(RawEvent::BondWithdrawn(stash, amount));
+			Ok(())
+		}
+
+		#[weight = T::WeightInfo::force_set_contract_key()]
 		fn force_set_contract_key(origin, id: u32, pubkey: Vec<u8>) -> dispatch::DispatchResult {
 			ensure_root(origin)?;
 			ContractKey::insert(id, pubkey);
 			Ok(())
 		}
 
+		/// Add a root certificate to validate the IAS report signing certificate against
+		#[weight = T::WeightInfo::force_add_ias_trust_anchor()]
+		fn force_add_ias_trust_anchor(origin, subject: Vec<u8>, spki: Vec<u8>) -> dispatch::DispatchResult {
+			ensure_root(origin)?;
+			let mut trust_anchors = IASTrustAnchors::get();
+			ensure!(!trust_anchors.iter().any(|anchor| anchor.spki == spki), Error::<T>::IASTrustAnchorExists);
+			trust_anchors.push(IASTrustAnchor { subject, spki });
+			IASTrustAnchors::put(trust_anchors);
+			Ok(())
+		}
+
+		/// Remove a root certificate (identified by its public key) from the IAS trust anchors
+		#[weight = T::WeightInfo::force_remove_ias_trust_anchor()]
+		fn force_remove_ias_trust_anchor(origin, spki: Vec<u8>) -> dispatch::DispatchResult {
+			ensure_root(origin)?;
+			let mut trust_anchors = IASTrustAnchors::get();
+			let len = trust_anchors.len();
+			trust_anchors.retain(|anchor| anchor.spki != spki);
+			ensure!(trust_anchors.len() < len, Error::<T>::IASTrustAnchorNotFound);
+			IASTrustAnchors::put(trust_anchors);
+			Ok(())
+		}
+
+		/// Add a root certificate to validate the PCK certificate chain of the DCAP quotes against
+		#[weight = T::WeightInfo::force_add_dcap_trust_anchor()]
+		fn force_add_dcap_trust_anchor(origin, subject: Vec<u8>, spki: Vec<u8>) -> dispatch::DispatchResult {
+			ensure_root(origin)?;
+			let mut trust_anchors = DCAPTrustAnchors::get();
+			ensure!(!trust_anchors.iter().any(|anchor| anchor.spki == spki), Error::<T>::DCAPTrustAnchorExists);
+			trust_anchors.push(DCAPTrustAnchor { subject, spki });
+			DCAPTrustAnchors::put(trust_anchors);
+			Ok(())
+		}
+
+		/// Remove a root certificate (identified by its public key) from the DCAP trust anchors
+		#[weight = T::WeightInfo::force_remove_dcap_trust_anchor()]
+		fn force_remove_dcap_trust_anchor(origin, spki: Vec<u8>) -> dispatch::DispatchResult {
+			ensure_root(origin)?;
+			let mut trust_anchors = DCAPTrustAnchors::get();
+			let len = trust_anchors.len();
+			trust_anchors.retain(|anchor| anchor.spki != spki);
+			ensure!(trust_anchors.len() < len, Error::<T>::DCAPTrustAnchorNotFound);
+			DCAPTrustAnchors::put(trust_anchors);
+			Ok(())
+		}
+
+		/// Set the allowed measurement of the Quoting Enclave signing the DCAP quotes, as
+		/// published in the QE identity collateral of Intel
+		#[weight = T::WeightInfo::set_qe_identity()]
+		fn set_qe_identity(origin, mr_signer: Vec<u8>, isv_prod_id: u16, min_isv_svn: u16) -> dispatch::DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			ensure!(mr_signer.len() == 32, Error::<T>::InvalidInput);
+			QEIdentity::put(EnclaveMeasurement {
+				mr_signer,
+				isv_prod_id,
+				min_isv_svn,
+			});
+			Self::deposit_event(RawEvent::QEIdentitySet);
+			Ok(())
+		}
+
+		/// Allow an enclave build (identified by its MRENCLAVE) to register as a worker, or update
+		/// its policy if it's already in the whitelist
+		#[weight = T::WeightInfo::add_enclave_measurement()]
+		fn add_enclave_measurement(origin, mr_enclave: Vec<u8>, mr_signer: Vec<u8>, isv_prod_id: u16, min_isv_svn: u16) -> dispatch::DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			ensure!(mr_enclave.len() == 32 && mr_signer.len() == 32, Error::<T>::InvalidInput);
+			EnclaveWhitelist::insert(&mr_enclave, EnclaveMeasurement {
+				mr_signer,
+				isv_prod_id,
+				min_isv_svn,
+			});
+			Self::deposit_event(RawEvent::EnclaveMeasurementAdded(mr_enclave));
+			Ok(())
+		}
+
+		/// Remove an enclave build from the whitelist. Workers already registered with the build
+		/// are evicted if `evict_workers` is set.
+		///
+		/// `max_workers` is an upper bound of the number of registered workers, used to calculate
+		/// the weight of the eviction.
+		#[weight = T::WeightInfo::revoke_enclave_measurement(if *evict_workers { *max_workers } else { 0 })]
+		fn revoke_enclave_measurement(origin, mr_enclave: Vec<u8>, evict_workers: bool, #[compact] max_workers: u32) -> dispatch::DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			ensure!(EnclaveWhitelist::contains_key(&mr_enclave), Error::<T>::EnclaveMeasurementNotFound);
+			let mut stashes: Vec<T::AccountId> = Vec::new();
+			if evict_workers {
+				let mut num_workers: u32 = 0;
+				stashes = WorkerState::<T>::iter()
+					.inspect(|_| num_workers += 1)
+					.filter(|(_, worker_info)| {
+						worker_info.mr_enclave == mr_enclave && worker_info.state != MinerState::Evicted
+					})
+					.map(|(stash, _)| stash)
+					.collect();
+				ensure!(num_workers <= max_workers, Error::<T>::InvalidInput);
+			}
+			EnclaveWhitelist::remove(&mr_enclave);
+			for stash in stashes.iter() {
+				Self::evict_worker(stash);
+			}
+			Self::deposit_event(RawEvent::EnclaveMeasurementRevoked(mr_enclave));
+			Ok(())
+		}
+
+		/// Update the parameters of the scoring formula, and rescore all the registered workers
+		/// by the features they reported.
+		///
+		/// `max_workers` is an upper bound of the number of registered workers, used to calculate
+		/// the weight of the rescoring.
+		#[weight = T::WeightInfo::set_scoring_params(*max_workers)]
+		fn set_scoring_params(origin, params: ScoringParams, #[compact] max_workers: u32) -> dispatch::DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			let mut num_workers: u32 = 0;
+			let rescored: Vec<(T::AccountId, WorkerInfo)> = WorkerState::<T>::iter()
+				.inspect(|_| num_workers += 1)
+				.filter_map(|(stash, mut worker_info)| {
+					let score = worker_info.score.as_mut()?;
+					score.overall_score = params.overall_score(&score.features)?;
+					Some((stash, worker_info))
+				})
+				.collect();
+			ensure!(num_workers <= max_workers, Error::<T>::InvalidInput);
+			for (stash, worker_info) in rescored {
+				WorkerState::<T>::insert(&stash, worker_info);
+			}
+			WorkerScoringParams::put(params);
+			Self::deposit_event(RawEvent::ScoringParamsSet(num_workers));
+			Ok(())
+		}
+
 		// Mining
 
-		#[weight = 0]
+		/// Start mining with the registered worker. The worker must be idle or offline.
+		#[weight = T::WeightInfo::start_mine()]
 		fn start_mine(origin) -> dispatch::DispatchResult {
 			let who = ensure_signed(origin)?;
 			ensure!(Stash::<T>::contains_key(&who), Error::<T>::ControllerNotFound);
 			let stash = Stash::<T>::get(who);
-			WorkerState::<T>::mutate(&stash, |worker_info| worker_info.status = 1);
+			ensure!(WorkerState::<T>::contains_key(&stash), Error::<T>::MinerNotFound);
+			let mut worker_info = WorkerState::<T>::get(&stash);
+			ensure!(
+				worker_info.state == MinerState::Idle || worker_info.state == MinerState::Offline,
+				Error::<T>::InvalidMinerState
+			);
+			Self::ensure_bonded(&stash)?;
+			worker_info.state = MinerState::Mining;
+			WorkerState::<T>::insert(&stash, worker_info);
+			// Start the liveness window from now
+			let now = <frame_system::Module<T>>::block_number();
+			let reported = LastHeartbeat::<T>::get(&stash).map(|(_, block_num)| block_num).unwrap_or(0);
+			LastHeartbeat::<T>::insert(&stash, (now, reported));
 			Ok(())
 		}
 
-		#[weight = 0]
+		/// Stop mining. A mining worker keeps mining until the end of the current round, while an
+		/// offline worker becomes idle immediately.
+		#[weight = T::WeightInfo::stop_mine()]
 		fn stop_mine(origin) -> dispatch::DispatchResult {
 			let who = ensure_signed(origin)?;
 			ensure!(Stash::<T>::contains_key(&who), Error::<T>::ControllerNotFound);
 			let stash = Stash::<T>::get(who);
-			WorkerState::<T>::mutate(&stash, |worker_info| worker_info.status = 0);
+			ensure!(WorkerState::<T>::contains_key(&stash), Error::<T>::MinerNotFound);
+			let mut worker_info = WorkerState::<T>::get(&stash);
+			worker_info.state = match worker_info.state {
+				MinerState::Mining => MinerState::PendingStop,
+				MinerState::Offline => MinerState::Idle,
+				_ => return Err(Error::<T>::InvalidMinerState.into()),
+			};
+			WorkerState::<T>::insert(&stash, worker_info);
 			Ok(())
 		}
 
-		#[weight = 0]
+		/// Pay out the pending reward of a stash according to its payout preferences. The
+		/// commission goes to the payout target and the rest goes to the stash.
+		#[weight = T::WeightInfo::claim_reward()]
 		fn claim_reward(origin, stash: T::AccountId) -> dispatch::DispatchResult {
 			ensure_signed(origin)?;
 			// invoked by anyone
+			ensure!(StashState::<T>::contains_key(&stash), Error::<T>::StashNotFound);
+			let amount = PendingRewards::<T>::take(&stash);
+			ensure!(!amount.is_zero(), Error::<T>::NoPendingReward);
+			let payout_prefs = StashState::<T>::get(&stash).payout_prefs;
+			let to_target = Perbill::from_percent(payout_prefs.commission) * amount;
+			let to_stash = amount - to_target;
+			// Rewards are minted from the void
+			drop(T::TEECurrency::deposit_creating(&payout_prefs.target, to_target));
+			drop(T::TEECurrency::deposit_creating(&stash, to_stash));
+			Self::deposit_event(RawEvent::RewardPaid(stash, payout_prefs.target, to_target, to_stash));
 			Ok(())
 		}
 
 		// Token
 
-		#[weight = 0]
+		#[weight = T::WeightInfo::transfer_to_tee()]
 		fn transfer_to_tee(origin, #[compact] amount: BalanceOf<T>) -> dispatch::DispatchResult {
 			let who = ensure_signed(origin)?;
 			T::TEECurrency::transfer(&who, &Self::account_id(), amount, AllowDeath)
@@ -378,36 +1237,293 @@ decl_module! {
 			Ok(())
 		}
 
-		#[weight = 0]
+		#[weight = T::WeightInfo::transfer_to_chain()]
 		fn transfer_to_chain(origin, data: Vec<u8>) -> dispatch::DispatchResult {
 			// This is a specialized Contract-to-Chain message passing where the confidential
-			// contract is always Balances (id = 2)
+			// contract is always Balances (id = 2). New contracts should use `push_egress_message`.
 			// Anyone can call this method. As long as the message meets all the requirements
 			// (signature, sequence id, etc), it's considered as a valid message.
-			const CONTRACT_ID: u32 = 2;
 			ensure_signed(origin)?;
-			let transfer_data: TransferData<<T as frame_system::Trait>::AccountId, BalanceOf<T>>
-				= Decode::decode(&mut &data[..]).map_err(|_| Error::<T>::InvalidInput)?;
+			Self::apply_transfer_to_chain(&data)
+		}
+
+		/// Submit a contiguous run of `transfer_to_chain` messages starting from the next
+		/// sequence. The messages are applied atomically: if any of them fails, none is applied.
+		///
+		/// Each message uses the call quota of a `transfer_to_chain`, so a batch can't exceed
+		/// `MaxCallsPerWindow` messages.
+		#[weight = T::WeightInfo::transfer_to_chain_batch(messages.len() as u32)]
+		#[transactional]
+		fn transfer_to_chain_batch(origin, messages: Vec<Vec<u8>>) -> dispatch::DispatchResult {
+			ensure_signed(origin)?;
+			ensure!(!messages.is_empty(), Error::<T>::InvalidInput);
+			for data in messages.iter() {
+				Self::apply_transfer_to_chain(data)?;
+			}
+			Ok(())
+		}
+
+		/// Submit a signed message from a confidential contract. The message is routed to the
+		/// egress handler registered for the contract.
+		///
+		/// Anyone can call this method. As long as the message meets all the requirements
+		/// (signature, sequence id, etc), it's considered as a valid message.
+		#[weight = T::WeightInfo::push_egress_message(data.len() as u32)]
+		fn push_egress_message(origin, data: Vec<u8>) -> dispatch::DispatchResult {
+			ensure_signed(origin)?;
+			let message: SignedEgressMessage = Decode::decode(&mut &data[..])
+				.map_err(|_| Error::<T>::InvalidInput)?;
+			let contract_id = message.data.contract_id;
+			ensure!(!EgressThreshold::contains_key(contract_id), Error::<T>::EgressThresholdRequired);
+			let handler = EgressHandlers::get(contract_id).ok_or(Error::<T>::EgressHandlerNotFound)?;
 			// Check sequence
-			let sequence = IngressSequence::get(CONTRACT_ID);
-			ensure!(transfer_data.data.sequence == sequence + 1, Error::<T>::BadMessageSequence);
-			// Contract key
-			ensure!(ContractKey::contains_key(CONTRACT_ID), Error::<T>::InvalidContract);
-			let pubkey = ContractKey::get(CONTRACT_ID);
+			let sequence = IngressSequence::get(contract_id);
+			ensure!(message.data.sequence == sequence + 1, Error::<T>::BadMessageSequence);
 			// Validate TEE signature
-			Self::verify_signature(&pubkey, &transfer_data)?;
-			// Release funds
-			T::TEECurrency::transfer(
-				&Self::account_id(), &transfer_data.data.dest, transfer_data.data.amount,
-				AllowDeath)
-				.map_err(|_| Error::<T>::CannotWithdraw)?;
-			// Announce the successful execution
-			IngressSequence::insert(CONTRACT_ID, sequence + 1);
-			Self::deposit_event(RawEvent::TransferToChain(transfer_data.data.dest.encode(), transfer_data.data.amount, sequence + 1));
+			Self::verify_contract_signature(contract_id, &message)?;
+			// Apply the side effects
+			Self::handle_egress_message(handler, contract_id, sequence + 1, &message.data.payload)?;
+			IngressSequence::insert(contract_id, sequence + 1);
+			Self::deposit_event(RawEvent::EgressMessageHandled(contract_id, sequence + 1));
+			Ok(())
+		}
+
+		/// Register (or unregister with `None`) the egress handler of a contract
+		#[weight = T::WeightInfo::set_egress_handler()]
+		fn set_egress_handler(origin, contract_id: u32, handler: Option<EgressHandler>) -> dispatch::DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			match handler {
+				Some(handler) => EgressHandlers::insert(contract_id, handler),
+				None => EgressHandlers::remove(contract_id),
+			}
+			Self::deposit_event(RawEvent::EgressHandlerSet(contract_id));
+			Ok(())
+		}
+
+		/// Require (or stop requiring with `None`) the egress messages of a contract to be signed
+		/// by `threshold` distinct workers assigned to the contract, instead of the contract key.
+		#[weight = T::WeightInfo::set_egress_threshold()]
+		fn set_egress_threshold(origin, contract_id: u32, threshold: Option<u32>) -> dispatch::DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			match threshold {
+				Some(threshold) => {
+					ensure!(threshold > 0, Error::<T>::InvalidInput);
+					EgressThreshold::insert(contract_id, threshold);
+				},
+				None => {
+					EgressThreshold::remove(contract_id);
+					PendingEgress::<T>::remove_prefix(contract_id);
+				},
+			}
+			Self::deposit_event(RawEvent::EgressThresholdSet(contract_id));
+			Ok(())
+		}
+
+		/// Sign an egress message of a contract with the identity key of a worker assigned to the
+		/// contract. Must be called by the controller of the worker.
+		///
+		/// The message is routed to the egress handler once it's signed by `EgressThreshold`
+		/// distinct workers. The signatures expire after `EgressSignatureTimeout` blocks.
+		///
+		/// `max_signers` is an upper bound of the number of workers already signed the message,
+		/// used to calculate the weight.
+		#[weight = T::WeightInfo::sign_egress_message(data.len() as u32, *max_signers)]
+		fn sign_egress_message(origin, data: Vec<u8>, #[compact] max_signers: u32) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Stash::<T>::contains_key(&who), Error::<T>::ControllerNotFound);
+			let stash = Stash::<T>::get(&who);
+			ensure!(WorkerState::<T>::contains_key(&stash), Error::<T>::MinerNotFound);
+			let message: SignedEgressMessage = Decode::decode(&mut &data[..])
+				.map_err(|_| Error::<T>::InvalidInput)?;
+			let contract_id = message.data.contract_id;
+			let threshold = EgressThreshold::get(contract_id).ok_or(Error::<T>::EgressThresholdNotSet)?;
+			let handler = EgressHandlers::get(contract_id).ok_or(Error::<T>::EgressHandlerNotFound)?;
+			ensure!(ContractAssign::<T>::get(contract_id).contains(&stash), Error::<T>::WorkerNotAssigned);
+			// Check sequence
+			let sequence = IngressSequence::get(contract_id);
+			ensure!(message.data.sequence == sequence + 1, Error::<T>::BadMessageSequence);
+			// Validate the signature of the worker identity key
+			let worker_info = WorkerState::<T>::get(&stash);
+			ensure!(worker_info.state != MinerState::Evicted, Error::<T>::InvalidMinerState);
+			Self::verify_signature(&worker_info.pubkey, &message)?;
+			// Accumulate the signature
+			let hash = T::Hashing::hash_of(&message.data);
+			let now = <frame_system::Module<T>>::block_number();
+			let mut pending = PendingEgress::<T>::get(contract_id, hash)
+				.unwrap_or_else(|| PendingEgressMessage {
+					message: message.data,
+					signers: Vec::new(),
+					expiry: now.saturating_add(T::EgressSignatureTimeout::get()),
+				});
+			ensure!(pending.signers.len() as u32 <= max_signers, Error::<T>::InvalidInput);
+			ensure!(!pending.signers.contains(&stash), Error::<T>::DuplicateEgressSignature);
+			pending.signers.push(stash.clone());
+			if (pending.signers.len() as u32) < threshold {
+				PendingEgress::<T>::insert(contract_id, hash, pending);
+				Self::deposit_event(RawEvent::EgressMessageSigned(contract_id, sequence + 1, stash));
+				return Ok(());
+			}
+			// Enough signatures collected. The other candidates of the same sequence are dropped.
+			Self::handle_egress_message(handler, contract_id, sequence + 1, &pending.message.payload)?;
+			PendingEgress::<T>::remove_prefix(contract_id);
+			IngressSequence::insert(contract_id, sequence + 1);
+			Self::deposit_event(RawEvent::EgressMessageHandled(contract_id, sequence + 1));
+			Ok(())
+		}
+
+		/// Report a worker signed two different egress messages of the same contract and
+		/// sequence. Can be called by anyone.
+		///
+		/// The whole bond of the worker, including the funds being unbonded, is slashed and the
+		/// worker is evicted. If the worker runs a validator, the offence is also reported to
+		/// slash the validator and its nominators, with the caller as the reporter.
+		#[weight = T::WeightInfo::report_egress_equivocation((first.len() + second.len()) as u32)]
+		fn report_egress_equivocation(origin, stash: T::AccountId, first: Vec<u8>, second: Vec<u8>) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(WorkerState::<T>::contains_key(&stash), Error::<T>::MinerNotFound);
+			let first: SignedEgressMessage = Decode::decode(&mut &first[..])
+				.map_err(|_| Error::<T>::InvalidInput)?;
+			let second: SignedEgressMessage = Decode::decode(&mut &second[..])
+				.map_err(|_| Error::<T>::InvalidInput)?;
+			let contract_id = first.data.contract_id;
+			let sequence = first.data.sequence;
+			ensure!(
+				second.data.contract_id == contract_id && second.data.sequence == sequence &&
+				second.data.payload != first.data.payload,
+				Error::<T>::InvalidEquivocationProof
+			);
+			ensure!(
+				!ReportedEquivocations::<T>::get(&stash, (contract_id, sequence)),
+				Error::<T>::DuplicateEquivocationReport
+			);
+			// Both messages must be signed by the identity key of the worker
+			let worker_info = WorkerState::<T>::get(&stash);
+			Self::verify_signature(&worker_info.pubkey, &first)
+				.and_then(|_| Self::verify_signature(&worker_info.pubkey, &second))
+				.map_err(|_| Error::<T>::InvalidEquivocationProof)?;
+			ReportedEquivocations::<T>::insert(&stash, (contract_id, sequence), true);
+			let slashed = Self::slash_bond(&stash);
+			if worker_info.state != MinerState::Evicted {
+				Self::evict_worker(&stash);
+			}
+			if let Some(offender) = Self::offending_validators(&[stash.clone()]).pop() {
+				let (session_index, validator_set_count) = T::IdentifyValidator::current_session();
+				Self::report_offence(vec![who], EgressEquivocationOffence {
+					session_index,
+					validator_set_count,
+					message: (contract_id, sequence),
+					offender,
+				});
+			}
+			Self::deposit_event(RawEvent::EquivocationSlashed(stash, contract_id, sequence, slashed));
 			Ok(())
 		}
 
-		#[weight = 0]
+		// Contract registry
+
+		/// Register a confidential contract. Its public key is used to verify the messages sent
+		/// from the contract.
+		#[weight = T::WeightInfo::register_contract()]
+		fn register_contract(origin, contract_id: u32, owner: T::AccountId, code_hash: T::Hash, pubkey: Vec<u8>) -> dispatch::DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			ensure!(!Contracts::<T>::contains_key(contract_id), Error::<T>::ContractExists);
+			ensure!(pubkey.len() == 33, Error::<T>::InvalidPubKey);
+			Contracts::<T>::insert(contract_id, ContractInfo {
+				owner: owner.clone(),
+				code_hash,
+				pubkey: pubkey.clone(),
+				status: ContractStatus::Active,
+			});
+			ContractKey::insert(contract_id, pubkey);
+			Self::deposit_event(RawEvent::ContractRegistered(contract_id, owner));
+			Ok(())
+		}
+
+		/// Assign an active contract to the workers of the given stashes, replacing the workers
+		/// previously assigned. Their ECDH public keys are published to encrypt the commands.
+		#[weight = T::WeightInfo::assign_contract(workers.len() as u32)]
+		fn assign_contract(origin, contract_id: u32, workers: Vec<T::AccountId>) -> dispatch::DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			let contract = Contracts::<T>::get(contract_id).ok_or(Error::<T>::ContractNotFound)?;
+			ensure!(contract.status == ContractStatus::Active, Error::<T>::ContractNotActive);
+			let mut machine_ids = Vec::new();
+			for stash in workers.iter() {
+				ensure!(WorkerState::<T>::contains_key(stash), Error::<T>::MinerNotFound);
+				let worker_info = WorkerState::<T>::get(stash);
+				ensure!(worker_info.state != MinerState::Evicted, Error::<T>::InvalidMinerState);
+				machine_ids.push(worker_info.machine_id);
+			}
+			Self::unassign_contract(contract_id);
+			for stash in workers.iter() {
+				WorkerContracts::<T>::mutate(stash, |contracts| if !contracts.contains(&contract_id) {
+					contracts.push(contract_id);
+				});
+			}
+			ContractEcdhKeys::insert(contract_id, Self::ecdh_keys_of(&workers));
+			ContractAssign::<T>::insert(contract_id, workers);
+			Self::deposit_event(RawEvent::ContractAssigned(contract_id, machine_ids));
+			Ok(())
+		}
+
+		/// Retire a contract so that it doesn't accept commands anymore. Can be called by the
+		/// owner of the contract or the governance.
+		#[weight = T::WeightInfo::retire_contract()]
+		fn retire_contract(origin, contract_id: u32) -> dispatch::DispatchResult {
+			let mut contract = Contracts::<T>::get(contract_id).ok_or(Error::<T>::ContractNotFound)?;
+			if let Err(origin) = T::GovernanceOrigin::try_origin(origin) {
+				let who = ensure_signed(origin)?;
+				ensure!(who == contract.owner, Error::<T>::NotContractOwner);
+			}
+			ensure!(contract.status == ContractStatus::Active, Error::<T>::ContractNotActive);
+			contract.status = ContractStatus::Retired;
+			Contracts::<T>::insert(contract_id, contract);
+			Self::unassign_contract(contract_id);
+			ContractEcdhKeys::remove(contract_id);
+			Self::deposit_event(RawEvent::ContractRetired(contract_id));
+			Ok(())
+		}
+
+		/// Publish a new key of a contract, generated and signed by a worker assigned to the
+		/// contract. Must be called by the controller of the worker.
+		///
+		/// The replaced key stays valid for `ContractKeyGracePeriod` blocks, so that the egress
+		/// messages signed by it can still be accepted.
+		#[weight = T::WeightInfo::rotate_contract_key()]
+		fn rotate_contract_key(origin, data: Vec<u8>) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Stash::<T>::contains_key(&who), Error::<T>::ControllerNotFound);
+			let stash = Stash::<T>::get(&who);
+			ensure!(WorkerState::<T>::contains_key(&stash), Error::<T>::MinerNotFound);
+			let rotation: SignedContractKeyRotation = Decode::decode(&mut &data[..])
+				.map_err(|_| Error::<T>::InvalidInput)?;
+			let contract_id = rotation.data.contract_id;
+			let mut contract = Contracts::<T>::get(contract_id).ok_or(Error::<T>::ContractNotFound)?;
+			ensure!(contract.status == ContractStatus::Active, Error::<T>::ContractNotActive);
+			ensure!(ContractAssign::<T>::get(contract_id).contains(&stash), Error::<T>::WorkerNotAssigned);
+			// Validate the signature of the worker identity key
+			let worker_info = WorkerState::<T>::get(&stash);
+			ensure!(worker_info.state != MinerState::Evicted, Error::<T>::InvalidMinerState);
+			Self::verify_signature(&worker_info.pubkey, &rotation)?;
+			// Check sequence
+			let sequence = ContractKeySequence::get(contract_id);
+			ensure!(rotation.data.sequence == sequence + 1, Error::<T>::BadMessageSequence);
+			let pubkey = rotation.data.pubkey;
+			ensure!(pubkey.len() == 33, Error::<T>::InvalidPubKey);
+			// Keep the previous key valid for a while
+			if ContractKey::contains_key(contract_id) {
+				let now = <frame_system::Module<T>>::block_number();
+				let expiry = now.saturating_add(T::ContractKeyGracePeriod::get());
+				PreviousContractKey::<T>::insert(contract_id, (ContractKey::get(contract_id), expiry));
+			}
+			ContractKey::insert(contract_id, &pubkey);
+			ContractKeySequence::insert(contract_id, sequence + 1);
+			contract.pubkey = pubkey.clone();
+			Contracts::<T>::insert(contract_id, contract);
+			Self::deposit_event(RawEvent::ContractKeyRotated(contract_id, pubkey));
+			Ok(())
+		}
+
+		#[weight = T::WeightInfo::heartbeat()]
 		fn heartbeat(origin, data: Vec<u8>) -> dispatch::DispatchResult {
 			let who = ensure_signed(origin)?;
 			// Decode payload
@@ -415,14 +1531,91 @@ decl_module! {
 			// Get identity key from controller
 			ensure!(Stash::<T>::contains_key(&who), Error::<T>::ControllerNotFound);
 			let stash = Stash::<T>::get(&who);
+			ensure!(WorkerState::<T>::contains_key(&stash), Error::<T>::MinerNotFound);
 			let worker_info = WorkerState::<T>::get(&stash);
+			ensure!(worker_info.state != MinerState::Evicted, Error::<T>::InvalidMinerState);
 			// Validate TEE signature
 			Self::verify_signature(&worker_info.pubkey, &heartbeat_data)?;
+			// Validate the reported block number
+			let block_num = heartbeat_data.data.block_num;
+			if let Some((_, last_block_num)) = LastHeartbeat::<T>::get(&stash) {
+				ensure!(block_num >= last_block_num, Error::<T>::StaleHeartbeat);
+			}
+			let now = <frame_system::Module<T>>::block_number();
+			let reported = T::BlockNumber::from(block_num);
+			ensure!(
+				reported <= now && now - reported <= T::HeartbeatMaxLag::get(),
+				Error::<T>::HeartbeatOutOfRange
+			);
+			LastHeartbeat::<T>::insert(&stash, (now, block_num));
+			// Mark the worker as online in this round
+			if worker_info.state.is_mining() {
+				RoundHeartbeats::<T>::mutate(&stash, |num| *num += 1);
+			}
 			// Emit event
 			Self::deposit_event(RawEvent::Heartbeat(stash, heartbeat_data.data.block_num));
 			Ok(())
 		}
 
+		/// Answer the challenge of the worker with the response signed by its pRuntime. Must be
+		/// called by the controller before the deadline.
+		///
+		/// The response must carry the block the challenge was issued in, proving pRuntime has
+		/// synced the chain to it.
+		#[weight = T::WeightInfo::respond_challenge()]
+		fn respond_challenge(origin, data: Vec<u8>) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Stash::<T>::contains_key(&who), Error::<T>::ControllerNotFound);
+			let stash = Stash::<T>::get(&who);
+			let challenge = Challenges::<T>::get(&stash).ok_or(Error::<T>::ChallengeNotFound)?;
+			let response: SignedChallengeResponse = Decode::decode(&mut &data[..])
+				.map_err(|_| Error::<T>::InvalidInput)?;
+			ensure!(
+				response.data.nonce == challenge.nonce &&
+				T::BlockNumber::from(response.data.block_num) == challenge.issued_at,
+				Error::<T>::InvalidChallengeResponse
+			);
+			// Validate TEE signature
+			let worker_info = WorkerState::<T>::get(&stash);
+			Self::verify_signature(&worker_info.pubkey, &response)?;
+			Challenges::<T>::remove(&stash);
+			Self::deposit_event(RawEvent::ChallengeAnswered(stash));
+			Ok(())
+		}
+
+		// Attestation, continued. Placed last to keep the indices of the calls above, which
+		// pRuntime decodes from the blocks.
+
+		/// Set the TCB levels accepted for a platform model (identified by its 6 bytes FMSPC), as
+		/// published in the TCB info collateral of Intel. An empty list rejects the whole model.
+		///
+		/// Only the platforms registering afterwards are checked. The workers already registered
+		/// have to attest again within `ReattestationInterval`.
+		#[weight = T::WeightInfo::set_tcb_levels(levels.len() as u32)]
+		fn set_tcb_levels(origin, fmspc: Vec<u8>, levels: Vec<TcbLevel>) -> dispatch::DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			ensure!(fmspc.len() == 6, Error::<T>::InvalidInput);
+			if levels.is_empty() {
+				TcbLevels::remove(&fmspc);
+			} else {
+				TcbLevels::insert(&fmspc, levels);
+			}
+			Self::deposit_event(RawEvent::TcbLevelsSet(fmspc));
+			Ok(())
+		}
+
+		/// Revoke the PCK certificates by their serial numbers, as listed in the PCK CRL of Intel.
+		/// The platforms certified by them cannot register with DCAP quotes anymore.
+		#[weight = T::WeightInfo::revoke_pck_certs(serials.len() as u32)]
+		fn revoke_pck_certs(origin, serials: Vec<Vec<u8>>) -> dispatch::DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			for serial in serials.iter() {
+				RevokedPCKCerts::insert(serial, true);
+			}
+			Self::deposit_event(RawEvent::PCKCertsRevoked(serials.len() as u32));
+			Ok(())
+		}
+
 		// Borrowing
 	}
 }
@@ -450,24 +1643,590 @@ impl<T: Trait> Module<T> {
 		Ok(())
 	}
 
+	/// Verify the signature of a message from a contract with its current key, or the previous
+	/// key if it's still in the grace period
+	pub fn verify_contract_signature(contract_id: u32, data: &impl SignedDataType<Vec<u8>>) -> dispatch::DispatchResult {
+		ensure!(ContractKey::contains_key(contract_id), Error::<T>::InvalidContract);
+		let result = Self::verify_signature(&ContractKey::get(contract_id), data);
+		if result.is_ok() {
+			return result;
+		}
+		match PreviousContractKey::<T>::get(contract_id) {
+			Some((pubkey, expiry)) if <frame_system::Module<T>>::block_number() <= expiry =>
+				Self::verify_signature(&pubkey, data),
+			_ => result,
+		}
+	}
+
+	/// Verify a `TransferData` message from the Balances contract and release the funds
+	fn apply_transfer_to_chain(data: &[u8]) -> dispatch::DispatchResult {
+		const CONTRACT_ID: u32 = 2;
+		ensure!(!EgressThreshold::contains_key(CONTRACT_ID), Error::<T>::EgressThresholdRequired);
+		let transfer_data: TransferData<<T as frame_system::Trait>::AccountId, BalanceOf<T>>
+			= Decode::decode(&mut &data[..]).map_err(|_| Error::<T>::InvalidInput)?;
+		// Check sequence
+		let sequence = IngressSequence::get(CONTRACT_ID);
+		ensure!(transfer_data.data.sequence == sequence + 1, Error::<T>::BadMessageSequence);
+		// Validate TEE signature
+		Self::verify_contract_signature(CONTRACT_ID, &transfer_data)?;
+		// Release funds
+		Self::release_balance(&transfer_data.data.dest, transfer_data.data.amount, sequence + 1)?;
+		// Announce the successful execution
+		IngressSequence::insert(CONTRACT_ID, sequence + 1);
+		Ok(())
+	}
+
+	/// Decode the payload of an egress message and apply it with the handler
+	fn handle_egress_message(handler: EgressHandler, contract_id: u32, sequence: u64, payload: &[u8]) -> dispatch::DispatchResult {
+		match handler {
+			EgressHandler::Balance => {
+				let release: BalanceRelease<T::AccountId, BalanceOf<T>> = Decode::decode(&mut &payload[..])
+					.map_err(|_| Error::<T>::InvalidMessage)?;
+				Self::release_balance(&release.dest, release.amount, sequence)
+			},
+			EgressHandler::Asset => {
+				let release: AssetRelease<T::AccountId, BalanceOf<T>> = Decode::decode(&mut &payload[..])
+					.map_err(|_| Error::<T>::InvalidMessage)?;
+				Self::deposit_event(RawEvent::AssetReleased(contract_id, release.asset_id, release.dest, release.amount));
+				Ok(())
+			},
+			EgressHandler::Event => {
+				Self::deposit_event(RawEvent::ContractEvent(contract_id, payload.to_vec()));
+				Ok(())
+			},
+		}
+	}
+
+	/// Release the native tokens deposited to pRuntime by `transfer_to_tee`
+	fn release_balance(dest: &T::AccountId, amount: BalanceOf<T>, sequence: u64) -> dispatch::DispatchResult {
+		T::TEECurrency::transfer(&Self::account_id(), dest, amount, AllowDeath)
+			.map_err(|_| Error::<T>::CannotWithdraw)?;
+		Self::deposit_event(RawEvent::TransferToChain(dest.encode(), amount, sequence));
+		Ok(())
+	}
+
+	/// Validate the enclave measurement of an attested report and the runtime info bound to it by
+	/// the expected `report_data`, and check the machine is not in the cooldown. Returns the
+	/// runtime info and its score.
+	fn validate_attested_runtime(encoded_runtime_info: &[u8], isv_report: &dcap::ReportBody, report_data: &[u8]) -> Result<(PRuntimeInfo, Score), dispatch::DispatchError> {
+		// Validate enclave measurement
+		let measurement = EnclaveWhitelist::get(isv_report.mr_enclave).ok_or(Error::<T>::EnclaveNotWhitelisted)?;
+		ensure!(
+			measurement.mr_signer == isv_report.mr_signer &&
+			measurement.isv_prod_id == isv_report.isv_prod_id &&
+			measurement.min_isv_svn <= isv_report.isv_svn,
+			Error::<T>::EnclaveNotWhitelisted
+		);
+		// Validate report data
+		ensure!(report_data == isv_report.report_data, Error::<T>::InvalidRuntimeInfoHash);
+		let runtime_info = PRuntimeInfo::decode(&mut &encoded_runtime_info[..]).map_err(|_| Error::<T>::InvalidRuntimeInfo)?;
+		ensure!(
+			runtime_info.version < 3 || runtime_info.ecdh_pubkey.len() == types::ECDH_PUBKEY_LEN,
+			Error::<T>::InvalidRuntimeInfo
+		);
+		Self::ensure_cooled_down(MachineCooldown::<T>::get(runtime_info.machine_id.to_vec()))?;
+		let overall_score = Self::scoring_params().overall_score(&runtime_info.features)
+			.ok_or(Error::<T>::InvalidRuntimeInfo)?;
+		let score = Score {
+			overall_score,
+			features: runtime_info.features.clone(),
+		};
+		Ok((runtime_info, score))
+	}
+
+	/// Add an attested worker into the registry, replacing the existing worker of the machine
+	fn add_attested_worker(stash: T::AccountId, runtime_info: PRuntimeInfo, mr_enclave: Vec<u8>, score: Score, now_millis: u64) {
+		let machine_id = runtime_info.machine_id.to_vec();
+		MachineCooldown::<T>::remove(&machine_id);
+		// The worker registered again by the same stash keeps its mining state, while a machine
+		// moving to another stash starts over
+		let perv_worker_info = match Self::remove_machine_if_present(&machine_id) {
+			Some((perv_stash, info)) if perv_stash == stash => Some(info),
+			Some((perv_stash, _)) => {
+				Self::clear_liveness(&perv_stash);
+				None
+			},
+			None => None,
+		};
+		let pubkey = runtime_info.pubkey.to_vec();
+		let ecdh_pubkey = runtime_info.ecdh_pubkey;
+		let score = Some(score);
+		let worker_info = match perv_worker_info {
+			Some(info) => WorkerInfo {
+				pubkey,
+				ecdh_pubkey,
+				mr_enclave,
+				last_updated: now_millis,
+				score,
+				attestation_exempt: false,
+				..info
+			},
+			None => WorkerInfo {
+				machine_id: machine_id.clone(),
+				pubkey,
+				ecdh_pubkey,
+				mr_enclave,
+				last_updated: now_millis,
+				score,
+				state: MinerState::Idle,
+				attestation_exempt: false,
+			},
+		};
+		// A mining worker attested again keeps its heartbeats
+		if !worker_info.state.is_mining() {
+			Self::clear_liveness(&stash);
+		}
+		WorkerState::<T>::insert(&stash, worker_info);
+		MachineOwner::<T>::insert(&machine_id, &stash);
+		Self::refresh_contract_ecdh_keys(&stash);
+		Self::deposit_event(RawEvent::WorkerRegistered(stash, machine_id));
+	}
+
 	/// Try to remove a registered worker from the registry by its `machine_id` identity if
-	/// presents, keeping the stash untouched
-	fn remove_machine_if_present(machine_id: &Vec<u8>) -> Option<WorkerInfo> {
+	/// presents, keeping the stash untouched. Returns the stash and the removed worker.
+	///
+	/// The liveness records of the stash are kept, as the worker stays mining if the same stash
+	/// registers the machine again. The caller must drop them with `clear_liveness` otherwise.
+	fn remove_machine_if_present(machine_id: &Vec<u8>) -> Option<(T::AccountId, WorkerInfo)> {
 		if !MachineOwner::<T>::contains_key(machine_id) {
 			return None;
 		}
 		let stash = MachineOwner::<T>::take(machine_id);
 		let worker_info = WorkerState::<T>::take(&stash);
-		Self::deposit_event(RawEvent::WorkerUnregistered(stash, machine_id.clone()));
-		Some(worker_info)
+		Self::deposit_event(RawEvent::WorkerUnregistered(stash.clone(), machine_id.clone()));
+		Some((stash, worker_info))
+	}
+
+	/// Forget the heartbeats of the worker of a stash, once the worker stopped mining or left the
+	/// registry. The pending challenge is kept, so that it's still counted as missed if not
+	/// answered before the deadline.
+	fn clear_liveness(stash: &T::AccountId) {
+		LastHeartbeat::<T>::remove(stash);
+		RoundHeartbeats::<T>::remove(stash);
+	}
+
+	/// Remove the worker of a stash from the registry and start the cooldown of its machine. The
+	/// worker must not be mining.
+	fn do_unregister_worker(stash: &T::AccountId) -> dispatch::DispatchResult {
+		let worker_info = WorkerState::<T>::get(stash);
+		ensure!(!worker_info.state.is_mining(), Error::<T>::InvalidMinerState);
+		WorkerState::<T>::remove(stash);
+		Self::clear_liveness(stash);
+		let machine_id = worker_info.machine_id;
+		// An evicted machine is not owned by the stash anymore
+		if MachineOwner::<T>::contains_key(&machine_id) && &MachineOwner::<T>::get(&machine_id) == stash {
+			MachineOwner::<T>::remove(&machine_id);
+			let now = <frame_system::Module<T>>::block_number();
+			MachineCooldown::<T>::insert(&machine_id, now.saturating_add(T::LeaveCooldown::get()));
+		}
+		Self::refresh_contract_ecdh_keys(stash);
+		Self::deposit_event(RawEvent::WorkerUnregistered(stash.clone(), machine_id));
+		Self::start_unbonding(stash);
+		Ok(())
+	}
+
+	/// Ensure the stash bonded at least `WorkerBond`
+	fn ensure_bonded(stash: &T::AccountId) -> dispatch::DispatchResult {
+		ensure!(WorkerBonds::<T>::get(stash) >= T::WorkerBond::get(), Error::<T>::InsufficientBond);
+		Ok(())
+	}
+
+	/// Move the worker bond of a stash to unbonding, restarting the unbonding period of the funds
+	/// already being unbonded
+	fn start_unbonding(stash: &T::AccountId) {
+		let bond = WorkerBonds::<T>::take(stash);
+		if bond.is_zero() {
+			return;
+		}
+		let unbonding = UnbondingBonds::<T>::get(stash).map(|(amount, _)| amount).unwrap_or_else(Zero::zero);
+		let now = <frame_system::Module<T>>::block_number();
+		let expiry = now.saturating_add(T::BondUnbondingPeriod::get());
+		UnbondingBonds::<T>::insert(stash, (unbonding.saturating_add(bond), expiry));
+		Self::deposit_event(RawEvent::WorkerUnbonded(stash.clone(), bond));
+	}
+
+	/// Slash the whole bond of a stash, including the funds being unbonded. Returns the slashed
+	/// amount.
+	fn slash_bond(stash: &T::AccountId) -> BalanceOf<T> {
+		let bond = WorkerBonds::<T>::take(stash);
+		let unbonding = UnbondingBonds::<T>::take(stash).map(|(amount, _)| amount).unwrap_or_else(Zero::zero);
+		let (imbalance, _) = T::TEECurrency::slash_reserved(stash, bond.saturating_add(unbonding));
+		let slashed = imbalance.peek();
+		T::Slash::on_unbalanced(imbalance);
+		slashed
+	}
+
+	/// The validators run by the workers of the given stashes. The workers not running an active
+	/// validator are skipped.
+	fn offending_validators(stashes: &[T::AccountId]) -> Vec<T::ValidatorIdentification> {
+		stashes.iter()
+			.filter(|stash| StashState::<T>::contains_key(stash))
+			.filter_map(|stash| T::IdentifyValidator::identify_validator(&StashState::<T>::get(stash).controller))
+			.collect()
+	}
+
+	/// Report an offence of the workers to `T::ReportOffence`
+	fn report_offence<O>(reporters: Vec<T::AccountId>, offence: O) where
+		O: Offence<T::ValidatorIdentification>,
+		T::ReportOffence: ReportOffence<T::AccountId, T::ValidatorIdentification, O>,
+	{
+		if let Err(e) = <T::ReportOffence as ReportOffence<_, _, O>>::report_offence(reporters, offence) {
+			sp_runtime::print(e);
+		}
+	}
+
+	/// Ensure the cooldown of a stash or a machine which left the registry is over
+	fn ensure_cooled_down(expiry: Option<T::BlockNumber>) -> dispatch::DispatchResult {
+		if let Some(expiry) = expiry {
+			ensure!(<frame_system::Module<T>>::block_number() > expiry, Error::<T>::InCooldown);
+		}
+		Ok(())
+	}
+
+	/// Distribute the reward of the ending round to the mining workers that sent heartbeats in the
+	/// round, weighted by their overall score. The workers pending to stop become idle afterwards.
+	fn handle_round_ends() -> Weight {
+		let round = Round::get();
+		let mut drained: Weight = 0;
+		let online_workers: Vec<(T::AccountId, u32)> = RoundHeartbeats::<T>::drain()
+			.filter_map(|(stash, _)| {
+				drained += 1;
+				let worker_info = WorkerState::<T>::get(&stash);
+				if !worker_info.state.is_mining() {
+					return None;
+				}
+				match worker_info.score {
+					Some(score) if score.overall_score > 0 => Some((stash, score.overall_score)),
+					_ => None,
+				}
+			})
+			.collect();
+		let total_score: u64 = online_workers.iter().map(|(_, score)| *score as u64).sum();
+		let blocks: BalanceOf<T> = T::RoundInterval::get().saturated_into::<u32>().into();
+		let round_reward = T::RewardPerBlock::get().saturating_mul(blocks);
+		let mut total_reward: BalanceOf<T> = Zero::zero();
+		let rewarded = online_workers.len() as Weight;
+		for (stash, score) in online_workers {
+			let amount = Perbill::from_rational_approximation(score as u64, total_score) * round_reward;
+			PendingRewards::<T>::mutate(&stash, |pending| *pending = pending.saturating_add(amount));
+			total_reward = total_reward.saturating_add(amount);
+			Self::deposit_event(RawEvent::RewardAccrued(stash, amount));
+		}
+		let mut workers: Weight = 0;
+		let stopping_workers: Vec<T::AccountId> = WorkerState::<T>::iter()
+			.inspect(|_| workers += 1)
+			.filter(|(_, worker_info)| worker_info.state == MinerState::PendingStop)
+			.map(|(stash, _)| stash)
+			.collect();
+		for stash in stopping_workers.iter() {
+			WorkerState::<T>::mutate(stash, |worker_info| worker_info.state = MinerState::Idle);
+		}
+		Round::put(round + 1);
+		Self::deposit_event(RawEvent::RoundEnded(round, total_reward));
+		let stopping = stopping_workers.len() as Weight;
+		T::DbWeight::get().reads_writes(
+			1 + drained * 2 + rewarded + workers + stopping,
+			drained + rewarded + stopping + 1,
+		)
+	}
+
+	/// Evict the mining workers not attested again within `ReattestationInterval`, and forget the
+	/// reports and quotes too old to be accepted anyway. The workers exempted from the attestation
+	/// are skipped.
+	fn handle_lapsed_attestations(block_number: T::BlockNumber) -> Weight {
+		let now = T::UnixTime::now().as_millis().saturated_into::<u64>();
+		let mut reads: Weight = 1;
+		let lapsed_workers: Vec<T::AccountId> = WorkerState::<T>::iter()
+			.filter(|(_, worker_info)| {
+				reads += 1;
+				worker_info.state.is_mining() &&
+				!worker_info.attestation_exempt &&
+				now.saturating_sub(worker_info.last_updated) > T::ReattestationInterval::get()
+			})
+			.map(|(stash, _)| stash)
+			.collect();
+		for stash in lapsed_workers.iter() {
+			Self::evict_worker(stash);
+		}
+		let outdated_reports: Vec<[u8; 32]> = UsedReports::iter()
+			.filter(|(_, timestamp)| {
+				reads += 1;
+				now.saturating_sub(*timestamp) > T::MaxReportAge::get()
+			})
+			.map(|(hash, _)| hash)
+			.collect();
+		for hash in outdated_reports.iter() {
+			UsedReports::remove(hash);
+		}
+		let outdated_quotes: Vec<[u8; 32]> = UsedQuotes::<T>::iter()
+			.filter(|(_, anchor)| {
+				reads += 1;
+				block_number.saturating_sub(*anchor) > T::BlockHashCount::get()
+			})
+			.map(|(replay_key, _)| replay_key)
+			.collect();
+		for replay_key in outdated_quotes.iter() {
+			UsedQuotes::<T>::remove(replay_key);
+		}
+		let lapsed = lapsed_workers.len() as Weight;
+		let outdated = (outdated_reports.len() + outdated_quotes.len()) as Weight;
+		T::DbWeight::get().reads_writes(reads + lapsed, lapsed * 5 + outdated)
+	}
+
+	/// Stop the mining workers which haven't sent heartbeats within `OfflineThreshold` blocks and
+	/// report the offence of those running validators
+	fn handle_offline_workers(now: T::BlockNumber) -> Weight {
+		let threshold = T::OfflineThreshold::get();
+		let mut reads: Weight = 0;
+		let offline_workers: Vec<T::AccountId> = LastHeartbeat::<T>::iter()
+			.filter(|(stash, (last_seen, _))| {
+				reads += 1;
+				now.saturating_sub(*last_seen) > threshold && WorkerState::<T>::get(stash).state.is_mining()
+			})
+			.map(|(stash, _)| stash)
+			.collect();
+		let now_millis = T::UnixTime::now().as_millis().saturated_into::<u64>();
+		for stash in offline_workers.iter() {
+			WorkerState::<T>::mutate(stash, |worker_info| worker_info.state = MinerState::Offline);
+			RoundHeartbeats::<T>::remove(stash);
+			LastOffline::<T>::insert(stash, now_millis);
+			Self::deposit_event(RawEvent::WorkerOffline(stash.clone()));
+		}
+		let offenders = Self::offending_validators(&offline_workers);
+		if !offenders.is_empty() {
+			let (session_index, validator_set_count) = T::IdentifyValidator::current_session();
+			Self::report_offence(Vec::new(), WorkerOfflineOffence {
+				session_index,
+				validator_set_count,
+				block_number: now,
+				offenders,
+			});
+		}
+		let offline = offline_workers.len() as Weight;
+		T::DbWeight::get().reads_writes(reads * 2 + offline * 2, offline * 3)
+	}
+
+	/// Challenge a random subset of the mining workers without a pending challenge. Each of them
+	/// has to answer within `ChallengeDeadline` blocks.
+	fn issue_challenges(now: T::BlockNumber) -> Weight {
+		let seed = T::Randomness::random(b"phala_challenge");
+		let mut reads: Weight = 1;
+		let mut candidates: Vec<([u8; 32], T::AccountId, Vec<u8>)> = WorkerState::<T>::iter()
+			.filter(|(stash, worker_info)| {
+				reads += 2;
+				worker_info.state == MinerState::Mining && !Challenges::<T>::contains_key(stash)
+			})
+			.map(|(stash, worker_info)| {
+				let nonce = sp_io::hashing::blake2_256(&(seed, &stash).encode());
+				(nonce, stash, worker_info.machine_id)
+			})
+			.collect();
+		// Sorting by the random nonces shuffles the workers
+		candidates.sort_by(|a, b| a.0.cmp(&b.0));
+		let deadline = now.saturating_add(T::ChallengeDeadline::get());
+		let mut issued: Weight = 0;
+		for (nonce, stash, machine_id) in candidates.into_iter().take(T::ChallengesPerRound::get() as usize) {
+			Challenges::<T>::insert(&stash, Challenge {
+				nonce,
+				issued_at: now,
+				deadline,
+			});
+			issued += 1;
+			Self::deposit_event(RawEvent::ChallengeIssued(stash, machine_id, nonce));
+		}
+		T::DbWeight::get().reads_writes(reads, issued)
+	}
+
+	/// Stop the mining workers which didn't answer their challenges before the deadline and report
+	/// the offence of those running validators
+	fn handle_missed_challenges(now: T::BlockNumber) -> Weight {
+		let mut reads: Weight = 0;
+		let missed: Vec<T::AccountId> = Challenges::<T>::iter()
+			.filter(|(_, challenge)| {
+				reads += 1;
+				now > challenge.deadline
+			})
+			.map(|(stash, _)| stash)
+			.collect();
+		let now_millis = T::UnixTime::now().as_millis().saturated_into::<u64>();
+		for stash in missed.iter() {
+			Challenges::<T>::remove(stash);
+			MissedChallenges::<T>::mutate(stash, |num| *num += 1);
+			if WorkerState::<T>::get(stash).state.is_mining() {
+				WorkerState::<T>::mutate(stash, |worker_info| worker_info.state = MinerState::Offline);
+				RoundHeartbeats::<T>::remove(stash);
+				LastOffline::<T>::insert(stash, now_millis);
+			}
+			Self::deposit_event(RawEvent::ChallengeMissed(stash.clone()));
+		}
+		let offenders = Self::offending_validators(&missed);
+		if !offenders.is_empty() {
+			let (session_index, validator_set_count) = T::IdentifyValidator::current_session();
+			Self::report_offence(Vec::new(), ChallengeMissedOffence {
+				session_index,
+				validator_set_count,
+				block_number: now,
+				offenders,
+			});
+		}
+		let missed = missed.len() as Weight;
+		T::DbWeight::get().reads_writes(reads + missed * 3, missed * 5)
+	}
+
+	/// Drop the egress messages failed to collect enough signatures before their expiry
+	fn handle_expired_egress(now: T::BlockNumber) -> Weight {
+		let mut reads: Weight = 0;
+		let expired: Vec<(u32, T::Hash, u64)> = PendingEgress::<T>::iter()
+			.filter(|(_, _, pending)| {
+				reads += 1;
+				now > pending.expiry
+			})
+			.map(|(contract_id, hash, pending)| (contract_id, hash, pending.message.sequence))
+			.collect();
+		for (contract_id, hash, sequence) in expired.iter() {
+			PendingEgress::<T>::remove(contract_id, hash);
+			Self::deposit_event(RawEvent::EgressMessageExpired(*contract_id, *sequence));
+		}
+		T::DbWeight::get().reads_writes(reads, expired.len() as Weight)
+	}
+
+	/// Forcibly remove the worker of a stash from the registry, keeping the stash untouched. The
+	/// worker info is kept in the `Evicted` state until the stash registers a worker again.
+	fn evict_worker(stash: &T::AccountId) {
+		let mut worker_info = WorkerState::<T>::get(stash);
+		worker_info.state = MinerState::Evicted;
+		WorkerState::<T>::insert(stash, &worker_info);
+		MachineOwner::<T>::remove(&worker_info.machine_id);
+		Self::clear_liveness(stash);
+		Self::refresh_contract_ecdh_keys(stash);
+		Self::deposit_event(RawEvent::WorkerEvicted(stash.clone(), worker_info.machine_id));
+	}
+
+	/// The ECDH public keys of the given workers, skipping the evicted ones and those without a
+	/// key
+	fn ecdh_keys_of(workers: &[T::AccountId]) -> Vec<Vec<u8>> {
+		workers.iter()
+			.filter(|stash| WorkerState::<T>::contains_key(stash))
+			.map(|stash| WorkerState::<T>::get(stash))
+			.filter(|worker_info| worker_info.state != MinerState::Evicted && !worker_info.ecdh_pubkey.is_empty())
+			.map(|worker_info| worker_info.ecdh_pubkey)
+			.collect()
+	}
+
+	/// Update the ECDH public keys of the contracts assigned to the worker of a stash after the
+	/// worker changed
+	fn refresh_contract_ecdh_keys(stash: &T::AccountId) {
+		for contract_id in WorkerContracts::<T>::get(stash) {
+			let workers = ContractAssign::<T>::get(contract_id);
+			ContractEcdhKeys::insert(contract_id, Self::ecdh_keys_of(&workers));
+		}
+	}
+
+	/// Remove the workers assigned to a contract, and the contract from their `WorkerContracts`
+	fn unassign_contract(contract_id: u32) {
+		for stash in ContractAssign::<T>::take(contract_id) {
+			let mut contracts = WorkerContracts::<T>::get(&stash);
+			contracts.retain(|id| *id != contract_id);
+			if contracts.is_empty() {
+				WorkerContracts::<T>::remove(&stash);
+			} else {
+				WorkerContracts::<T>::insert(&stash, contracts);
+			}
+		}
+	}
+
+	// Queries for the runtime API
+
+	/// Get the worker registered by a stash
+	pub fn worker_info(stash: &T::AccountId) -> Option<WorkerInfo> {
+		if !WorkerState::<T>::contains_key(stash) {
+			return None;
+		}
+		Some(WorkerState::<T>::get(stash))
+	}
+
+	/// Get the stash and the worker of a registered machine
+	pub fn worker_info_by_machine_id(machine_id: &Vec<u8>) -> Option<(T::AccountId, WorkerInfo)> {
+		if !MachineOwner::<T>::contains_key(machine_id) {
+			return None;
+		}
+		let stash = MachineOwner::<T>::get(machine_id);
+		let worker_info = WorkerState::<T>::get(&stash);
+		Some((stash, worker_info))
+	}
+
+	/// Get the stash info if the account is a stash
+	pub fn stash_info(stash: &T::AccountId) -> Option<StashInfo<T::AccountId>> {
+		if !StashState::<T>::contains_key(stash) {
+			return None;
+		}
+		Some(StashState::<T>::get(stash))
+	}
+
+	/// Get the public key of a contract if it has any
+	pub fn contract_pubkey(contract_id: u32) -> Option<Vec<u8>> {
+		if !ContractKey::contains_key(contract_id) {
+			return None;
+		}
+		Some(ContractKey::get(contract_id))
+	}
+
+	/// Get the overall score of the worker run by a controller if it's healthy, i.e. mining and not
+	/// gone offline since `since` (in milliseconds)
+	pub fn healthy_miner_score(controller: &T::AccountId, since: u64) -> Option<u32> {
+		if !Stash::<T>::contains_key(controller) {
+			return None;
+		}
+		let stash = Stash::<T>::get(controller);
+		let worker_info = Self::worker_info(&stash)?;
+		if worker_info.state != MinerState::Mining {
+			return None;
+		}
+		if LastOffline::<T>::get(&stash).map_or(false, |offline_at| offline_at >= since) {
+			return None;
+		}
+		worker_info.score.map(|score| score.overall_score)
+	}
+
+	/// Get the mining state of the worker registered by a stash
+	pub fn mining_status(stash: &T::AccountId) -> Option<MiningStatus<T::BlockNumber, BalanceOf<T>>> {
+		let worker_info = Self::worker_info(stash)?;
+		Some(MiningStatus {
+			state: worker_info.state,
+			last_heartbeat: LastHeartbeat::<T>::get(stash),
+			round_heartbeats: RoundHeartbeats::<T>::get(stash),
+			pending_reward: PendingRewards::<T>::get(stash),
+		})
 	}
 }
 
-fn calc_overall_score(features: &Vec<u32>) -> Result<u32, ()> {
-	if features.len() != 2 {
-		return Err(())
+/// Parse the timestamp of an IAS report (`YYYY-MM-DDThh:mm:ss[.ffffff]` in UTC) to milliseconds
+/// since the UNIX epoch
+fn parse_ias_timestamp(timestamp: &str) -> Option<u64> {
+	let bytes = timestamp.as_bytes();
+	if bytes.len() < 19 || bytes[4] != b'-' || bytes[7] != b'-' || bytes[10] != b'T' ||
+		bytes[13] != b':' || bytes[16] != b':' {
+		return None;
+	}
+	let field = |start: usize, end: usize| -> Option<u64> {
+		let digits = &bytes[start..end];
+		if !digits.iter().all(|b| b.is_ascii_digit()) {
+			return None;
+		}
+		Some(digits.iter().fold(0u64, |acc, b| acc * 10 + (b - b'0') as u64))
+	};
+	let (year, month, day) = (field(0, 4)?, field(5, 7)?, field(8, 10)?);
+	let (hour, minute, second) = (field(11, 13)?, field(14, 16)?, field(17, 19)?);
+	if year < 1970 || month < 1 || month > 12 || day < 1 || day > 31 ||
+		hour > 23 || minute > 59 || second > 60 {
+		return None;
 	}
-	let core = features[0];
-	let feature_level = features[1];
-	Ok(core * (feature_level * 10 + 60))
+	// Days since the epoch of the civil date, with March as the first month of the year
+	let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+	let era = y / 400;
+	let yoe = y - era * 400;
+	let doy = (153 * m + 2) / 5 + day - 1;
+	let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+	let days = (era * 146097 + doe).checked_sub(719468)?;
+	Some((((days * 24 + hour) * 60 + minute) * 60 + second) * 1000)
 }
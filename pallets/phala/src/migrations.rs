@@ -0,0 +1,138 @@
+use sp_std::{prelude::*, cell::Cell};
+use codec::{Encode, Decode};
+use frame_support::{weights::Weight, storage::IterableStorageMap, traits::Get};
+
+use crate::{Trait, WorkerState, UsedQuotes, ContractAssign, WorkerContracts, types::{WorkerInfo, MinerState, Score}};
+
+/// `WorkerInfo` as stored before `Releases::V2_0_0`, without the enclave measurement and with the
+/// mining state as a bare integer
+#[derive(Encode, Decode)]
+pub struct OldWorkerInfo {
+	pub machine_id: Vec<u8>,
+	pub pubkey: Vec<u8>,
+	pub last_updated: u64,
+	/// 1 if mining, otherwise 0
+	pub status: i32,
+	pub score: Option<Score>
+}
+
+/// `WorkerInfo` as stored in `Releases::V2_0_0`, without the ECDH public key
+#[derive(Encode, Decode)]
+pub struct WorkerInfoV2 {
+	pub machine_id: Vec<u8>,
+	pub pubkey: Vec<u8>,
+	pub mr_enclave: Vec<u8>,
+	pub last_updated: u64,
+	pub state: MinerState,
+	pub score: Option<Score>
+}
+
+/// `WorkerInfo` as stored from `Releases::V3_0_0` to `Releases::V5_0_0`, without the attestation
+/// exemption
+#[derive(Encode, Decode)]
+pub struct WorkerInfoV5 {
+	pub machine_id: Vec<u8>,
+	pub pubkey: Vec<u8>,
+	pub ecdh_pubkey: Vec<u8>,
+	pub mr_enclave: Vec<u8>,
+	pub last_updated: u64,
+	pub state: MinerState,
+	pub score: Option<Score>
+}
+
+/// Convert the integer mining status of all the workers to `MinerState`. The enclave measurement
+/// and the ECDH public key of the existing workers are unknown and left empty until they register
+/// again. None of them is exempted from the attestation.
+pub fn migrate_from_v1<T: Trait>() -> Weight {
+	let translated: Cell<Weight> = Cell::new(0);
+	WorkerState::<T>::translate::<OldWorkerInfo, _>(|_, old| {
+		translated.set(translated.get() + 1);
+		let state = match old.status {
+			1 => MinerState::Mining,
+			_ => MinerState::Idle,
+		};
+		Some(WorkerInfo {
+			machine_id: old.machine_id,
+			pubkey: old.pubkey,
+			ecdh_pubkey: Vec::new(),
+			mr_enclave: Vec::new(),
+			last_updated: old.last_updated,
+			state,
+			score: old.score,
+			attestation_exempt: false,
+		})
+	});
+	let translated = translated.get();
+	T::DbWeight::get().reads_writes(translated, translated)
+}
+
+/// Add the ECDH public key to all the workers. It's left empty until they register again. None of
+/// them is exempted from the attestation.
+pub fn migrate_from_v2<T: Trait>() -> Weight {
+	let translated: Cell<Weight> = Cell::new(0);
+	WorkerState::<T>::translate::<WorkerInfoV2, _>(|_, old| {
+		translated.set(translated.get() + 1);
+		Some(WorkerInfo {
+			machine_id: old.machine_id,
+			pubkey: old.pubkey,
+			ecdh_pubkey: Vec::new(),
+			mr_enclave: old.mr_enclave,
+			last_updated: old.last_updated,
+			state: old.state,
+			score: old.score,
+			attestation_exempt: false,
+		})
+	});
+	let translated = translated.get();
+	T::DbWeight::get().reads_writes(translated, translated)
+}
+
+/// Forget the DCAP quotes accepted before they were bound to a recent block. They were stored with
+/// the time they were accepted, and cannot pass the freshness check anyway.
+pub fn migrate_from_v3<T: Trait>() -> Weight {
+	let removed: Cell<Weight> = Cell::new(0);
+	UsedQuotes::<T>::translate::<u64, _>(|_, _| {
+		removed.set(removed.get() + 1);
+		None
+	});
+	let removed = removed.get();
+	T::DbWeight::get().reads_writes(removed, removed)
+}
+
+/// Index the assigned contracts by their workers in `WorkerContracts`
+pub fn migrate_from_v4<T: Trait>() -> Weight {
+	let mut contracts: Weight = 0;
+	let mut indexed: Weight = 0;
+	for (contract_id, workers) in ContractAssign::<T>::iter() {
+		contracts += 1;
+		for stash in workers.iter() {
+			indexed += 1;
+			WorkerContracts::<T>::mutate(stash, |ids| if !ids.contains(&contract_id) {
+				ids.push(contract_id);
+			});
+		}
+	}
+	T::DbWeight::get().reads_writes(contracts + indexed, indexed)
+}
+
+/// Exempt the workers without an enclave measurement from the attestation. These are the workers
+/// registered by the root or in the genesis, and the ones left from `Releases::V1_0_0` that have
+/// not registered since.
+pub fn migrate_from_v5<T: Trait>() -> Weight {
+	let translated: Cell<Weight> = Cell::new(0);
+	WorkerState::<T>::translate::<WorkerInfoV5, _>(|_, old| {
+		translated.set(translated.get() + 1);
+		Some(WorkerInfo {
+			attestation_exempt: old.mr_enclave.is_empty(),
+			machine_id: old.machine_id,
+			pubkey: old.pubkey,
+			ecdh_pubkey: old.ecdh_pubkey,
+			mr_enclave: old.mr_enclave,
+			last_updated: old.last_updated,
+			state: old.state,
+			score: old.score,
+		})
+	});
+	let translated = translated.get();
+	T::DbWeight::get().reads_writes(translated, translated)
+}
@@ -2,13 +2,18 @@
 
 use crate::{Module, Trait};
 use sp_core::H256;
-use frame_support::{impl_outer_origin, impl_outer_event, parameter_types, weights::Weight};
+use frame_support::{impl_outer_origin, impl_outer_event, impl_outer_dispatch, parameter_types, traits::Get, weights::Weight};
 use sp_runtime::{
 	traits::{BlakeTwo256, IdentityLookup}, testing::Header, Perbill,
 };
+use sp_staking::{
+	offence::{Kind, Offence, OffenceError, ReportOffence},
+	SessionIndex,
+};
 use frame_system as system;
 use pallet_balances as balances;
 use crate as phala;
+use std::cell::RefCell;
 
 pub(crate) type Balance = u128;
 
@@ -24,6 +29,12 @@ impl_outer_event! {
 	}
 }
 
+impl_outer_dispatch! {
+	pub enum Call for Test where origin: Origin {
+		phala::PhalaModule,
+	}
+}
+
 // For testing the pallet, we construct most of a mock runtime. This means
 // first constructing a configuration type (`Test`) which `impl`s each of the
 // configuration traits of pallets we want to use.
@@ -36,12 +47,80 @@ parameter_types! {
 	pub const MaximumBlockLength: u32 = 2 * 1024;
 	pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
 	pub const MinimumPeriod: u64 = 1;
+	pub const RewardPerBlock: Balance = 8;
+	pub const RoundInterval: u64 = 5;
+	pub const OfflineThreshold: u64 = 10;
+	pub const HeartbeatMaxLag: u64 = 3;
+	pub const ChallengesPerRound: u32 = 3;
+	pub const ChallengeDeadline: u64 = 5;
+	pub const MaxCommandSize: u32 = 16;
+	pub const CommandByteFee: Balance = 2;
+	pub const ContractKeyGracePeriod: u64 = 5;
+	pub const LeaveCooldown: u64 = 10;
+	pub const EgressSignatureTimeout: u64 = 5;
+	pub const MaxReportAge: u64 = 10 * 60 * 1000;
+	pub const ReattestationInterval: u64 = 24 * 60 * 60 * 1000;
+	pub const BondUnbondingPeriod: u64 = 10;
+	pub const CallQuotaWindow: u64 = 5;
+	pub const MaxCallsPerWindow: u32 = 2;
+}
+
+thread_local! {
+	static WORKER_BOND: RefCell<Balance> = RefCell::new(0);
+	static VALIDATORS: RefCell<Vec<u64>> = RefCell::new(Vec::new());
+	static OFFENCES: RefCell<Vec<(Kind, Vec<u64>, Vec<u64>)>> = RefCell::new(Vec::new());
+}
+
+pub struct WorkerBond;
+impl Get<Balance> for WorkerBond {
+	fn get() -> Balance {
+		WORKER_BOND.with(|v| *v.borrow())
+	}
+}
+
+/// Require the stashes to bond `amount` to register or mine. No bond is required by default.
+pub fn set_worker_bond(amount: Balance) {
+	WORKER_BOND.with(|v| *v.borrow_mut() = amount);
+}
+
+/// A validator set of 10 validators, where the controllers are the validators themselves
+pub struct IdentifyController;
+impl phala::IdentifyValidator<u64, u64> for IdentifyController {
+	fn current_session() -> (SessionIndex, u32) {
+		(0, 10)
+	}
+	fn identify_validator(controller: &u64) -> Option<u64> {
+		VALIDATORS.with(|v| v.borrow().iter().find(|v| *v == controller).cloned())
+	}
+}
+
+/// Make the given controllers validators. There's no validator by default.
+pub fn set_validators(controllers: Vec<u64>) {
+	VALIDATORS.with(|v| *v.borrow_mut() = controllers);
+}
+
+/// Records the reported offences
+pub struct OffenceHandler;
+impl<O: Offence<u64>> ReportOffence<u64, u64, O> for OffenceHandler {
+	fn report_offence(reporters: Vec<u64>, offence: O) -> Result<(), OffenceError> {
+		OFFENCES.with(|l| l.borrow_mut().push((O::ID, offence.offenders(), reporters)));
+		Ok(())
+	}
+
+	fn is_known_offence(_offenders: &[u64], _time_slot: &O::TimeSlot) -> bool {
+		false
+	}
+}
+
+/// Takes the reported offences as `(kind, offenders, reporters)`
+pub fn offences() -> Vec<(Kind, Vec<u64>, Vec<u64>)> {
+	OFFENCES.with(|l| l.borrow_mut().drain(..).collect())
 }
 
 impl system::Trait for Test {
 	type BaseCallFilter = ();
 	type Origin = Origin;
-	type Call = ();
+	type Call = Call;
 	type Index = u64;
 	type BlockNumber = u64;
 	type Hash = H256;
@@ -87,6 +166,32 @@ impl Trait for Test {
 	type Event = TestEvent;
 	type TEECurrency = Balances;
 	type UnixTime = pallet_timestamp::Module<Test>;
+	type GovernanceOrigin = frame_system::EnsureRoot<u64>;
+	type RewardPerBlock = RewardPerBlock;
+	type RoundInterval = RoundInterval;
+	type OfflineThreshold = OfflineThreshold;
+	type HeartbeatMaxLag = HeartbeatMaxLag;
+	type Randomness = ();
+	type ChallengesPerRound = ChallengesPerRound;
+	type ChallengeDeadline = ChallengeDeadline;
+	type MaxCommandSize = MaxCommandSize;
+	type CommandByteFee = CommandByteFee;
+	type CommandFee = ();
+	type ContractKeyGracePeriod = ContractKeyGracePeriod;
+	type EgressSignatureTimeout = EgressSignatureTimeout;
+	type LeaveCooldown = LeaveCooldown;
+	type OnStashRemoved = ();
+	type MaxReportAge = MaxReportAge;
+	type ReattestationInterval = ReattestationInterval;
+	type WorkerBond = WorkerBond;
+	type BondUnbondingPeriod = BondUnbondingPeriod;
+	type Slash = ();
+	type ValidatorIdentification = u64;
+	type IdentifyValidator = IdentifyController;
+	type ReportOffence = OffenceHandler;
+	type CallQuotaWindow = CallQuotaWindow;
+	type MaxCallsPerWindow = MaxCallsPerWindow;
+	type WeightInfo = ();
 }
 
 mod test_events {
@@ -95,10 +200,22 @@ mod test_events {
 
 pub type System = frame_system::Module<Test>;
 pub type Balances = pallet_balances::Module<Test>;
+pub type Timestamp = pallet_timestamp::Module<Test>;
 pub type PhalaModule = Module<Test>;
 
 // This function basically just builds a genesis storage key/value store according to
 // our desired mockup.
 pub fn new_test_ext() -> sp_io::TestExternalities {
-	system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
+	let mut t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	phala::GenesisConfig::<Test> {
+		stakers: Default::default(),
+		contract_keys: Default::default(),
+		contracts: Default::default(),
+		worker_scoring_params: Default::default(),
+		ias_trust_anchors: vec![
+			(phala::constants::IAS_ROOT_CA_SUBJECT.to_vec(), phala::constants::IAS_ROOT_CA_SPKI.to_vec()),
+		],
+		dcap_trust_anchors: Default::default(),
+	}.assimilate_storage(&mut t).unwrap();
+	t.into()
 }
@@ -0,0 +1,150 @@
+//! Offences of the workers running validators, reported to the offences module so that the
+//! stashes of the validators and their nominators are slashed.
+
+use sp_std::prelude::*;
+use sp_runtime::Perbill;
+use sp_staking::{
+	offence::{Kind, Offence},
+	SessionIndex,
+};
+
+/// Identifies the validators run by the workers and the current validator set.
+pub trait IdentifyValidator<AccountId, Identification> {
+	/// The current session index and the number of validators in the session
+	fn current_session() -> (SessionIndex, u32);
+	/// The full identification of the validator run by the worker of a controller, or `None` if
+	/// the controller isn't an active validator
+	fn identify_validator(controller: &AccountId) -> Option<Identification>;
+}
+
+impl<AccountId, Identification> IdentifyValidator<AccountId, Identification> for () {
+	fn current_session() -> (SessionIndex, u32) {
+		(0, 0)
+	}
+	fn identify_validator(_controller: &AccountId) -> Option<Identification> {
+		None
+	}
+}
+
+/// `min(3 * offenders / validators, 1) * max`, i.e. the full `max` is slashed once a third of the
+/// validators misbehave at the same time
+fn scaled_fraction(offenders_count: u32, validator_set_count: u32, max: Perbill) -> Perbill {
+	let proportion = Perbill::from_rational_approximation(
+		offenders_count.saturating_mul(3),
+		validator_set_count.max(1),
+	);
+	proportion * max
+}
+
+/// The mining workers didn't send heartbeats within `OfflineThreshold` blocks
+#[derive(Clone, PartialEq, Eq, sp_runtime::RuntimeDebug)]
+pub struct WorkerOfflineOffence<BlockNumber, Offender> {
+	pub session_index: SessionIndex,
+	pub validator_set_count: u32,
+	/// The block where the workers were found offline
+	pub block_number: BlockNumber,
+	pub offenders: Vec<Offender>,
+}
+
+impl<BlockNumber, Offender> Offence<Offender> for WorkerOfflineOffence<BlockNumber, Offender> where
+	BlockNumber: Clone + codec::Codec + Ord,
+	Offender: Clone,
+{
+	const ID: Kind = *b"phala:wk-offline";
+	type TimeSlot = BlockNumber;
+
+	fn offenders(&self) -> Vec<Offender> {
+		self.offenders.clone()
+	}
+
+	fn session_index(&self) -> SessionIndex {
+		self.session_index
+	}
+
+	fn validator_set_count(&self) -> u32 {
+		self.validator_set_count
+	}
+
+	fn time_slot(&self) -> Self::TimeSlot {
+		self.block_number.clone()
+	}
+
+	fn slash_fraction(offenders_count: u32, validator_set_count: u32) -> Perbill {
+		scaled_fraction(offenders_count, validator_set_count, Perbill::from_percent(5))
+	}
+}
+
+/// The workers didn't answer their challenges before the deadline
+#[derive(Clone, PartialEq, Eq, sp_runtime::RuntimeDebug)]
+pub struct ChallengeMissedOffence<BlockNumber, Offender> {
+	pub session_index: SessionIndex,
+	pub validator_set_count: u32,
+	/// The block where the challenges expired
+	pub block_number: BlockNumber,
+	pub offenders: Vec<Offender>,
+}
+
+impl<BlockNumber, Offender> Offence<Offender> for ChallengeMissedOffence<BlockNumber, Offender> where
+	BlockNumber: Clone + codec::Codec + Ord,
+	Offender: Clone,
+{
+	const ID: Kind = *b"phala:missed-chl";
+	type TimeSlot = BlockNumber;
+
+	fn offenders(&self) -> Vec<Offender> {
+		self.offenders.clone()
+	}
+
+	fn session_index(&self) -> SessionIndex {
+		self.session_index
+	}
+
+	fn validator_set_count(&self) -> u32 {
+		self.validator_set_count
+	}
+
+	fn time_slot(&self) -> Self::TimeSlot {
+		self.block_number.clone()
+	}
+
+	fn slash_fraction(offenders_count: u32, validator_set_count: u32) -> Perbill {
+		scaled_fraction(offenders_count, validator_set_count, Perbill::from_percent(10))
+	}
+}
+
+/// The worker signed two different egress messages with the same contract id and sequence
+#[derive(Clone, PartialEq, Eq, sp_runtime::RuntimeDebug)]
+pub struct EgressEquivocationOffence<Offender> {
+	pub session_index: SessionIndex,
+	pub validator_set_count: u32,
+	/// The contract id and the sequence of the conflicting messages
+	pub message: (u32, u64),
+	pub offender: Offender,
+}
+
+impl<Offender: Clone> Offence<Offender> for EgressEquivocationOffence<Offender> {
+	const ID: Kind = *b"phala:equivocati";
+	type TimeSlot = (u32, u64);
+
+	fn offenders(&self) -> Vec<Offender> {
+		vec![self.offender.clone()]
+	}
+
+	fn session_index(&self) -> SessionIndex {
+		self.session_index
+	}
+
+	fn validator_set_count(&self) -> u32 {
+		self.validator_set_count
+	}
+
+	fn time_slot(&self) -> Self::TimeSlot {
+		self.message
+	}
+
+	fn slash_fraction(offenders_count: u32, validator_set_count: u32) -> Perbill {
+		// Same as the block equivocations: `min(3 * offenders / validators, 1) ^ 2`
+		let proportion = scaled_fraction(offenders_count, validator_set_count, Perbill::one());
+		proportion.square()
+	}
+}
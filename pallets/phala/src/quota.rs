@@ -0,0 +1,130 @@
+//! Per-account quotas of the calls every pRuntime has to process, so that a single account can't
+//! fill the blocks with them.
+
+use sp_std::{fmt, marker::PhantomData};
+use codec::{Encode, Decode};
+use frame_support::{
+	traits::{Get, IsSubType}, weights::Weight, StorageDoubleMap, storage::IterableStorageDoubleMap,
+};
+use sp_runtime::{
+	traits::{DispatchInfoOf, One, SignedExtension, Zero},
+	transaction_validity::{
+		InvalidTransaction, TransactionValidity, TransactionValidityError, ValidTransaction,
+	},
+};
+
+use crate::{Call, CallQuotaUsage, Trait, types::RateLimitedCall};
+
+/// The kind of the call if it's rate limited, and the number of calls it's counted as. A batch of
+/// transfers is counted as one call per message.
+fn rate_limited<T: Trait>(call: &Call<T>) -> Option<(RateLimitedCall, u32)> {
+	match call {
+		Call::push_command(..) => Some((RateLimitedCall::Command, 1)),
+		Call::heartbeat(..) => Some((RateLimitedCall::Heartbeat, 1)),
+		Call::transfer_to_chain(..) => Some((RateLimitedCall::Transfer, 1)),
+		Call::transfer_to_chain_batch(messages) => {
+			Some((RateLimitedCall::Transfer, (messages.len() as u32).max(1)))
+		},
+		_ => None,
+	}
+}
+
+/// The start of the current quota window and the number of the calls of the kind the account
+/// already submitted in it, or an error if the quota can't cover `count` more calls
+fn check_quota<T: Trait>(
+	who: &T::AccountId,
+	kind: RateLimitedCall,
+	count: u32,
+) -> Result<(T::BlockNumber, u32), TransactionValidityError> {
+	let now = <frame_system::Module<T>>::block_number();
+	let window = T::CallQuotaWindow::get().max(One::one());
+	let start = now - now % window;
+	let used = match CallQuotaUsage::<T>::get(who, kind) {
+		(last_start, used) if last_start == start => used,
+		_ => 0,
+	};
+	if used.saturating_add(count) > T::MaxCallsPerWindow::get() {
+		return Err(InvalidTransaction::ExhaustsResources.into());
+	}
+	Ok((start, used))
+}
+
+/// Forget the quota usage of the previous window at the start of each window, so that no account
+/// keeps an entry after it stopped submitting the rate limited calls
+pub(crate) fn prune_quota_usage<T: Trait>(now: T::BlockNumber) -> Weight {
+	let window = T::CallQuotaWindow::get().max(One::one());
+	if !(now % window).is_zero() {
+		return 0;
+	}
+	let pruned = CallQuotaUsage::<T>::drain().count() as Weight;
+	T::DbWeight::get().reads_writes(pruned, pruned)
+}
+
+/// Rejects the `push_command`, `heartbeat` and `transfer_to_chain` calls of an account once it
+/// submitted `MaxCallsPerWindow` of the same kind in the current window of `CallQuotaWindow`
+/// blocks. Each message of `transfer_to_chain_batch` counts as a `transfer_to_chain` call.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, Default)]
+pub struct CheckCallQuota<T: Trait + Send + Sync>(PhantomData<T>);
+
+impl<T: Trait + Send + Sync> CheckCallQuota<T> {
+	/// Create new `SignedExtension` to check the call quota.
+	pub fn new() -> Self {
+		Self(PhantomData)
+	}
+}
+
+impl<T: Trait + Send + Sync> fmt::Debug for CheckCallQuota<T> {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "CheckCallQuota")
+	}
+
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+		Ok(())
+	}
+}
+
+impl<T: Trait + Send + Sync> SignedExtension for CheckCallQuota<T> where
+	<T as frame_system::Trait>::Call: IsSubType<Call<T>>,
+{
+	const IDENTIFIER: &'static str = "CheckCallQuota";
+	type AccountId = T::AccountId;
+	type Call = <T as frame_system::Trait>::Call;
+	type AdditionalSigned = ();
+	type Pre = ();
+
+	fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+
+	fn validate(
+		&self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		_info: &DispatchInfoOf<Self::Call>,
+		_len: usize,
+	) -> TransactionValidity {
+		match call.is_sub_type().and_then(rate_limited::<T>) {
+			Some((kind, count)) => {
+				check_quota::<T>(who, kind, count)?;
+				Ok(ValidTransaction::default())
+			},
+			None => Ok(ValidTransaction::default()),
+		}
+	}
+
+	fn pre_dispatch(
+		self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		_info: &DispatchInfoOf<Self::Call>,
+		_len: usize,
+	) -> Result<(), TransactionValidityError> {
+		if let Some((kind, count)) = call.is_sub_type().and_then(rate_limited::<T>) {
+			let (start, used) = check_quota::<T>(who, kind, count)?;
+			CallQuotaUsage::<T>::insert(who, kind, (start, used.saturating_add(count)));
+		}
+		Ok(())
+	}
+}
@@ -1,12 +1,32 @@
-use codec::Encode;
-use frame_support::{assert_ok, assert_noop, traits::{Currency}};
+use codec::{Decode, Encode};
+use frame_support::{
+	assert_ok, assert_noop, traits::{Currency, OnInitialize, OnRuntimeUpgrade, ReservableCurrency},
+	StorageMap, StorageValue, storage::{IterableStorageMap, IterableStorageDoubleMap, StoragePrefixedMap},
+	weights::DispatchInfo,
+};
 use frame_system::RawOrigin;
 use hex_literal::hex;
 use secp256k1;
-use sp_runtime::traits::BadOrigin;
+use sp_core::H256;
+use sp_runtime::{
+	traits::{BadOrigin, BlakeTwo256, Hash, SignedExtension},
+	transaction_validity::{InvalidTransaction, ValidTransaction},
+};
 
-use crate::{Error, mock::*};
-use crate::{RawEvent, types::{Transfer, TransferData}};
+use crate::{Error, mock::*, constants, SUPPORTED_SIG_ALGS, parse_ias_timestamp, CheckCallQuota};
+use crate::{
+	RawEvent, WorkerState, StashState, Stash, MachineOwner, PendingRewards, PendingEgress, UsedReports,
+	UsedQuotes, RoundHeartbeats, ContractAssign, WorkerContracts,
+	UnbondingBonds,
+	StorageVersion, Releases,
+	migrations::{OldWorkerInfo, WorkerInfoV2, WorkerInfoV5},
+	types::{
+		Transfer, TransferData, Heartbeat, HeartbeatData, EgressMessage, SignedEgressMessage,
+		EgressHandler, BalanceRelease, AssetRelease, ContractKeyRotation, SignedContractKeyRotation,
+		ChallengeResponse, SignedChallengeResponse, MiningStatus, MinerState, Score, ScoringParams,
+		FeatureWeight, PRuntimeInfo, ECDH_PUBKEY_LEN, RateLimitedCall, WorkerInfo, TcbLevel,
+	},
+};
 
 fn events() -> Vec<TestEvent> {
 	let evt = System::events().into_iter().map(|evt| evt.event).collect::<Vec<_>>();
@@ -14,64 +34,82 @@ fn events() -> Vec<TestEvent> {
 	evt
 }
 
-type SignatureAlgorithms = &'static [&'static webpki::SignatureAlgorithm];
-static SUPPORTED_SIG_ALGS: SignatureAlgorithms = &[
-	// &webpki::ECDSA_P256_SHA256,
-	// &webpki::ECDSA_P256_SHA384,
-	// &webpki::ECDSA_P384_SHA256,
-	// &webpki::ECDSA_P384_SHA384,
-	&webpki::RSA_PKCS1_2048_8192_SHA256,
-	&webpki::RSA_PKCS1_2048_8192_SHA384,
-	&webpki::RSA_PKCS1_2048_8192_SHA512,
-	&webpki::RSA_PKCS1_3072_8192_SHA384,
-];
-
-pub static IAS_SERVER_ROOTS: webpki::TLSServerTrustAnchors = webpki::TLSServerTrustAnchors(&[
-	/*
-	 * -----BEGIN CERTIFICATE-----
-	 * MIIFSzCCA7OgAwIBAgIJANEHdl0yo7CUMA0GCSqGSIb3DQEBCwUAMH4xCzAJBgNV
-	 * BAYTAlVTMQswCQYDVQQIDAJDQTEUMBIGA1UEBwwLU2FudGEgQ2xhcmExGjAYBgNV
-	 * BAoMEUludGVsIENvcnBvcmF0aW9uMTAwLgYDVQQDDCdJbnRlbCBTR1ggQXR0ZXN0
-	 * YXRpb24gUmVwb3J0IFNpZ25pbmcgQ0EwIBcNMTYxMTE0MTUzNzMxWhgPMjA0OTEy
-	 * MzEyMzU5NTlaMH4xCzAJBgNVBAYTAlVTMQswCQYDVQQIDAJDQTEUMBIGA1UEBwwL
-	 * U2FudGEgQ2xhcmExGjAYBgNVBAoMEUludGVsIENvcnBvcmF0aW9uMTAwLgYDVQQD
-	 * DCdJbnRlbCBTR1ggQXR0ZXN0YXRpb24gUmVwb3J0IFNpZ25pbmcgQ0EwggGiMA0G
-	 * CSqGSIb3DQEBAQUAA4IBjwAwggGKAoIBgQCfPGR+tXc8u1EtJzLA10Feu1Wg+p7e
-	 * LmSRmeaCHbkQ1TF3Nwl3RmpqXkeGzNLd69QUnWovYyVSndEMyYc3sHecGgfinEeh
-	 * rgBJSEdsSJ9FpaFdesjsxqzGRa20PYdnnfWcCTvFoulpbFR4VBuXnnVLVzkUvlXT
-	 * L/TAnd8nIZk0zZkFJ7P5LtePvykkar7LcSQO85wtcQe0R1Raf/sQ6wYKaKmFgCGe
-	 * NpEJUmg4ktal4qgIAxk+QHUxQE42sxViN5mqglB0QJdUot/o9a/V/mMeH8KvOAiQ
-	 * byinkNndn+Bgk5sSV5DFgF0DffVqmVMblt5p3jPtImzBIH0QQrXJq39AT8cRwP5H
-	 * afuVeLHcDsRp6hol4P+ZFIhu8mmbI1u0hH3W/0C2BuYXB5PC+5izFFh/nP0lc2Lf
-	 * 6rELO9LZdnOhpL1ExFOq9H/B8tPQ84T3Sgb4nAifDabNt/zu6MmCGo5U8lwEFtGM
-	 * RoOaX4AS+909x00lYnmtwsDVWv9vBiJCXRsCAwEAAaOByTCBxjBgBgNVHR8EWTBX
-	 * MFWgU6BRhk9odHRwOi8vdHJ1c3RlZHNlcnZpY2VzLmludGVsLmNvbS9jb250ZW50
-	 * L0NSTC9TR1gvQXR0ZXN0YXRpb25SZXBvcnRTaWduaW5nQ0EuY3JsMB0GA1UdDgQW
-	 * BBR4Q3t2pn680K9+QjfrNXw7hwFRPDAfBgNVHSMEGDAWgBR4Q3t2pn680K9+Qjfr
-	 * NXw7hwFRPDAOBgNVHQ8BAf8EBAMCAQYwEgYDVR0TAQH/BAgwBgEB/wIBADANBgkq
-	 * hkiG9w0BAQsFAAOCAYEAeF8tYMXICvQqeXYQITkV2oLJsp6J4JAqJabHWxYJHGir
-	 * IEqucRiJSSx+HjIJEUVaj8E0QjEud6Y5lNmXlcjqRXaCPOqK0eGRz6hi+ripMtPZ
-	 * sFNaBwLQVV905SDjAzDzNIDnrcnXyB4gcDFCvwDFKKgLRjOB/WAqgscDUoGq5ZVi
-	 * zLUzTqiQPmULAQaB9c6Oti6snEFJiCQ67JLyW/E83/frzCmO5Ru6WjU4tmsmy8Ra
-	 * Ud4APK0wZTGtfPXU7w+IBdG5Ez0kE1qzxGQaL4gINJ1zMyleDnbuS8UicjJijvqA
-	 * 152Sq049ESDz+1rRGc2NVEqh1KaGXmtXvqxXcTB+Ljy5Bw2ke0v8iGngFBPqCTVB
-	 * 3op5KBG3RjbF6RRSzwzuWfL7QErNC8WEy5yDVARzTA5+xmBc388v9Dm21HGfcC8O
-	 * DD+gT9sSpssq0ascmvH49MOgjt1yoysLtdCtJW/9FZpoOypaHx0R+mJTLwPXVMrv
-	 * DaVzWh5aiEx+idkSGMnX
-	 * -----END CERTIFICATE-----
-	 */
-	webpki::TrustAnchor {
-		subject: b"1\x0b0\t\x06\x03U\x04\x06\x13\x02US1\x0b0\t\x06\x03U\x04\x08\x0c\x02CA1\x140\x12\x06\x03U\x04\x07\x0c\x0bSanta Clara1\x1a0\x18\x06\x03U\x04\n\x0c\x11Intel Corporation100.\x06\x03U\x04\x03\x0c\'Intel SGX Attestation Report Signing CA",
-		spki: b"0\r\x06\t*\x86H\x86\xf7\r\x01\x01\x01\x05\x00\x03\x82\x01\x8f\x000\x82\x01\x8a\x02\x82\x01\x81\x00\x9f<d~\xb5w<\xbbQ-\'2\xc0\xd7A^\xbbU\xa0\xfa\x9e\xde.d\x91\x99\xe6\x82\x1d\xb9\x10\xd51w7\twFjj^G\x86\xcc\xd2\xdd\xeb\xd4\x14\x9dj/c%R\x9d\xd1\x0c\xc9\x877\xb0w\x9c\x1a\x07\xe2\x9cG\xa1\xae\x00IHGlH\x9fE\xa5\xa1]z\xc8\xec\xc6\xac\xc6E\xad\xb4=\x87g\x9d\xf5\x9c\t;\xc5\xa2\xe9ilTxT\x1b\x97\x9euKW9\x14\xbeU\xd3/\xf4\xc0\x9d\xdf\'!\x994\xcd\x99\x05\'\xb3\xf9.\xd7\x8f\xbf)$j\xbe\xcbq$\x0e\xf3\x9c-q\x07\xb4GTZ\x7f\xfb\x10\xeb\x06\nh\xa9\x85\x80!\x9e6\x91\tRh8\x92\xd6\xa5\xe2\xa8\x08\x03\x19>@u1@N6\xb3\x15b7\x99\xaa\x82Pt@\x97T\xa2\xdf\xe8\xf5\xaf\xd5\xfec\x1e\x1f\xc2\xaf8\x08\x90o(\xa7\x90\xd9\xdd\x9f\xe0`\x93\x9b\x12W\x90\xc5\x80]\x03}\xf5j\x99S\x1b\x96\xdei\xde3\xed\"l\xc1 }\x10B\xb5\xc9\xab\x7f@O\xc7\x11\xc0\xfeGi\xfb\x95x\xb1\xdc\x0e\xc4i\xea\x1a%\xe0\xff\x99\x14\x88n\xf2i\x9b#[\xb4\x84}\xd6\xff@\xb6\x06\xe6\x17\x07\x93\xc2\xfb\x98\xb3\x14X\x7f\x9c\xfd%sb\xdf\xea\xb1\x0b;\xd2\xd9vs\xa1\xa4\xbdD\xc4S\xaa\xf4\x7f\xc1\xf2\xd3\xd0\xf3\x84\xf7J\x06\xf8\x9c\x08\x9f\r\xa6\xcd\xb7\xfc\xee\xe8\xc9\x82\x1a\x8eT\xf2\\\x04\x16\xd1\x8cF\x83\x9a_\x80\x12\xfb\xdd=\xc7M%by\xad\xc2\xc0\xd5Z\xffo\x06\"B]\x1b\x02\x03\x01\x00\x01",
-		name_constraints: None
-	},
-]);
-
 pub const IAS_REPORT_SAMPLE: &[u8] = include_bytes!("../sample/report");
 pub const IAS_REPORT_SIGNATURE: &[u8] = include_bytes!("../sample/report_signature");
 pub const IAS_REPORT_SIGNING_CERTIFICATE: &[u8] = include_bytes!("../sample/report_signing_certificate");
+// A report signed by a self-issued certificate chain mimicking the subjects of the IAS certificates
+pub const FORGED_REPORT_SIGNATURE: &[u8] = include_bytes!("../sample/forged_report_signature");
+pub const FORGED_REPORT_SIGNING_CERTIFICATE: &[u8] = include_bytes!("../sample/forged_report_signing_certificate");
+// A self-issued IAS-like certificate chain, signing reports with unexpected contents
+const TEST_IAS_ROOT_CA_SUBJECT: &[u8] = include_bytes!("../sample/test_ias_root_ca_subject");
+const TEST_IAS_ROOT_CA_SPKI: &[u8] = include_bytes!("../sample/test_ias_root_ca_spki");
+const TEST_IAS_SIGNING_CERTIFICATE: &[u8] = include_bytes!("../sample/test_ias_signing_certificate");
+const MALFORMED_REPORTS: [(&[u8], &[u8]); 3] = [
+	(
+		include_bytes!("../sample/malformed_report_not_json"),
+		include_bytes!("../sample/malformed_report_not_json_signature"),
+	),
+	(
+		include_bytes!("../sample/malformed_report_no_quote_body"),
+		include_bytes!("../sample/malformed_report_no_quote_body_signature"),
+	),
+	(
+		include_bytes!("../sample/malformed_report_bad_quote_body"),
+		include_bytes!("../sample/malformed_report_bad_quote_body_signature"),
+	),
+];
+// 2020-09-30T16:52:48Z, right after the sample report was issued
+const IAS_REPORT_TIMESTAMP: u64 = 1601484768000;
 pub const TEE_REPORT_SAMPLE: &[u8] =  &[1, 122, 238, 139, 126, 110, 55, 54, 207, 3, 19, 185, 137, 120, 238, 90, 71, 2, 28, 239, 90, 188, 129, 213, 193, 164, 64, 149, 82, 38, 229, 204, 150, 142, 110, 10, 182, 8, 122, 212, 50, 211, 194, 12, 193, 229, 219, 235, 185, 232, 8, 4, 0, 0, 0, 1, 0, 0, 0];
 const PANIC: bool = false;
+// Measurement of the enclave producing the sample report
+const SAMPLE_MR_ENCLAVE: [u8; 32] = hex!["c585865ef0d9f1c6b71e0d3f2189c2dcadc0d93c95b79ba79ad34e7f6eb5f9ae"];
+const SAMPLE_MR_SIGNER: [u8; 32] = hex!["83d719e77deaca1470f6baf62a4d774303c899db69020f9c70ee1dfc08c7ce9e"];
+// A DCAP quote of the sample enclave, certified by a self-issued PCK certificate chain mimicking
+// the Intel SGX PKI (generated by `sample/gen_dcap_sample.py`)
+pub const DCAP_QUOTE_SAMPLE: &[u8] = include_bytes!("../sample/dcap_quote");
+pub const DCAP_PCK_CERTIFICATE: &[u8] = include_bytes!("../sample/dcap_pck_certificate");
+pub const DCAP_PCK_CA_CERTIFICATE: &[u8] = include_bytes!("../sample/dcap_pck_ca_certificate");
+pub const DCAP_ROOT_CA_SUBJECT: &[u8] = include_bytes!("../sample/dcap_root_ca_subject");
+pub const DCAP_ROOT_CA_SPKI: &[u8] = include_bytes!("../sample/dcap_root_ca_spki");
+// Identity of the Quoting Enclave signing the sample quote
+const SAMPLE_QE_MR_SIGNER: [u8; 32] = hex!["8c4f5775d796503e96137f77c68a829a0056ac8ded70140b081b094490c57bff"];
+const SAMPLE_QE_ISV_SVN: u16 = 5;
+// Platform of the sample PCK certificate
+const SAMPLE_PCK_SERIAL: [u8; 20] = hex!["1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d"];
+const SAMPLE_FMSPC: [u8; 6] = hex!["00906ed50000"];
+const SAMPLE_TCB: TcbLevel = TcbLevel {
+	components: [5, 5, 2, 2, 3, 1, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0],
+	pce_svn: 10,
+};
+
+// Allow the sample report to be used again
+fn forget_used_reports() {
+	UsedReports::remove_all();
+}
+
+fn whitelist_sample_enclave() {
+	assert_ok!(PhalaModule::add_enclave_measurement(
+		RawOrigin::Root.into(), SAMPLE_MR_ENCLAVE.to_vec(), SAMPLE_MR_SIGNER.to_vec(), 0, 0));
+}
+
+fn trust_sample_dcap_root() {
+	assert_ok!(PhalaModule::force_add_dcap_trust_anchor(
+		RawOrigin::Root.into(), DCAP_ROOT_CA_SUBJECT.to_vec(), DCAP_ROOT_CA_SPKI.to_vec()));
+}
+
+fn sample_pck_cert_chain() -> Vec<Vec<u8>> {
+	vec![DCAP_PCK_CERTIFICATE.to_vec(), DCAP_PCK_CA_CERTIFICATE.to_vec()]
+}
+
+// Trust the DCAP quotes of the sample enclave on the sample platform
+fn setup_sample_dcap_attestation() {
+	whitelist_sample_enclave();
+	trust_sample_dcap_root();
+	assert_ok!(PhalaModule::set_qe_identity(
+		RawOrigin::Root.into(), SAMPLE_QE_MR_SIGNER.to_vec(), 1, SAMPLE_QE_ISV_SVN));
+	assert_ok!(PhalaModule::set_tcb_levels(RawOrigin::Root.into(), SAMPLE_FMSPC.to_vec(), vec![SAMPLE_TCB]));
+}
 
 #[test]
 fn test_validate_cert() {
@@ -91,10 +129,17 @@ fn test_validate_cert() {
 
 	let chain: Vec<&[u8]> = Vec::new();
 	let now_func = webpki::Time::from_seconds_since_unix_epoch(1573419050);
+	let trust_anchors = [
+		webpki::TrustAnchor {
+			subject: constants::IAS_ROOT_CA_SUBJECT,
+			spki: constants::IAS_ROOT_CA_SPKI,
+			name_constraints: None
+		},
+	];
 
 	match sig_cert.verify_is_valid_tls_server_cert(
 		SUPPORTED_SIG_ALGS,
-		&IAS_SERVER_ROOTS,
+		&webpki::TLSServerTrustAnchors(&trust_anchors),
 		&chain,
 		now_func
 	) {
@@ -131,17 +176,618 @@ fn test_register_worker() {
 			Err(_) => panic!("decode cert failed")
 		};
 
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		whitelist_sample_enclave();
 		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
 		assert_ok!(PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone()));
+		forget_used_reports();
 		assert_ok!(PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone()));
 	});
 }
 
+#[test]
+fn test_register_mining_worker_again() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		let sig: Vec<u8> = base64::decode(&IAS_REPORT_SIGNATURE).expect("decode sig failed");
+		let sig_cert_dec: Vec<u8> = base64::decode_config(&IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
+		let register = |controller: u64| PhalaModule::register_worker(Origin::signed(controller), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone());
+		whitelist_sample_enclave();
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 11));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(2), 12));
+		assert_ok!(register(11));
+		assert_ok!(PhalaModule::start_mine(Origin::signed(11)));
+		RoundHeartbeats::<Test>::insert(1, 2);
+
+		// Attested again by the same stash, the worker keeps mining
+		forget_used_reports();
+		assert_ok!(register(11));
+		assert_eq!(MinerState::Mining, PhalaModule::worker_state(1).state);
+		assert_eq!(Some((1, 0)), PhalaModule::last_heartbeat(1));
+		assert_eq!(2, PhalaModule::round_heartbeats(1));
+
+		// The machine moving to another stash starts over
+		forget_used_reports();
+		assert_ok!(register(12));
+		assert_eq!(false, WorkerState::<Test>::contains_key(1));
+		assert_eq!(None, PhalaModule::last_heartbeat(1));
+		assert_eq!(0, PhalaModule::round_heartbeats(1));
+		assert_eq!(MinerState::Idle, PhalaModule::worker_state(2).state);
+	});
+}
+
+#[test]
+fn test_parse_ias_timestamp() {
+	assert_eq!(Some(1601484768000), parse_ias_timestamp("2020-09-30T16:52:48.649888"));
+	assert_eq!(Some(1601484768000), parse_ias_timestamp("2020-09-30T16:52:48"));
+	assert_eq!(Some(951782400000), parse_ias_timestamp("2000-02-29T00:00:00"));
+	assert_eq!(Some(0), parse_ias_timestamp("1970-01-01T00:00:00"));
+	assert_eq!(None, parse_ias_timestamp("2020-09-30 16:52:48"));
+	assert_eq!(None, parse_ias_timestamp("2020-13-30T16:52:48"));
+	assert_eq!(None, parse_ias_timestamp("2020-+9-30T16:52:48"));
+	assert_eq!(None, parse_ias_timestamp("2020-09-30"));
+}
+
+#[test]
+fn test_report_freshness() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let sig: Vec<u8> = base64::decode(&IAS_REPORT_SIGNATURE).expect("decode sig failed");
+		let sig_cert_dec: Vec<u8> = base64::decode_config(&IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
+		let register = |controller: u64| PhalaModule::register_worker(Origin::signed(controller), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone());
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(2), 2));
+		whitelist_sample_enclave();
+
+		// The sample report is issued at 2020-09-30T16:52:48
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP + MaxReportAge::get() + 1);
+		assert_noop!(register(1), Error::<Test>::OutdatedReport);
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP + MaxReportAge::get());
+		assert_ok!(register(1));
+		// The report cannot be replayed by anyone
+		assert_noop!(register(1), Error::<Test>::DuplicateReport);
+		assert_noop!(register(2), Error::<Test>::DuplicateReport);
+
+		// The mining worker must be attested again in time
+		let machine_id = PhalaModule::worker_state(1).machine_id;
+		assert_ok!(PhalaModule::start_mine(Origin::signed(1)));
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP + MaxReportAge::get() + ReattestationInterval::get());
+		PhalaModule::on_initialize(5);
+		assert_eq!(MinerState::Mining, PhalaModule::worker_state(1).state);
+		// The used report is forgotten once it's outdated
+		assert_eq!(0, UsedReports::iter().count());
+		events();
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP + MaxReportAge::get() + ReattestationInterval::get() + 1);
+		PhalaModule::on_initialize(10);
+		assert_eq!(MinerState::Evicted, PhalaModule::worker_state(1).state);
+		let phala_events: Vec<TestEvent> = events().into_iter()
+			.filter(|evt| match evt { TestEvent::phala(RawEvent::WorkerEvicted(..)) => true, _ => false })
+			.collect();
+		assert_eq!(phala_events, vec![TestEvent::phala(RawEvent::WorkerEvicted(1, machine_id))]);
+	});
+}
+
+#[test]
+fn test_exempt_workers_not_reattested() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		// The genesis workers are never attested
+		WorkerState::<Test>::insert(1, WorkerInfo {
+			machine_id: b"BUILTIN0".to_vec(),
+			state: MinerState::Mining,
+			attestation_exempt: true,
+			..Default::default()
+		});
+		// Neither are the workers registered by the root, whatever their machine ids
+		assert_ok!(PhalaModule::set_stash(Origin::signed(2), 2));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 2, vec![2], vec![2]));
+		assert!(PhalaModule::worker_state(2).attestation_exempt);
+		WorkerState::<Test>::mutate(2, |info| info.state = MinerState::Mining);
+		// An attested worker is not exempted by its machine id
+		WorkerState::<Test>::insert(3, WorkerInfo {
+			machine_id: b"BUILTIN3".to_vec(),
+			state: MinerState::Mining,
+			..Default::default()
+		});
+		Timestamp::set_timestamp(ReattestationInterval::get() + 1);
+		PhalaModule::on_initialize(5);
+		assert_eq!(MinerState::Mining, PhalaModule::worker_state(1).state);
+		assert_eq!(MinerState::Mining, PhalaModule::worker_state(2).state);
+		assert_eq!(MinerState::Evicted, PhalaModule::worker_state(3).state);
+	});
+}
+
+#[test]
+fn test_builtin_workers_mining_since_genesis() {
+	let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	crate::GenesisConfig::<Test> {
+		stakers: vec![(1, 2, vec![3; 33])],
+		contract_keys: Default::default(),
+		contracts: Default::default(),
+		worker_scoring_params: Default::default(),
+		ias_trust_anchors: Default::default(),
+		dcap_trust_anchors: Default::default(),
+	}.assimilate_storage(&mut t).unwrap();
+	sp_io::TestExternalities::from(t).execute_with(|| {
+		let worker_info = PhalaModule::worker_state(1);
+		assert_eq!(b"BUILTIN0".to_vec(), worker_info.machine_id);
+		assert_eq!(MinerState::Mining, worker_info.state);
+		assert!(worker_info.score.is_some());
+		assert!(worker_info.attestation_exempt);
+		assert_eq!(Some((0, 0)), PhalaModule::last_heartbeat(1));
+		// Healthy, so that the validator of the genesis is elected
+		assert!(PhalaModule::healthy_miner_score(&2, 0).is_some());
+	});
+}
+
+#[test]
+fn test_register_worker_with_forged_report() {
+	new_test_ext().execute_with(|| {
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		let sig: Vec<u8> = base64::decode(&IAS_REPORT_SIGNATURE).expect("decode sig failed");
+		let sig_cert_dec: Vec<u8> = base64::decode_config(&IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
+		let forged_sig: Vec<u8> = base64::decode(&FORGED_REPORT_SIGNATURE).expect("decode sig failed");
+		let forged_cert_dec: Vec<u8> = base64::decode_config(&FORGED_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
+
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		// Self-issued signing certificate
+		assert_noop!(
+			PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), forged_sig.clone(), forged_cert_dec.clone()),
+			Error::<Test>::InvalidIASSigningCert
+		);
+		// Real signing certificate with a signature not made by it
+		assert_noop!(
+			PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), forged_sig.clone(), sig_cert_dec.clone()),
+			Error::<Test>::InvalidIASReportSignature
+		);
+		// Tampered report
+		let mut tampered_report = IAS_REPORT_SAMPLE.to_vec();
+		tampered_report[7] ^= 1;
+		assert_noop!(
+			PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), tampered_report, sig.clone(), sig_cert_dec.clone()),
+			Error::<Test>::InvalidIASReportSignature
+		);
+	});
+}
+
+#[test]
+fn test_register_worker_with_expired_cert() {
+	new_test_ext().execute_with(|| {
+		let sig: Vec<u8> = base64::decode(&IAS_REPORT_SIGNATURE).expect("decode sig failed");
+		let sig_cert_dec: Vec<u8> = base64::decode_config(&IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
+
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		// 2030-01-01, after the signing certificate expired
+		Timestamp::set_timestamp(1893456000000);
+		assert_noop!(
+			PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone()),
+			Error::<Test>::InvalidIASSigningCert
+		);
+	});
+}
+
+#[test]
+fn test_register_worker_with_malformed_report() {
+	new_test_ext().execute_with(|| {
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		assert_ok!(PhalaModule::force_add_ias_trust_anchor(
+			RawOrigin::Root.into(), TEST_IAS_ROOT_CA_SUBJECT.to_vec(), TEST_IAS_ROOT_CA_SPKI.to_vec()));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		// Validly signed, but not the reports expected
+		for (report, signature) in MALFORMED_REPORTS.iter() {
+			assert_noop!(
+				PhalaModule::register_worker(
+					Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), report.to_vec(), signature.to_vec(),
+					TEST_IAS_SIGNING_CERTIFICATE.to_vec()),
+				Error::<Test>::InvalidIASReport
+			);
+		}
+	});
+}
+
+#[test]
+fn test_ias_trust_anchors() {
+	new_test_ext().execute_with(|| {
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		let sig: Vec<u8> = base64::decode(&IAS_REPORT_SIGNATURE).expect("decode sig failed");
+		let sig_cert_dec: Vec<u8> = base64::decode_config(&IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
+		let subject = constants::IAS_ROOT_CA_SUBJECT.to_vec();
+		let spki = constants::IAS_ROOT_CA_SPKI.to_vec();
+
+		whitelist_sample_enclave();
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		assert_noop!(
+			PhalaModule::force_remove_ias_trust_anchor(Origin::signed(1), spki.clone()),
+			BadOrigin
+		);
+		assert_noop!(
+			PhalaModule::force_add_ias_trust_anchor(RawOrigin::Root.into(), subject.clone(), spki.clone()),
+			Error::<Test>::IASTrustAnchorExists
+		);
+		// Revoke the Intel root
+		assert_ok!(PhalaModule::force_remove_ias_trust_anchor(RawOrigin::Root.into(), spki.clone()));
+		assert_eq!(0, PhalaModule::ias_trust_anchors().len());
+		assert_noop!(
+			PhalaModule::force_remove_ias_trust_anchor(RawOrigin::Root.into(), spki.clone()),
+			Error::<Test>::IASTrustAnchorNotFound
+		);
+		assert_noop!(
+			PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone()),
+			Error::<Test>::InvalidIASSigningCert
+		);
+		// Restore it
+		assert_ok!(PhalaModule::force_add_ias_trust_anchor(RawOrigin::Root.into(), subject, spki));
+		assert_ok!(PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig, sig_cert_dec));
+	});
+}
+
+#[test]
+fn test_register_worker_dcap() {
+	new_test_ext().execute_with(|| {
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		let register = |controller: u64, chain: Vec<Vec<u8>>| PhalaModule::register_worker_dcap(
+			Origin::signed(controller), TEE_REPORT_SAMPLE.to_vec(), DCAP_QUOTE_SAMPLE.to_vec(), chain);
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(2), 2));
+		whitelist_sample_enclave();
+
+		// The root is not trusted yet
+		assert_noop!(register(1, sample_pck_cert_chain()), Error::<Test>::InvalidPCKCertChain);
+		trust_sample_dcap_root();
+		// Incomplete chain
+		assert_noop!(register(1, vec![DCAP_PCK_CERTIFICATE.to_vec()]), Error::<Test>::InvalidPCKCertChain);
+		assert_noop!(register(1, vec![]), Error::<Test>::InvalidPCKCertChain);
+		// The Quoting Enclave must be known and up to date
+		assert_noop!(register(1, sample_pck_cert_chain()), Error::<Test>::QEIdentityNotSet);
+		assert_ok!(PhalaModule::set_qe_identity(
+			RawOrigin::Root.into(), SAMPLE_QE_MR_SIGNER.to_vec(), 1, SAMPLE_QE_ISV_SVN + 1));
+		assert_noop!(register(1, sample_pck_cert_chain()), Error::<Test>::InvalidQEReport);
+		assert_ok!(PhalaModule::set_qe_identity(
+			RawOrigin::Root.into(), SAMPLE_QE_MR_SIGNER.to_vec(), 1, SAMPLE_QE_ISV_SVN));
+		// The platform must be up to date
+		assert_noop!(register(1, sample_pck_cert_chain()), Error::<Test>::TcbOutOfDate);
+		let mut newer_tcb = SAMPLE_TCB;
+		newer_tcb.components[4] += 1;
+		assert_ok!(PhalaModule::set_tcb_levels(RawOrigin::Root.into(), SAMPLE_FMSPC.to_vec(), vec![newer_tcb.clone()]));
+		assert_noop!(register(1, sample_pck_cert_chain()), Error::<Test>::TcbOutOfDate);
+		newer_tcb.components[4] -= 1;
+		newer_tcb.pce_svn += 1;
+		assert_ok!(PhalaModule::set_tcb_levels(RawOrigin::Root.into(), SAMPLE_FMSPC.to_vec(), vec![newer_tcb.clone()]));
+		assert_noop!(register(1, sample_pck_cert_chain()), Error::<Test>::TcbOutOfDate);
+		// Any of the accepted levels
+		assert_ok!(PhalaModule::set_tcb_levels(RawOrigin::Root.into(), SAMPLE_FMSPC.to_vec(), vec![newer_tcb, SAMPLE_TCB]));
+
+		assert_ok!(register(1, sample_pck_cert_chain()));
+		// Remembered until the block the quote is bound to is too old
+		assert_eq!(1, UsedQuotes::<Test>::iter().filter(|(_, anchor)| *anchor == 0).count());
+		let worker_info = PhalaModule::worker_state(1);
+		assert_eq!(SAMPLE_MR_ENCLAVE.to_vec(), worker_info.mr_enclave);
+		assert_eq!(IAS_REPORT_TIMESTAMP, worker_info.last_updated);
+		assert_eq!(1, PhalaModule::machine_owner(&worker_info.machine_id));
+		// The quote cannot be replayed by anyone
+		assert_noop!(register(1, sample_pck_cert_chain()), Error::<Test>::DuplicateReport);
+		assert_noop!(register(2, sample_pck_cert_chain()), Error::<Test>::DuplicateReport);
+		// Neither with a malleated signature (r, n - s)
+		let mut malleated_quote = DCAP_QUOTE_SAMPLE.to_vec();
+		let s = &mut malleated_quote[468..500];
+		let order = hex!["ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551"];
+		let mut borrow = 0i16;
+		for i in (0..32).rev() {
+			let diff = order[i] as i16 - s[i] as i16 - borrow;
+			borrow = if diff < 0 { 1 } else { 0 };
+			s[i] = (diff + (borrow << 8)) as u8;
+		}
+		assert_noop!(
+			PhalaModule::register_worker_dcap(
+				Origin::signed(2), TEE_REPORT_SAMPLE.to_vec(), malleated_quote, sample_pck_cert_chain()),
+			Error::<Test>::DuplicateReport
+		);
+	});
+}
+
+#[test]
+fn test_register_worker_with_forged_dcap_quote() {
+	new_test_ext().execute_with(|| {
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		let register = |quote: Vec<u8>, chain: Vec<Vec<u8>>| PhalaModule::register_worker_dcap(
+			Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), quote, chain);
+		let tampered = |offset: usize| {
+			let mut quote = DCAP_QUOTE_SAMPLE.to_vec();
+			quote[offset] ^= 1;
+			quote
+		};
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		setup_sample_dcap_attestation();
+
+		// Malformed or unsupported quotes
+		assert_noop!(register(DCAP_QUOTE_SAMPLE[..600].to_vec(), sample_pck_cert_chain()), Error::<Test>::InvalidDCAPQuote);
+		assert_noop!(register(tampered(0), sample_pck_cert_chain()), Error::<Test>::InvalidDCAPQuote);
+		// Certificates not in a chain to the root
+		assert_noop!(
+			register(DCAP_QUOTE_SAMPLE.to_vec(), vec![DCAP_PCK_CA_CERTIFICATE.to_vec(), DCAP_PCK_CERTIFICATE.to_vec()]),
+			Error::<Test>::InvalidPCKCertChain
+		);
+		let ias_cert = base64::decode_config(&IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
+		assert_noop!(
+			register(DCAP_QUOTE_SAMPLE.to_vec(), vec![ias_cert, DCAP_PCK_CA_CERTIFICATE.to_vec()]),
+			Error::<Test>::InvalidPCKCertChain
+		);
+		// Tampered report of the Quoting Enclave
+		assert_noop!(register(tampered(564), sample_pck_cert_chain()), Error::<Test>::InvalidQEReport);
+		// Attestation key not certified by the Quoting Enclave
+		assert_noop!(register(tampered(500), sample_pck_cert_chain()), Error::<Test>::InvalidQEReport);
+		// Tampered measurement and report data
+		assert_noop!(register(tampered(112), sample_pck_cert_chain()), Error::<Test>::InvalidDCAPQuote);
+		assert_noop!(register(tampered(368), sample_pck_cert_chain()), Error::<Test>::InvalidDCAPQuote);
+		// Signed quote of another runtime
+		assert_noop!(
+			PhalaModule::register_worker_dcap(
+				Origin::signed(1), vec![0u8; 59], DCAP_QUOTE_SAMPLE.to_vec(), sample_pck_cert_chain()),
+			Error::<Test>::InvalidRuntimeInfoHash
+		);
+		// 2050-01-01, after the certificates expired
+		Timestamp::set_timestamp(2524608000000);
+		assert_noop!(register(DCAP_QUOTE_SAMPLE.to_vec(), sample_pck_cert_chain()), Error::<Test>::InvalidPCKCertChain);
+	});
+}
+
+#[test]
+fn test_dcap_quote_freshness() {
+	new_test_ext().execute_with(|| {
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		let register = || PhalaModule::register_worker_dcap(
+			Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), DCAP_QUOTE_SAMPLE.to_vec(), sample_pck_cert_chain());
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		setup_sample_dcap_attestation();
+
+		// The sample quote is bound to the genesis block
+		System::set_block_number(BlockHashCount::get() + 1);
+		assert_noop!(register(), Error::<Test>::OutdatedReport);
+		System::set_block_number(BlockHashCount::get());
+		// Not produced on this chain
+		frame_system::BlockHash::<Test>::insert(0, H256::repeat_byte(1));
+		assert_noop!(register(), Error::<Test>::InvalidRuntimeInfoHash);
+		frame_system::BlockHash::<Test>::insert(0, H256::repeat_byte(69));
+		assert_ok!(register());
+
+		// The replay key is kept as long as the quote is fresh
+		PhalaModule::on_initialize(BlockHashCount::get());
+		assert_eq!(1, UsedQuotes::<Test>::iter().count());
+		PhalaModule::on_initialize(BlockHashCount::get() + RoundInterval::get());
+		assert_eq!(0, UsedQuotes::<Test>::iter().count());
+	});
+}
+
+#[test]
+fn test_register_worker_with_revoked_pck_cert() {
+	new_test_ext().execute_with(|| {
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		setup_sample_dcap_attestation();
+		assert_ok!(PhalaModule::revoke_pck_certs(RawOrigin::Root.into(), vec![vec![1], SAMPLE_PCK_SERIAL.to_vec()]));
+		assert_noop!(
+			PhalaModule::register_worker_dcap(
+				Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), DCAP_QUOTE_SAMPLE.to_vec(), sample_pck_cert_chain()),
+			Error::<Test>::PCKCertRevoked
+		);
+	});
+}
+
+#[test]
+fn test_dcap_collateral() {
+	new_test_ext().execute_with(|| {
+		let subject = DCAP_ROOT_CA_SUBJECT.to_vec();
+		let spki = DCAP_ROOT_CA_SPKI.to_vec();
+		assert_noop!(
+			PhalaModule::force_add_dcap_trust_anchor(Origin::signed(1), subject.clone(), spki.clone()),
+			BadOrigin
+		);
+		assert_ok!(PhalaModule::force_add_dcap_trust_anchor(RawOrigin::Root.into(), subject.clone(), spki.clone()));
+		assert_noop!(
+			PhalaModule::force_add_dcap_trust_anchor(RawOrigin::Root.into(), subject.clone(), spki.clone()),
+			Error::<Test>::DCAPTrustAnchorExists
+		);
+		assert_eq!(1, PhalaModule::dcap_trust_anchors().len());
+		assert_ok!(PhalaModule::force_remove_dcap_trust_anchor(RawOrigin::Root.into(), spki.clone()));
+		assert_eq!(0, PhalaModule::dcap_trust_anchors().len());
+		assert_noop!(
+			PhalaModule::force_remove_dcap_trust_anchor(RawOrigin::Root.into(), spki),
+			Error::<Test>::DCAPTrustAnchorNotFound
+		);
+
+		assert_noop!(
+			PhalaModule::set_qe_identity(Origin::signed(1), SAMPLE_QE_MR_SIGNER.to_vec(), 1, 0),
+			BadOrigin
+		);
+		assert_noop!(
+			PhalaModule::set_qe_identity(RawOrigin::Root.into(), vec![0], 1, 0),
+			Error::<Test>::InvalidInput
+		);
+		assert_ok!(PhalaModule::set_qe_identity(RawOrigin::Root.into(), SAMPLE_QE_MR_SIGNER.to_vec(), 1, 0));
+		assert_eq!(Some(1), PhalaModule::qe_identity().map(|qe| qe.isv_prod_id));
+
+		assert_noop!(
+			PhalaModule::set_tcb_levels(Origin::signed(1), SAMPLE_FMSPC.to_vec(), vec![SAMPLE_TCB]),
+			BadOrigin
+		);
+		assert_noop!(
+			PhalaModule::set_tcb_levels(RawOrigin::Root.into(), vec![0], vec![SAMPLE_TCB]),
+			Error::<Test>::InvalidInput
+		);
+		assert_ok!(PhalaModule::set_tcb_levels(RawOrigin::Root.into(), SAMPLE_FMSPC.to_vec(), vec![SAMPLE_TCB]));
+		assert_eq!(vec![SAMPLE_TCB], PhalaModule::tcb_levels(SAMPLE_FMSPC.to_vec()));
+		assert_ok!(PhalaModule::set_tcb_levels(RawOrigin::Root.into(), SAMPLE_FMSPC.to_vec(), vec![]));
+		assert!(PhalaModule::tcb_levels(SAMPLE_FMSPC.to_vec()).is_empty());
+
+		assert_noop!(
+			PhalaModule::revoke_pck_certs(Origin::signed(1), vec![SAMPLE_PCK_SERIAL.to_vec()]),
+			BadOrigin
+		);
+		assert_ok!(PhalaModule::revoke_pck_certs(RawOrigin::Root.into(), vec![SAMPLE_PCK_SERIAL.to_vec()]));
+		assert!(PhalaModule::revoked_pck_cert(SAMPLE_PCK_SERIAL.to_vec()));
+	});
+}
+
+#[test]
+fn test_enclave_whitelist() {
+	new_test_ext().execute_with(|| {
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		let sig: Vec<u8> = base64::decode(&IAS_REPORT_SIGNATURE).expect("decode sig failed");
+		let sig_cert_dec: Vec<u8> = base64::decode_config(&IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
+		let register = || PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone());
+
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		assert_noop!(register(), Error::<Test>::EnclaveNotWhitelisted);
+		assert_noop!(
+			PhalaModule::add_enclave_measurement(Origin::signed(1), SAMPLE_MR_ENCLAVE.to_vec(), SAMPLE_MR_SIGNER.to_vec(), 0, 0),
+			BadOrigin
+		);
+		assert_noop!(
+			PhalaModule::add_enclave_measurement(RawOrigin::Root.into(), vec![0], SAMPLE_MR_SIGNER.to_vec(), 0, 0),
+			Error::<Test>::InvalidInput
+		);
+		// Mismatched signer
+		assert_ok!(PhalaModule::add_enclave_measurement(RawOrigin::Root.into(), SAMPLE_MR_ENCLAVE.to_vec(), vec![0u8; 32], 0, 0));
+		assert_noop!(register(), Error::<Test>::EnclaveNotWhitelisted);
+		// SVN lower than required
+		assert_ok!(PhalaModule::add_enclave_measurement(RawOrigin::Root.into(), SAMPLE_MR_ENCLAVE.to_vec(), SAMPLE_MR_SIGNER.to_vec(), 0, 1));
+		assert_noop!(register(), Error::<Test>::EnclaveNotWhitelisted);
+		// Mismatched product id
+		assert_ok!(PhalaModule::add_enclave_measurement(RawOrigin::Root.into(), SAMPLE_MR_ENCLAVE.to_vec(), SAMPLE_MR_SIGNER.to_vec(), 1, 0));
+		assert_noop!(register(), Error::<Test>::EnclaveNotWhitelisted);
+		// Matched
+		whitelist_sample_enclave();
+		assert_ok!(register());
+		assert_eq!(SAMPLE_MR_ENCLAVE.to_vec(), PhalaModule::worker_state(1).mr_enclave);
+		let machine_id = PhalaModule::worker_state(1).machine_id;
+		assert_eq!(1, PhalaModule::machine_owner(&machine_id));
+	});
+}
+
+#[test]
+fn test_revoke_enclave_measurement() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		let sig: Vec<u8> = base64::decode(&IAS_REPORT_SIGNATURE).expect("decode sig failed");
+		let sig_cert_dec: Vec<u8> = base64::decode_config(&IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
+
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(2), 2));
+		whitelist_sample_enclave();
+		assert_ok!(PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone()));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 2, vec![0], vec![1]));
+		let machine_id = PhalaModule::worker_state(1).machine_id;
+		events();
+
+		assert_noop!(
+			PhalaModule::revoke_enclave_measurement(Origin::signed(1), SAMPLE_MR_ENCLAVE.to_vec(), true, 2),
+			BadOrigin
+		);
+		// The witness must cover all the registered workers
+		assert_noop!(
+			PhalaModule::revoke_enclave_measurement(RawOrigin::Root.into(), SAMPLE_MR_ENCLAVE.to_vec(), true, 1),
+			Error::<Test>::InvalidInput
+		);
+		assert_ok!(PhalaModule::revoke_enclave_measurement(RawOrigin::Root.into(), SAMPLE_MR_ENCLAVE.to_vec(), true, 2));
+		assert_noop!(
+			PhalaModule::revoke_enclave_measurement(RawOrigin::Root.into(), SAMPLE_MR_ENCLAVE.to_vec(), true, 2),
+			Error::<Test>::EnclaveMeasurementNotFound
+		);
+		// Only the worker running the revoked build is evicted
+		assert_eq!(MinerState::Evicted, PhalaModule::worker_state(1).state);
+		assert_eq!(false, MachineOwner::<Test>::contains_key(&machine_id));
+		assert_eq!(vec![0], PhalaModule::worker_state(2).machine_id);
+		assert_eq!(MinerState::Idle, PhalaModule::worker_state(2).state);
+		assert_noop!(PhalaModule::start_mine(Origin::signed(1)), Error::<Test>::InvalidMinerState);
+		assert_eq!(
+			events().as_slice(),
+			[
+				TestEvent::phala(RawEvent::WorkerEvicted(1, machine_id)),
+				TestEvent::phala(RawEvent::EnclaveMeasurementRevoked(SAMPLE_MR_ENCLAVE.to_vec())),
+			]
+		);
+		assert_noop!(
+			PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig, sig_cert_dec),
+			Error::<Test>::EnclaveNotWhitelisted
+		);
+	});
+}
+
+#[test]
+fn test_scoring_params() {
+	// The default parameters keep the legacy formula: cores * (cpu_feature_level * 10 + 60)
+	let params = ScoringParams::default();
+	assert_eq!(Some(280), params.overall_score(&[4, 1]));
+	assert_eq!(Some(60), params.overall_score(&[1]));
+	assert_eq!(Some(100), params.overall_score(&[1, 4, 16384, 128, 1000]));
+	assert_eq!(None, params.overall_score(&[]));
+
+	let params = ScoringParams {
+		max_cores: 2,
+		baseline: 100,
+		feature_weights: vec![
+			FeatureWeight { weight: 20, cap: 2 },
+			FeatureWeight { weight: 1, cap: 8192 },
+		],
+	};
+	assert_eq!(Some(240), params.overall_score(&[4, 1]));
+	assert_eq!(Some(2 * (100 + 20 * 2 + 8192)), params.overall_score(&[8, 3, 16384]));
+}
+
+#[test]
+fn test_set_scoring_params() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		let sig: Vec<u8> = base64::decode(&IAS_REPORT_SIGNATURE).expect("decode sig failed");
+		let sig_cert_dec: Vec<u8> = base64::decode_config(&IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
+
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(2), 2));
+		whitelist_sample_enclave();
+		// The sample worker reports 4 cores with feature level 1
+		assert_ok!(PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig, sig_cert_dec));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 2, vec![0], vec![1]));
+		assert_eq!(280, PhalaModule::worker_state(1).score.unwrap().overall_score);
+		assert_eq!(100, PhalaModule::worker_state(2).score.unwrap().overall_score);
+		events();
+
+		let params = ScoringParams {
+			max_cores: 2,
+			baseline: 100,
+			feature_weights: vec![FeatureWeight { weight: 20, cap: 2 }],
+		};
+		assert_noop!(
+			PhalaModule::set_scoring_params(Origin::signed(1), params.clone(), 2),
+			BadOrigin
+		);
+		// The witness must cover all the registered workers
+		assert_noop!(
+			PhalaModule::set_scoring_params(RawOrigin::Root.into(), params.clone(), 1),
+			Error::<Test>::InvalidInput
+		);
+		assert_ok!(PhalaModule::set_scoring_params(RawOrigin::Root.into(), params.clone(), 2));
+		assert_eq!(params, PhalaModule::scoring_params());
+		// Rescored by the reported features
+		assert_eq!(240, PhalaModule::worker_state(1).score.unwrap().overall_score);
+		assert_eq!(vec![4, 1], PhalaModule::worker_state(1).score.unwrap().features);
+		assert_eq!(140, PhalaModule::worker_state(2).score.unwrap().overall_score);
+		assert_eq!(
+			events().as_slice(),
+			[TestEvent::phala(RawEvent::ScoringParamsSet(2))]
+		);
+	});
+}
+
 #[test]
 fn test_whitelist_works() {
 	new_test_ext().execute_with(|| {
+		whitelist_sample_enclave();
 		// Set block number to 1 to test the events
 		System::set_block_number(1);
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
 
 		let sig: Vec<u8> = base64::decode(&IAS_REPORT_SIGNATURE).expect("decode sig failed");
 		let sig_cert_dec: Vec<u8> = base64::decode_config(&IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
@@ -149,10 +795,10 @@ fn test_whitelist_works() {
 		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
 		assert_ok!(PhalaModule::set_stash(Origin::signed(2), 2));
 
-		// TODO: Handle RA report replay attack
 		assert_ok!(PhalaModule::register_worker(Origin::signed(1), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone()));
 		let machine_id = &PhalaModule::worker_state(1).machine_id;
 		assert_eq!(true, machine_id.len() > 0);
+		forget_used_reports();
 		assert_ok!(PhalaModule::register_worker(Origin::signed(2), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone()));
 		let machine_id2 = &PhalaModule::worker_state(2).machine_id;
 		assert_eq!(true, machine_id2.len() > 0);
@@ -206,14 +852,836 @@ fn test_force_register_worker() {
 	});
 }
 
+#[test]
+fn test_unregister_worker() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		let sig: Vec<u8> = base64::decode(&IAS_REPORT_SIGNATURE).expect("decode sig failed");
+		let sig_cert_dec: Vec<u8> = base64::decode_config(&IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
+		whitelist_sample_enclave();
+
+		assert_noop!(PhalaModule::unregister_worker(Origin::signed(1)), Error::<Test>::ControllerNotFound);
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 11));
+		assert_noop!(PhalaModule::unregister_worker(Origin::signed(11)), Error::<Test>::MinerNotFound);
+		assert_ok!(PhalaModule::register_worker(Origin::signed(11), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone()));
+		let machine_id = PhalaModule::worker_state(1).machine_id;
+		// Must stop mining first
+		assert_ok!(PhalaModule::start_mine(Origin::signed(11)));
+		assert_noop!(PhalaModule::unregister_worker(Origin::signed(11)), Error::<Test>::InvalidMinerState);
+		assert_ok!(PhalaModule::stop_mine(Origin::signed(11)));
+		PhalaModule::on_initialize(5);
+		events();
+
+		assert_ok!(PhalaModule::unregister_worker(Origin::signed(11)));
+		assert_eq!(false, WorkerState::<Test>::contains_key(1));
+		assert_eq!(false, MachineOwner::<Test>::contains_key(&machine_id));
+		assert_eq!(None, PhalaModule::last_heartbeat(1));
+		assert_eq!(Some(11), PhalaModule::machine_cooldown(&machine_id));
+		assert_eq!(
+			events().as_slice(),
+			[TestEvent::phala(RawEvent::WorkerUnregistered(1, machine_id.clone()))]
+		);
+		// The stash is kept
+		assert_eq!(11, PhalaModule::stash_state(1).controller);
+
+		// The machine cannot come back during the cooldown
+		System::set_block_number(11);
+		assert_noop!(
+			PhalaModule::register_worker(Origin::signed(11), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone()),
+			Error::<Test>::InCooldown
+		);
+		System::set_block_number(12);
+		forget_used_reports();
+		assert_ok!(PhalaModule::register_worker(Origin::signed(11), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig, sig_cert_dec));
+		assert_eq!(None, PhalaModule::machine_cooldown(&machine_id));
+	});
+}
+
+#[test]
+fn test_remove_stash() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 11));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 1, vec![1], vec![1]));
+		// Only the stash can remove itself
+		assert_noop!(PhalaModule::remove_stash(Origin::signed(11)), Error::<Test>::NotStash);
+		// Must claim the reward first
+		PendingRewards::<Test>::insert(1, 10);
+		assert_noop!(PhalaModule::remove_stash(Origin::signed(1)), Error::<Test>::PendingRewardNotClaimed);
+		assert_ok!(PhalaModule::claim_reward(Origin::signed(2), 1));
+		// Must stop mining first
+		assert_ok!(PhalaModule::start_mine(Origin::signed(11)));
+		assert_noop!(PhalaModule::remove_stash(Origin::signed(1)), Error::<Test>::InvalidMinerState);
+		WorkerState::<Test>::mutate(1, |info| info.state = MinerState::Offline);
+		events();
+
+		assert_ok!(PhalaModule::remove_stash(Origin::signed(1)));
+		assert_eq!(false, StashState::<Test>::contains_key(1));
+		assert_eq!(false, Stash::<Test>::contains_key(11));
+		assert_eq!(false, WorkerState::<Test>::contains_key(1));
+		assert_eq!(false, MachineOwner::<Test>::contains_key(vec![1]));
+		assert_eq!(Some(11), PhalaModule::stash_cooldown(1));
+		assert_eq!(
+			events().as_slice(),
+			[
+				TestEvent::phala(RawEvent::WorkerUnregistered(1, vec![1])),
+				TestEvent::phala(RawEvent::StashRemoved(1)),
+			]
+		);
+		assert_noop!(PhalaModule::start_mine(Origin::signed(11)), Error::<Test>::ControllerNotFound);
+
+		// The stash cannot come back during the cooldown
+		System::set_block_number(11);
+		assert_noop!(PhalaModule::set_stash(Origin::signed(1), 11), Error::<Test>::InCooldown);
+		System::set_block_number(12);
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 11));
+		assert_eq!(None, PhalaModule::stash_cooldown(1));
+	});
+}
+
+#[test]
+fn test_worker_bond() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		Timestamp::set_timestamp(IAS_REPORT_TIMESTAMP);
+		set_worker_bond(100);
+		let sig: Vec<u8> = base64::decode(&IAS_REPORT_SIGNATURE).expect("decode sig failed");
+		let sig_cert_dec: Vec<u8> = base64::decode_config(&IAS_REPORT_SIGNING_CERTIFICATE, base64::STANDARD).expect("decode cert failed");
+		whitelist_sample_enclave();
+		drop(Balances::deposit_creating(&1, 150));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 11));
+
+		// Must bond `WorkerBond` to register
+		assert_noop!(
+			PhalaModule::register_worker(Origin::signed(11), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone()),
+			Error::<Test>::InsufficientBond
+		);
+		assert_noop!(PhalaModule::bond_worker(Origin::signed(11), 100), Error::<Test>::NotStash);
+		assert_noop!(PhalaModule::bond_worker(Origin::signed(1), 200), Error::<Test>::CannotReserveBond);
+		assert_ok!(PhalaModule::bond_worker(Origin::signed(1), 60));
+		assert_noop!(
+			PhalaModule::register_worker(Origin::signed(11), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig.clone(), sig_cert_dec.clone()),
+			Error::<Test>::InsufficientBond
+		);
+		assert_ok!(PhalaModule::bond_worker(Origin::signed(1), 40));
+		assert_eq!(100, PhalaModule::worker_bond(1));
+		assert_eq!(100, Balances::reserved_balance(1));
+		assert_eq!(50, Balances::free_balance(1));
+		assert_ok!(PhalaModule::register_worker(Origin::signed(11), TEE_REPORT_SAMPLE.to_vec(), IAS_REPORT_SAMPLE.to_vec(), sig, sig_cert_dec));
+		let machine_id = PhalaModule::worker_state(1).machine_id;
+
+		// The bond starts unbonding when the worker is unregistered
+		assert_ok!(PhalaModule::start_mine(Origin::signed(11)));
+		assert_ok!(PhalaModule::stop_mine(Origin::signed(11)));
+		PhalaModule::on_initialize(5);
+		events();
+		assert_noop!(PhalaModule::withdraw_unbonded(Origin::signed(1)), Error::<Test>::NoUnbondingBond);
+		assert_ok!(PhalaModule::unregister_worker(Origin::signed(11)));
+		assert_eq!(0, PhalaModule::worker_bond(1));
+		assert_eq!(Some((100, 11)), PhalaModule::unbonding_bond(1));
+		assert_eq!(
+			events().as_slice(),
+			[
+				TestEvent::phala(RawEvent::WorkerUnregistered(1, machine_id)),
+				TestEvent::phala(RawEvent::WorkerUnbonded(1, 100)),
+			]
+		);
+		// The unbonding bond doesn't count
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 1, vec![1], vec![1]));
+		assert_noop!(PhalaModule::start_mine(Origin::signed(11)), Error::<Test>::InsufficientBond);
+
+		// Released after `BondUnbondingPeriod` blocks
+		System::set_block_number(11);
+		assert_noop!(PhalaModule::withdraw_unbonded(Origin::signed(1)), Error::<Test>::StillUnbonding);
+		System::set_block_number(12);
+		assert_ok!(PhalaModule::withdraw_unbonded(Origin::signed(1)));
+		assert_eq!(None, PhalaModule::unbonding_bond(1));
+		assert_eq!(0, Balances::reserved_balance(1));
+		assert_eq!(150, Balances::free_balance(1));
+
+		// Removing the stash unbonds its bond too
+		assert_ok!(PhalaModule::bond_worker(Origin::signed(1), 100));
+		assert_ok!(PhalaModule::remove_stash(Origin::signed(1)));
+		assert_eq!(Some((100, 22)), PhalaModule::unbonding_bond(1));
+	});
+}
+
+#[test]
+fn test_push_command() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		drop(Balances::deposit_creating(&1, 100));
+		assert_noop!(
+			PhalaModule::push_command(Origin::signed(1), 1, vec![0; 4]),
+			Error::<Test>::InvalidContract
+		);
+		assert_ok!(PhalaModule::register_contract(RawOrigin::Root.into(), 1, 10, H256::zero(), vec![1; 33]));
+		assert_ok!(PhalaModule::register_contract(RawOrigin::Root.into(), 2, 10, H256::zero(), vec![2; 33]));
+		assert_noop!(
+			PhalaModule::push_command(Origin::signed(1), 1, vec![0; 17]),
+			Error::<Test>::CommandTooLarge
+		);
+		assert_noop!(
+			PhalaModule::push_command(Origin::signed(2), 1, vec![0; 4]),
+			Error::<Test>::CannotPayCommandFee
+		);
+		events();
+		// 2 per byte
+		assert_ok!(PhalaModule::push_command(Origin::signed(1), 1, vec![0; 16]));
+		assert_eq!(68, Balances::free_balance(1));
+		assert_ok!(PhalaModule::push_command(Origin::signed(1), 2, vec![1; 4]));
+		assert_ok!(PhalaModule::push_command(Origin::signed(1), 1, vec![2; 4]));
+		assert_eq!(52, Balances::free_balance(1));
+		// Empty commands are free
+		assert_ok!(PhalaModule::push_command(Origin::signed(2), 2, vec![]));
+		// Sequences are counted per contract
+		assert_eq!(2, PhalaModule::contract_command_number(1));
+		assert_eq!(2, PhalaModule::contract_command_number(2));
+		assert_eq!(Some(4), PhalaModule::command_number());
+		let pushed: Vec<_> = events().into_iter()
+			.filter_map(|evt| match evt {
+				TestEvent::phala(RawEvent::CommandPushed(_, contract_id, _, sequence)) => Some((contract_id, sequence)),
+				_ => None,
+			})
+			.collect();
+		assert_eq!(pushed, vec![(1, 0), (2, 0), (1, 1), (2, 1)]);
+	});
+}
+
+#[test]
+fn test_call_quota() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let info = DispatchInfo::default();
+		let ext = CheckCallQuota::<Test>::new();
+		let command = Call::PhalaModule(crate::Call::push_command(1, vec![1]));
+		let heartbeat = Call::PhalaModule(crate::Call::heartbeat(vec![1]));
+		let transfer = Call::PhalaModule(crate::Call::transfer_to_chain(vec![1]));
+		let transfers = Call::PhalaModule(crate::Call::transfer_to_chain_batch(vec![vec![1], vec![2]]));
+		let too_many_transfers = Call::PhalaModule(crate::Call::transfer_to_chain_batch(vec![vec![1]; 3]));
+		let unlimited = Call::PhalaModule(crate::Call::stop_mine());
+		// Not prioritized over the other transactions
+		assert_eq!(Ok(ValidTransaction::default()), ext.validate(&1, &command, &info, 0));
+		assert_ok!(ext.clone().pre_dispatch(&1, &command, &info, 0));
+		assert_ok!(ext.validate(&1, &command, &info, 0));
+		assert_ok!(ext.clone().pre_dispatch(&1, &command, &info, 0));
+		// Exhausted in the current window
+		assert_eq!(
+			Err(InvalidTransaction::ExhaustsResources.into()),
+			ext.validate(&1, &command, &info, 0)
+		);
+		assert_eq!(
+			Err(InvalidTransaction::ExhaustsResources.into()),
+			ext.clone().pre_dispatch(&1, &command, &info, 0)
+		);
+		// Counted per account and per kind of call
+		assert_ok!(ext.validate(&2, &command, &info, 0));
+		assert_ok!(ext.validate(&1, &heartbeat, &info, 0));
+		// Each message of a batch is counted
+		assert_eq!(
+			Err(InvalidTransaction::ExhaustsResources.into()),
+			ext.validate(&1, &too_many_transfers, &info, 0)
+		);
+		assert_ok!(ext.clone().pre_dispatch(&1, &transfers, &info, 0));
+		assert_eq!((0, 2), PhalaModule::call_quota_usage(1, RateLimitedCall::Transfer));
+		assert!(ext.validate(&1, &transfer, &info, 0).is_err());
+		// Other calls are not limited
+		assert_ok!(ext.validate(&1, &unlimited, &info, 0));
+		assert_ok!(ext.clone().pre_dispatch(&1, &unlimited, &info, 0));
+		// Renewed in the next window
+		System::set_block_number(4);
+		assert!(ext.validate(&1, &command, &info, 0).is_err());
+		System::set_block_number(5);
+		assert_ok!(ext.validate(&1, &command, &info, 0));
+		assert_ok!(ext.clone().pre_dispatch(&1, &command, &info, 0));
+		assert_eq!((5, 1), PhalaModule::call_quota_usage(1, RateLimitedCall::Command));
+	});
+}
+
+#[test]
+fn test_call_quota_pruned() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let info = DispatchInfo::default();
+		let ext = CheckCallQuota::<Test>::new();
+		let command = Call::PhalaModule(crate::Call::push_command(1, vec![1]));
+		assert_ok!(ext.clone().pre_dispatch(&1, &command, &info, 0));
+		assert_ok!(ext.clone().pre_dispatch(&2, &command, &info, 0));
+		PhalaModule::on_initialize(4);
+		assert_eq!(2, crate::CallQuotaUsage::<Test>::iter().count());
+		// The usage of the previous window is forgotten when the next one starts
+		PhalaModule::on_initialize(5);
+		assert_eq!(0, crate::CallQuotaUsage::<Test>::iter().count());
+	});
+}
+
+#[test]
+fn test_contract_registry() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let code_hash = H256::repeat_byte(1);
+		assert_noop!(
+			PhalaModule::register_contract(Origin::signed(10), 1, 10, code_hash, vec![1; 33]),
+			BadOrigin
+		);
+		assert_noop!(
+			PhalaModule::register_contract(RawOrigin::Root.into(), 1, 10, code_hash, vec![1; 32]),
+			Error::<Test>::InvalidPubKey
+		);
+		assert_ok!(PhalaModule::register_contract(RawOrigin::Root.into(), 1, 10, code_hash, vec![1; 33]));
+		assert_noop!(
+			PhalaModule::register_contract(RawOrigin::Root.into(), 1, 11, code_hash, vec![2; 33]),
+			Error::<Test>::ContractExists
+		);
+		assert_eq!(vec![1; 33], PhalaModule::contract_key(1));
+		// Assign to workers
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(2), 2));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 1, vec![1], vec![1]));
+		assert_noop!(
+			PhalaModule::assign_contract(RawOrigin::Root.into(), 1, vec![1, 2]),
+			Error::<Test>::MinerNotFound
+		);
+		assert_noop!(
+			PhalaModule::assign_contract(RawOrigin::Root.into(), 2, vec![1]),
+			Error::<Test>::ContractNotFound
+		);
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 2, vec![2], vec![2]));
+		events();
+		assert_ok!(PhalaModule::assign_contract(RawOrigin::Root.into(), 1, vec![1, 2]));
+		assert_eq!(vec![1, 2], PhalaModule::contract_assign(1));
+		// Retire by the owner
+		assert_noop!(
+			PhalaModule::retire_contract(Origin::signed(11), 1),
+			Error::<Test>::NotContractOwner
+		);
+		assert_ok!(PhalaModule::retire_contract(Origin::signed(10), 1));
+		assert!(PhalaModule::contract_assign(1).is_empty());
+		assert_eq!(
+			events().as_slice(),
+			[
+				TestEvent::phala(RawEvent::ContractAssigned(1, vec![vec![1], vec![2]])),
+				TestEvent::phala(RawEvent::ContractRetired(1)),
+			]
+		);
+		// Retired contracts don't accept commands anymore
+		assert_noop!(
+			PhalaModule::push_command(Origin::signed(1), 1, vec![0]),
+			Error::<Test>::ContractNotActive
+		);
+		assert_noop!(
+			PhalaModule::assign_contract(RawOrigin::Root.into(), 1, vec![1]),
+			Error::<Test>::ContractNotActive
+		);
+		assert_noop!(
+			PhalaModule::retire_contract(RawOrigin::Root.into(), 1),
+			Error::<Test>::ContractNotActive
+		);
+	});
+}
+
+#[test]
+fn test_runtime_info_ecdh_pubkey() {
+	let mut info = PRuntimeInfo {
+		version: 3,
+		machine_id: [1; 16],
+		pubkey: [2; 33],
+		features: vec![4, 1],
+		ecdh_pubkey: vec![4; ECDH_PUBKEY_LEN],
+	};
+	let decoded = PRuntimeInfo::decode(&mut &info.encode()[..]).unwrap();
+	assert_eq!(vec![4; ECDH_PUBKEY_LEN], decoded.ecdh_pubkey);
+	assert_eq!(vec![4, 1], decoded.features);
+	// Not encoded by the older pRuntimes
+	info.version = 2;
+	let encoded = info.encode();
+	assert_eq!(1 + 16 + 33 + 1 + 8, encoded.len());
+	let decoded = PRuntimeInfo::decode(&mut &encoded[..]).unwrap();
+	assert!(decoded.ecdh_pubkey.is_empty());
+}
+
+#[test]
+fn test_contract_ecdh_keys() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(PhalaModule::register_contract(RawOrigin::Root.into(), 1, 10, H256::zero(), vec![1; 33]));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(2), 2));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 1, vec![1], vec![1]));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 2, vec![2], vec![2]));
+		WorkerState::<Test>::mutate(1, |info| info.ecdh_pubkey = vec![1; ECDH_PUBKEY_LEN]);
+		// Workers without an ECDH key are skipped
+		assert_ok!(PhalaModule::assign_contract(RawOrigin::Root.into(), 1, vec![1, 2]));
+		assert_eq!(vec![vec![1; ECDH_PUBKEY_LEN]], PhalaModule::contract_ecdh_keys(1));
+		assert_eq!(vec![1], PhalaModule::worker_contracts(1));
+		assert_eq!(vec![1], PhalaModule::worker_contracts(2));
+		// Unregistered workers are removed
+		assert_ok!(PhalaModule::unregister_worker(Origin::signed(1)));
+		assert!(PhalaModule::contract_ecdh_keys(1).is_empty());
+		// The workers previously assigned are replaced
+		assert_ok!(PhalaModule::assign_contract(RawOrigin::Root.into(), 1, vec![2]));
+		assert!(!WorkerContracts::<Test>::contains_key(1));
+		assert_eq!(vec![1], PhalaModule::worker_contracts(2));
+		assert_ok!(PhalaModule::retire_contract(RawOrigin::Root.into(), 1));
+		assert!(!crate::ContractEcdhKeys::contains_key(1));
+		assert!(!WorkerContracts::<Test>::contains_key(2));
+	});
+}
+
+#[test]
+fn test_contract_key_rotation() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let worker_sk = ecdsa_load_sk(&[1; 32]);
+		let old_sk = ecdsa_load_sk(&[2; 32]);
+		let new_sk = ecdsa_load_sk(&[3; 32]);
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(2), 2));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 1, vec![1], ecdsa_pubkey(&worker_sk)));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 2, vec![2], ecdsa_pubkey(&new_sk)));
+		assert_ok!(PhalaModule::register_contract(RawOrigin::Root.into(), 1, 10, H256::zero(), ecdsa_pubkey(&old_sk)));
+		assert_ok!(PhalaModule::set_egress_handler(RawOrigin::Root.into(), 1, Some(EgressHandler::Event)));
+		assert_ok!(PhalaModule::assign_contract(RawOrigin::Root.into(), 1, vec![1]));
+
+		// Only the assigned workers can rotate the key
+		assert_noop!(
+			PhalaModule::rotate_contract_key(Origin::signed(2), signed_key_rotation(&new_sk, 1, &new_sk, 1)),
+			Error::<Test>::WorkerNotAssigned
+		);
+		// Must be signed by the worker identity key
+		assert_noop!(
+			PhalaModule::rotate_contract_key(Origin::signed(1), signed_key_rotation(&new_sk, 1, &new_sk, 1)),
+			Error::<Test>::FailedToVerify
+		);
+		assert_noop!(
+			PhalaModule::rotate_contract_key(Origin::signed(1), signed_key_rotation(&worker_sk, 1, &new_sk, 2)),
+			Error::<Test>::BadMessageSequence
+		);
+		events();
+		assert_ok!(PhalaModule::rotate_contract_key(Origin::signed(1), signed_key_rotation(&worker_sk, 1, &new_sk, 1)));
+		assert_eq!(ecdsa_pubkey(&new_sk), PhalaModule::contract_key(1));
+		assert_eq!(Some((ecdsa_pubkey(&old_sk), 6)), PhalaModule::previous_contract_key(1));
+		assert_eq!(
+			events().as_slice(),
+			[TestEvent::phala(RawEvent::ContractKeyRotated(1, ecdsa_pubkey(&new_sk)))]
+		);
+		// Replay
+		assert_noop!(
+			PhalaModule::rotate_contract_key(Origin::signed(1), signed_key_rotation(&worker_sk, 1, &new_sk, 1)),
+			Error::<Test>::BadMessageSequence
+		);
+
+		// Both keys are accepted in the grace period
+		assert_ok!(PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&old_sk, 1, 1, vec![1])));
+		assert_ok!(PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&new_sk, 1, 2, vec![2])));
+		System::set_block_number(6);
+		assert_ok!(PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&old_sk, 1, 3, vec![3])));
+		// The previous key expires after the grace period
+		System::set_block_number(7);
+		assert_noop!(
+			PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&old_sk, 1, 4, vec![4])),
+			Error::<Test>::FailedToVerify
+		);
+		assert_ok!(PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&new_sk, 1, 4, vec![4])));
+	});
+}
+
 #[test]
 fn test_mine() {
 	new_test_ext().execute_with(|| {
 		assert_noop!(PhalaModule::start_mine(Origin::signed(1)), Error::<Test>::ControllerNotFound);
 		assert_noop!(PhalaModule::stop_mine(Origin::signed(1)), Error::<Test>::ControllerNotFound);
 		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 1));
+		assert_noop!(PhalaModule::start_mine(Origin::signed(1)), Error::<Test>::MinerNotFound);
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 1, vec![1], vec![1]));
+		assert_eq!(MinerState::Idle, PhalaModule::worker_state(1).state);
+		assert_noop!(PhalaModule::stop_mine(Origin::signed(1)), Error::<Test>::InvalidMinerState);
 		assert_ok!(PhalaModule::start_mine(Origin::signed(1)));
+		assert_eq!(MinerState::Mining, PhalaModule::worker_state(1).state);
+		assert_noop!(PhalaModule::start_mine(Origin::signed(1)), Error::<Test>::InvalidMinerState);
+		// Keeps mining until the end of the round
+		assert_ok!(PhalaModule::stop_mine(Origin::signed(1)));
+		assert_eq!(MinerState::PendingStop, PhalaModule::worker_state(1).state);
+		assert_noop!(PhalaModule::start_mine(Origin::signed(1)), Error::<Test>::InvalidMinerState);
+		assert_noop!(PhalaModule::stop_mine(Origin::signed(1)), Error::<Test>::InvalidMinerState);
+		PhalaModule::on_initialize(5);
+		assert_eq!(MinerState::Idle, PhalaModule::worker_state(1).state);
+		// An offline worker can restart or stop immediately
+		WorkerState::<Test>::mutate(1, |info| info.state = MinerState::Offline);
 		assert_ok!(PhalaModule::stop_mine(Origin::signed(1)));
+		assert_eq!(MinerState::Idle, PhalaModule::worker_state(1).state);
+		WorkerState::<Test>::mutate(1, |info| info.state = MinerState::Offline);
+		assert_ok!(PhalaModule::start_mine(Origin::signed(1)));
+		assert_eq!(MinerState::Mining, PhalaModule::worker_state(1).state);
+	});
+}
+
+#[test]
+fn test_migrate_worker_state() {
+	new_test_ext().execute_with(|| {
+		let old_info = |machine_id: u8, status: i32| OldWorkerInfo {
+			machine_id: vec![machine_id],
+			pubkey: vec![2],
+			last_updated: 3,
+			status,
+			score: Some(Score {
+				overall_score: 100,
+				features: vec![1, 4],
+			}),
+		};
+		frame_support::storage::unhashed::put(&WorkerState::<Test>::hashed_key_for(1), &old_info(1, 1));
+		frame_support::storage::unhashed::put(&WorkerState::<Test>::hashed_key_for(2), &old_info(2, 0));
+		StorageVersion::put(Releases::V1_0_0);
+
+		PhalaModule::on_runtime_upgrade();
+		assert_eq!(Releases::V6_0_0, StorageVersion::get());
+		let worker_info = PhalaModule::worker_state(1);
+		assert_eq!(MinerState::Mining, worker_info.state);
+		assert_eq!(vec![1], worker_info.machine_id);
+		assert_eq!(vec![2], worker_info.pubkey);
+		assert_eq!(Vec::<u8>::new(), worker_info.ecdh_pubkey);
+		assert_eq!(Vec::<u8>::new(), worker_info.mr_enclave);
+		assert_eq!(3, worker_info.last_updated);
+		assert_eq!(100, worker_info.score.unwrap().overall_score);
+		assert!(!worker_info.attestation_exempt);
+		assert_eq!(MinerState::Idle, PhalaModule::worker_state(2).state);
+
+		// Not migrated twice
+		PhalaModule::on_runtime_upgrade();
+		assert_eq!(MinerState::Mining, PhalaModule::worker_state(1).state);
+	});
+}
+
+#[test]
+fn test_migrate_worker_ecdh_pubkey() {
+	new_test_ext().execute_with(|| {
+		let info_v2 = WorkerInfoV2 {
+			machine_id: vec![1],
+			pubkey: vec![2],
+			mr_enclave: vec![3],
+			last_updated: 4,
+			state: MinerState::PendingStop,
+			score: None,
+		};
+		frame_support::storage::unhashed::put(&WorkerState::<Test>::hashed_key_for(1), &info_v2);
+		StorageVersion::put(Releases::V2_0_0);
+
+		PhalaModule::on_runtime_upgrade();
+		assert_eq!(Releases::V6_0_0, StorageVersion::get());
+		let worker_info = PhalaModule::worker_state(1);
+		assert_eq!(vec![2], worker_info.pubkey);
+		assert_eq!(Vec::<u8>::new(), worker_info.ecdh_pubkey);
+		assert_eq!(vec![3], worker_info.mr_enclave);
+		assert_eq!(4, worker_info.last_updated);
+		assert_eq!(MinerState::PendingStop, worker_info.state);
+	});
+}
+
+#[test]
+fn test_migrate_used_quotes() {
+	new_test_ext().execute_with(|| {
+		// Timestamped quotes
+		frame_support::storage::unhashed::put(&UsedQuotes::<Test>::hashed_key_for([1u8; 32]), &IAS_REPORT_TIMESTAMP);
+		frame_support::storage::unhashed::put(&UsedQuotes::<Test>::hashed_key_for([2u8; 32]), &IAS_REPORT_TIMESTAMP);
+		StorageVersion::put(Releases::V3_0_0);
+
+		PhalaModule::on_runtime_upgrade();
+		assert_eq!(Releases::V6_0_0, StorageVersion::get());
+		assert_eq!(0, UsedQuotes::<Test>::iter().count());
+	});
+}
+
+#[test]
+fn test_migrate_contract_assign() {
+	new_test_ext().execute_with(|| {
+		ContractAssign::<Test>::insert(1, vec![1, 2]);
+		ContractAssign::<Test>::insert(2, vec![2]);
+		StorageVersion::put(Releases::V4_0_0);
+
+		PhalaModule::on_runtime_upgrade();
+		assert_eq!(Releases::V6_0_0, StorageVersion::get());
+		assert_eq!(vec![1], PhalaModule::worker_contracts(1));
+		let mut contracts = PhalaModule::worker_contracts(2);
+		contracts.sort();
+		assert_eq!(vec![1, 2], contracts);
+	});
+}
+
+#[test]
+fn test_migrate_attestation_exempt() {
+	new_test_ext().execute_with(|| {
+		let info_v5 = |mr_enclave: Vec<u8>| WorkerInfoV5 {
+			machine_id: vec![1],
+			pubkey: vec![2],
+			ecdh_pubkey: vec![3],
+			mr_enclave,
+			last_updated: 4,
+			state: MinerState::Mining,
+			score: None,
+		};
+		// Attested
+		frame_support::storage::unhashed::put(&WorkerState::<Test>::hashed_key_for(1), &info_v5(vec![5]));
+		// Registered by the root
+		frame_support::storage::unhashed::put(&WorkerState::<Test>::hashed_key_for(2), &info_v5(Vec::new()));
+		ContractAssign::<Test>::insert(1, vec![1]);
+		WorkerContracts::<Test>::insert(1, vec![1]);
+		StorageVersion::put(Releases::V5_0_0);
+
+		PhalaModule::on_runtime_upgrade();
+		assert_eq!(Releases::V6_0_0, StorageVersion::get());
+		let worker_info = PhalaModule::worker_state(1);
+		assert!(!worker_info.attestation_exempt);
+		assert_eq!(vec![3], worker_info.ecdh_pubkey);
+		assert_eq!(vec![5], worker_info.mr_enclave);
+		assert_eq!(MinerState::Mining, worker_info.state);
+		assert!(PhalaModule::worker_state(2).attestation_exempt);
+		assert_eq!(vec![1], PhalaModule::worker_contracts(1));
+	});
+}
+
+#[test]
+fn test_mining_reward() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let raw_sk = hex!["0000000000000000000000000000000000000000000000000000000000000001"];
+		let pubkey = hex!["0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"].to_vec();
+		let sk = ecdsa_load_sk(&raw_sk);
+		// Worker 1 and 2 are mining, while worker 3 is registered but idle
+		for i in 1..=3 {
+			assert_ok!(PhalaModule::set_stash(Origin::signed(i), i + 10));
+			assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), i, vec![i as u8], pubkey.clone()));
+		}
+		WorkerState::<Test>::mutate(2, |info| info.score.as_mut().unwrap().overall_score = 300);
+		assert_ok!(PhalaModule::start_mine(Origin::signed(11)));
+		assert_ok!(PhalaModule::start_mine(Origin::signed(12)));
+		for i in 1..=3 {
+			assert_ok!(PhalaModule::heartbeat(Origin::signed(i + 10), signed_heartbeat(&sk, 1)));
+		}
+		assert_eq!(1, PhalaModule::round_heartbeats(1));
+		assert_eq!(0, PhalaModule::round_heartbeats(3));
+		events();
+
+		// Round reward (8 * 5) is distributed by score
+		PhalaModule::on_initialize(4);
+		assert_eq!(0, PhalaModule::round());
+		PhalaModule::on_initialize(5);
+		assert_eq!(1, PhalaModule::round());
+		assert_eq!(10, PhalaModule::pending_rewards(1));
+		assert_eq!(30, PhalaModule::pending_rewards(2));
+		assert_eq!(0, PhalaModule::pending_rewards(3));
+		assert_eq!(0, PhalaModule::round_heartbeats(1));
+		// Followed by the challenges of the two mining workers
+		let evts = events();
+		assert_eq!(5, evts.len());
+		assert!(evts.contains(&TestEvent::phala(RawEvent::RewardAccrued(1, 10))));
+		assert!(evts.contains(&TestEvent::phala(RawEvent::RewardAccrued(2, 30))));
+		assert_eq!(TestEvent::phala(RawEvent::RoundEnded(0, 40)), evts[2]);
+
+		// No heartbeat, no reward
+		System::set_block_number(6);
+		assert_ok!(PhalaModule::heartbeat(Origin::signed(12), signed_heartbeat(&sk, 6)));
+		PhalaModule::on_initialize(10);
+		assert_eq!(10, PhalaModule::pending_rewards(1));
+		assert_eq!(70, PhalaModule::pending_rewards(2));
+
+		// Payout with 20% commission to the target
+		assert_ok!(PhalaModule::set_payout_prefs(Origin::signed(12), Some(20), Some(100)));
+		assert_ok!(PhalaModule::claim_reward(Origin::signed(1), 2));
+		assert_eq!(14, Balances::free_balance(100));
+		assert_eq!(56, Balances::free_balance(2));
+		assert_eq!(0, PhalaModule::pending_rewards(2));
+		assert_noop!(PhalaModule::claim_reward(Origin::signed(1), 2), Error::<Test>::NoPendingReward);
+		assert_noop!(PhalaModule::claim_reward(Origin::signed(1), 4), Error::<Test>::StashNotFound);
+		// Default payout target is the stash itself
+		assert_ok!(PhalaModule::claim_reward(Origin::signed(1), 1));
+		assert_eq!(10, Balances::free_balance(1));
+	});
+}
+
+#[test]
+fn test_heartbeat_liveness() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let raw_sk = hex!["0000000000000000000000000000000000000000000000000000000000000001"];
+		let pubkey = hex!["0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"].to_vec();
+		let sk = ecdsa_load_sk(&raw_sk);
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 11));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 1, vec![1], pubkey));
+		assert_ok!(PhalaModule::start_mine(Origin::signed(11)));
+		assert_eq!(Some((1, 0)), PhalaModule::last_heartbeat(1));
+
+		// Ahead of the chain
+		assert_noop!(
+			PhalaModule::heartbeat(Origin::signed(11), signed_heartbeat(&sk, 2)),
+			Error::<Test>::HeartbeatOutOfRange
+		);
+		System::set_block_number(10);
+		// Too far behind the chain
+		assert_noop!(
+			PhalaModule::heartbeat(Origin::signed(11), signed_heartbeat(&sk, 6)),
+			Error::<Test>::HeartbeatOutOfRange
+		);
+		assert_ok!(PhalaModule::heartbeat(Origin::signed(11), signed_heartbeat(&sk, 8)));
+		assert_eq!(Some((10, 8)), PhalaModule::last_heartbeat(1));
+		// Regressed block number
+		assert_noop!(
+			PhalaModule::heartbeat(Origin::signed(11), signed_heartbeat(&sk, 7)),
+			Error::<Test>::StaleHeartbeat
+		);
+		assert_eq!(1, PhalaModule::round_heartbeats(1));
+		events();
+
+		// Still online within the window, which is checked at the end of each round
+		PhalaModule::on_initialize(20);
+		assert_eq!(MinerState::Mining, PhalaModule::worker_state(1).state);
+		assert!(!events().contains(&TestEvent::phala(RawEvent::WorkerOffline(1))));
+		// Missed the window
+		PhalaModule::on_initialize(21);
+		assert_eq!(MinerState::Mining, PhalaModule::worker_state(1).state);
+		PhalaModule::on_initialize(25);
+		assert_eq!(MinerState::Offline, PhalaModule::worker_state(1).state);
+		assert_eq!(0, PhalaModule::round_heartbeats(1));
+		assert!(events().contains(&TestEvent::phala(RawEvent::WorkerOffline(1))));
+		// Reported only once
+		PhalaModule::on_initialize(30);
+		assert!(!events().contains(&TestEvent::phala(RawEvent::WorkerOffline(1))));
+	});
+}
+
+#[test]
+fn test_healthy_miner_score() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		Timestamp::set_timestamp(1000);
+		assert_eq!(None, PhalaModule::healthy_miner_score(&11, 0));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 11));
+		assert_eq!(None, PhalaModule::healthy_miner_score(&11, 0));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 1, vec![1], vec![1]));
+		// Must be mining
+		assert_eq!(None, PhalaModule::healthy_miner_score(&11, 0));
+		assert_ok!(PhalaModule::start_mine(Origin::signed(11)));
+		let score = PhalaModule::worker_state(1).score.unwrap().overall_score;
+		assert_eq!(Some(score), PhalaModule::healthy_miner_score(&11, 0));
+
+		// Went offline
+		Timestamp::set_timestamp(2000);
+		PhalaModule::on_initialize(15);
+		assert_eq!(Some(2000), PhalaModule::last_offline(1));
+		assert_eq!(None, PhalaModule::healthy_miner_score(&11, 0));
+		// Back online, but it was offline since 1000
+		assert_ok!(PhalaModule::start_mine(Origin::signed(11)));
+		assert_eq!(None, PhalaModule::healthy_miner_score(&11, 1000));
+		assert_eq!(Some(score), PhalaModule::healthy_miner_score(&11, 2001));
+	});
+}
+
+#[test]
+fn test_challenge() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let raw_sk = hex!["0000000000000000000000000000000000000000000000000000000000000001"];
+		let sk = ecdsa_load_sk(&raw_sk);
+		let pubkey = ecdsa_pubkey(&sk);
+		// Worker 1 to 4 are mining, while worker 5 is idle
+		for i in 1..=5 {
+			assert_ok!(PhalaModule::set_stash(Origin::signed(i), i + 10));
+			assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), i, vec![i as u8], pubkey.clone()));
+			if i != 5 {
+				assert_ok!(PhalaModule::start_mine(Origin::signed(i + 10)));
+			}
+		}
+		events();
+
+		// At most 3 mining workers are challenged at the end of the round
+		System::set_block_number(5);
+		PhalaModule::on_initialize(5);
+		let challenged: Vec<u64> = (1..=5).filter(|i| PhalaModule::challenges(i).is_some()).collect();
+		assert_eq!(3, challenged.len());
+		assert!(!challenged.contains(&5));
+		let evts = events();
+		assert_eq!(4, evts.len());
+		for stash in challenged.iter() {
+			let challenge = PhalaModule::challenges(stash).unwrap();
+			assert_eq!(5, challenge.issued_at);
+			assert_eq!(10, challenge.deadline);
+			assert!(evts.contains(&TestEvent::phala(RawEvent::ChallengeIssued(*stash, vec![*stash as u8], challenge.nonce))));
+		}
+
+		let (answered, missed) = (challenged[0], challenged[1]);
+		let nonce = PhalaModule::challenges(answered).unwrap().nonce;
+		assert_noop!(
+			PhalaModule::respond_challenge(Origin::signed(15), signed_challenge_response(&sk, nonce, 5)),
+			Error::<Test>::ChallengeNotFound
+		);
+		// Bound to the nonce and the block of the challenge
+		assert_noop!(
+			PhalaModule::respond_challenge(Origin::signed(answered + 10), signed_challenge_response(&sk, [0u8; 32], 5)),
+			Error::<Test>::InvalidChallengeResponse
+		);
+		assert_noop!(
+			PhalaModule::respond_challenge(Origin::signed(answered + 10), signed_challenge_response(&sk, nonce, 4)),
+			Error::<Test>::InvalidChallengeResponse
+		);
+		let other_sk = ecdsa_load_sk(&hex!["0000000000000000000000000000000000000000000000000000000000000002"]);
+		assert_noop!(
+			PhalaModule::respond_challenge(Origin::signed(answered + 10), signed_challenge_response(&other_sk, nonce, 5)),
+			Error::<Test>::FailedToVerify
+		);
+		assert_ok!(PhalaModule::respond_challenge(Origin::signed(answered + 10), signed_challenge_response(&sk, nonce, 5)));
+		assert!(PhalaModule::challenges(answered).is_none());
+		assert_eq!(events().as_slice(), [TestEvent::phala(RawEvent::ChallengeAnswered(answered))]);
+
+		// The unanswered challenges expire after the deadline
+		PhalaModule::on_initialize(10);
+		assert!(PhalaModule::challenges(missed).is_some());
+		assert!(!events().contains(&TestEvent::phala(RawEvent::ChallengeMissed(missed))));
+		// Registering the worker again doesn't dodge the challenge
+		let reregistered = challenged[2];
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), reregistered, vec![reregistered as u8], pubkey.clone()));
+		assert!(PhalaModule::challenges(reregistered).is_some());
+		PhalaModule::on_initialize(11);
+		assert!(PhalaModule::challenges(missed).is_none());
+		assert_eq!(1, PhalaModule::missed_challenges(missed));
+		assert!(PhalaModule::challenges(reregistered).is_none());
+		assert_eq!(1, PhalaModule::missed_challenges(reregistered));
+		assert_eq!(MinerState::Offline, PhalaModule::worker_state(missed).state);
+		assert_eq!(0, PhalaModule::missed_challenges(answered));
+		assert_eq!(MinerState::Mining, PhalaModule::worker_state(answered).state);
+		assert!(events().contains(&TestEvent::phala(RawEvent::ChallengeMissed(missed))));
+	});
+}
+
+#[test]
+fn test_registry_queries() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let raw_sk = hex!["0000000000000000000000000000000000000000000000000000000000000001"];
+		let pubkey = hex!["0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"].to_vec();
+		let sk = ecdsa_load_sk(&raw_sk);
+		assert!(PhalaModule::stash_info(&1).is_none());
+		assert!(PhalaModule::worker_info(&1).is_none());
+		assert!(PhalaModule::worker_info_by_machine_id(&vec![1]).is_none());
+		assert!(PhalaModule::mining_status(&1).is_none());
+		assert!(PhalaModule::contract_pubkey(2).is_none());
+
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 11));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 1, vec![1], pubkey.clone()));
+		assert_ok!(PhalaModule::force_set_contract_key(RawOrigin::Root.into(), 2, pubkey.clone()));
+		assert_eq!(11, PhalaModule::stash_info(&1).unwrap().controller);
+		assert_eq!(pubkey, PhalaModule::worker_info(&1).unwrap().pubkey);
+		let (stash, worker_info) = PhalaModule::worker_info_by_machine_id(&vec![1]).unwrap();
+		assert_eq!(1, stash);
+		assert_eq!(vec![1], worker_info.machine_id);
+		assert_eq!(Some(pubkey), PhalaModule::contract_pubkey(2));
+
+		assert_ok!(PhalaModule::start_mine(Origin::signed(11)));
+		assert_ok!(PhalaModule::heartbeat(Origin::signed(11), signed_heartbeat(&sk, 1)));
+		assert_eq!(PhalaModule::mining_status(&1), Some(MiningStatus {
+			state: MinerState::Mining,
+			last_heartbeat: Some((1, 1)),
+			round_heartbeats: 1,
+			pending_reward: 0,
+		}));
 	});
 }
 
@@ -248,6 +1716,326 @@ fn test_transfer() {
 	});
 }
 
+#[test]
+fn test_transfer_batch() {
+	new_test_ext().execute_with(|| {
+		let sk = ecdsa_load_sk(&hex!["0000000000000000000000000000000000000000000000000000000000000001"]);
+		assert_ok!(PhalaModule::force_set_contract_key(RawOrigin::Root.into(), 2, ecdsa_pubkey(&sk)));
+		let imbalance = Balances::deposit_creating(&1, 100);
+		drop(imbalance);
+		assert_ok!(PhalaModule::transfer_to_tee(Origin::signed(1), 50));
+		let signed_transfer = |dest: u64, amount: Balance, sequence: u64| {
+			let transfer = Transfer::<u64, Balance> { dest, amount, sequence };
+			let signature = ecdsa_sign(&sk, &transfer);
+			TransferData { data: transfer, signature }.encode()
+		};
+
+		assert_noop!(
+			PhalaModule::transfer_to_chain_batch(Origin::signed(1), vec![]),
+			Error::<Test>::InvalidInput
+		);
+		// Not contiguous
+		assert_noop!(
+			PhalaModule::transfer_to_chain_batch(Origin::signed(1), vec![signed_transfer(2, 10, 1), signed_transfer(3, 10, 3)]),
+			Error::<Test>::BadMessageSequence
+		);
+		// A failed message reverts the whole batch
+		assert_noop!(
+			PhalaModule::transfer_to_chain_batch(Origin::signed(1), vec![signed_transfer(2, 10, 1), signed_transfer(3, 100, 2)]),
+			Error::<Test>::CannotWithdraw
+		);
+		assert_ok!(PhalaModule::transfer_to_chain_batch(Origin::signed(1), vec![
+			signed_transfer(2, 10, 1),
+			signed_transfer(3, 20, 2),
+			signed_transfer(2, 5, 3),
+		]));
+		assert_eq!(15, Balances::free_balance(2));
+		assert_eq!(20, Balances::free_balance(3));
+		assert_eq!(3, PhalaModule::ingress_sequence(2));
+		// Replay
+		assert_noop!(
+			PhalaModule::transfer_to_chain_batch(Origin::signed(1), vec![signed_transfer(2, 5, 3)]),
+			Error::<Test>::BadMessageSequence
+		);
+	});
+}
+
+#[test]
+fn test_egress_message() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let raw_sk = hex!["0000000000000000000000000000000000000000000000000000000000000001"];
+		let pubkey = hex!["0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"].to_vec();
+		let sk = ecdsa_load_sk(&raw_sk);
+		let imbalance = Balances::deposit_creating(&1, 100);
+		drop(imbalance);
+		assert_ok!(PhalaModule::transfer_to_tee(Origin::signed(1), 50));
+		for contract_id in 10..13 {
+			assert_ok!(PhalaModule::force_set_contract_key(RawOrigin::Root.into(), contract_id, pubkey.clone()));
+		}
+		assert_noop!(
+			PhalaModule::set_egress_handler(Origin::signed(1), 10, Some(EgressHandler::Balance)),
+			BadOrigin
+		);
+		assert_ok!(PhalaModule::set_egress_handler(RawOrigin::Root.into(), 10, Some(EgressHandler::Balance)));
+		assert_ok!(PhalaModule::set_egress_handler(RawOrigin::Root.into(), 11, Some(EgressHandler::Asset)));
+		assert_ok!(PhalaModule::set_egress_handler(RawOrigin::Root.into(), 12, Some(EgressHandler::Event)));
+		events();
+
+		// Balance release
+		let payload = BalanceRelease::<u64, Balance> { dest: 2, amount: 10 }.encode();
+		assert_noop!(
+			PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&sk, 10, 2, payload.clone())),
+			Error::<Test>::BadMessageSequence
+		);
+		assert_ok!(PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&sk, 10, 1, payload.clone())));
+		assert_eq!(10, Balances::free_balance(2));
+		assert_eq!(1, PhalaModule::ingress_sequence(10));
+		// Replay
+		assert_noop!(
+			PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&sk, 10, 1, payload)),
+			Error::<Test>::BadMessageSequence
+		);
+		// Undecodable payload
+		assert_noop!(
+			PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&sk, 10, 2, vec![1])),
+			Error::<Test>::InvalidMessage
+		);
+		// Asset release
+		let payload = AssetRelease::<u64, Balance> { asset_id: 7, dest: 2, amount: 5 }.encode();
+		assert_ok!(PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&sk, 11, 1, payload)));
+		// Arbitrary event
+		assert_ok!(PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&sk, 12, 1, b"hello".to_vec())));
+		let phala_events: Vec<TestEvent> = events().into_iter()
+			.filter(|evt| match evt { TestEvent::phala(_) => true, _ => false })
+			.collect();
+		assert_eq!(
+			phala_events.as_slice(),
+			[
+				TestEvent::phala(RawEvent::TransferToChain(2u64.encode(), 10, 1)),
+				TestEvent::phala(RawEvent::EgressMessageHandled(10, 1)),
+				TestEvent::phala(RawEvent::AssetReleased(11, 7, 2, 5)),
+				TestEvent::phala(RawEvent::EgressMessageHandled(11, 1)),
+				TestEvent::phala(RawEvent::ContractEvent(12, b"hello".to_vec())),
+				TestEvent::phala(RawEvent::EgressMessageHandled(12, 1)),
+			]
+		);
+
+		// Signed by a wrong key
+		let other_sk = ecdsa_load_sk(&hex!["0000000000000000000000000000000000000000000000000000000000000002"]);
+		assert_noop!(
+			PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&other_sk, 12, 2, vec![])),
+			Error::<Test>::FailedToVerify
+		);
+		// Unregistered handler
+		assert_ok!(PhalaModule::set_egress_handler(RawOrigin::Root.into(), 12, None));
+		assert_noop!(
+			PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&sk, 12, 2, vec![])),
+			Error::<Test>::EgressHandlerNotFound
+		);
+	});
+}
+
+#[test]
+fn test_threshold_egress() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let contract_sk = ecdsa_load_sk(&[10; 32]);
+		let worker_sks: Vec<secp256k1::SecretKey> = (1..5).map(|i| ecdsa_load_sk(&[i; 32])).collect();
+		for (i, sk) in worker_sks.iter().enumerate() {
+			let stash = i as u64 + 1;
+			assert_ok!(PhalaModule::set_stash(Origin::signed(stash), stash));
+			assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), stash, vec![stash as u8], ecdsa_pubkey(sk)));
+		}
+		assert_ok!(PhalaModule::register_contract(RawOrigin::Root.into(), 1, 10, H256::zero(), ecdsa_pubkey(&contract_sk)));
+		assert_ok!(PhalaModule::set_egress_handler(RawOrigin::Root.into(), 1, Some(EgressHandler::Event)));
+		assert_ok!(PhalaModule::assign_contract(RawOrigin::Root.into(), 1, vec![1, 2, 3]));
+		assert_noop!(
+			PhalaModule::sign_egress_message(Origin::signed(1), signed_egress_message(&worker_sks[0], 1, 1, b"a".to_vec()), 0),
+			Error::<Test>::EgressThresholdNotSet
+		);
+		assert_noop!(
+			PhalaModule::set_egress_threshold(Origin::signed(1), 1, Some(2)),
+			BadOrigin
+		);
+		assert_noop!(
+			PhalaModule::set_egress_threshold(RawOrigin::Root.into(), 1, Some(0)),
+			Error::<Test>::InvalidInput
+		);
+		assert_ok!(PhalaModule::set_egress_threshold(RawOrigin::Root.into(), 1, Some(2)));
+		events();
+
+		// The contract key alone is not accepted anymore
+		assert_noop!(
+			PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&contract_sk, 1, 1, b"a".to_vec())),
+			Error::<Test>::EgressThresholdRequired
+		);
+		// Only the assigned workers can sign, with their identity keys
+		assert_noop!(
+			PhalaModule::sign_egress_message(Origin::signed(4), signed_egress_message(&worker_sks[3], 1, 1, b"a".to_vec()), 0),
+			Error::<Test>::WorkerNotAssigned
+		);
+		assert_noop!(
+			PhalaModule::sign_egress_message(Origin::signed(1), signed_egress_message(&contract_sk, 1, 1, b"a".to_vec()), 0),
+			Error::<Test>::FailedToVerify
+		);
+		assert_noop!(
+			PhalaModule::sign_egress_message(Origin::signed(1), signed_egress_message(&worker_sks[0], 1, 2, b"a".to_vec()), 0),
+			Error::<Test>::BadMessageSequence
+		);
+		assert_ok!(PhalaModule::sign_egress_message(Origin::signed(1), signed_egress_message(&worker_sks[0], 1, 1, b"a".to_vec()), 0));
+		assert_noop!(
+			PhalaModule::sign_egress_message(Origin::signed(1), signed_egress_message(&worker_sks[0], 1, 1, b"a".to_vec()), 1),
+			Error::<Test>::DuplicateEgressSignature
+		);
+		// The witness must cover the existing signatures
+		assert_noop!(
+			PhalaModule::sign_egress_message(Origin::signed(3), signed_egress_message(&worker_sks[2], 1, 1, b"a".to_vec()), 0),
+			Error::<Test>::InvalidInput
+		);
+		// A conflicting message collects its signatures separately
+		assert_ok!(PhalaModule::sign_egress_message(Origin::signed(2), signed_egress_message(&worker_sks[1], 1, 1, b"b".to_vec()), 0));
+		let message_a = EgressMessage { contract_id: 1, sequence: 1, payload: b"a".to_vec() };
+		let message_b = EgressMessage { contract_id: 1, sequence: 1, payload: b"b".to_vec() };
+		assert_eq!(vec![1], PhalaModule::pending_egress(1, BlakeTwo256::hash_of(&message_a)).unwrap().signers);
+		assert_eq!(vec![2], PhalaModule::pending_egress(1, BlakeTwo256::hash_of(&message_b)).unwrap().signers);
+		assert_eq!(0, PhalaModule::ingress_sequence(1));
+		// The second signature executes the message and drops the conflicting one
+		assert_ok!(PhalaModule::sign_egress_message(Origin::signed(3), signed_egress_message(&worker_sks[2], 1, 1, b"a".to_vec()), 1));
+		assert_eq!(1, PhalaModule::ingress_sequence(1));
+		assert_eq!(None, PhalaModule::pending_egress(1, BlakeTwo256::hash_of(&message_a)));
+		assert_eq!(None, PhalaModule::pending_egress(1, BlakeTwo256::hash_of(&message_b)));
+		assert_noop!(
+			PhalaModule::sign_egress_message(Origin::signed(2), signed_egress_message(&worker_sks[1], 1, 1, b"a".to_vec()), 0),
+			Error::<Test>::BadMessageSequence
+		);
+		assert_eq!(
+			events().as_slice(),
+			[
+				TestEvent::phala(RawEvent::EgressMessageSigned(1, 1, 1)),
+				TestEvent::phala(RawEvent::EgressMessageSigned(1, 1, 2)),
+				TestEvent::phala(RawEvent::ContractEvent(1, b"a".to_vec())),
+				TestEvent::phala(RawEvent::EgressMessageHandled(1, 1)),
+			]
+		);
+
+		// The partial signatures expire after `EgressSignatureTimeout` blocks
+		assert_ok!(PhalaModule::sign_egress_message(Origin::signed(1), signed_egress_message(&worker_sks[0], 1, 2, b"c".to_vec()), 0));
+		let message_c = EgressMessage { contract_id: 1, sequence: 2, payload: b"c".to_vec() };
+		PhalaModule::on_initialize(6);
+		assert!(PhalaModule::pending_egress(1, BlakeTwo256::hash_of(&message_c)).is_some());
+		events();
+		PhalaModule::on_initialize(7);
+		assert_eq!(None, PhalaModule::pending_egress(1, BlakeTwo256::hash_of(&message_c)));
+		assert_eq!(
+			events().as_slice(),
+			[TestEvent::phala(RawEvent::EgressMessageExpired(1, 2))]
+		);
+
+		// Back to the contract key
+		assert_ok!(PhalaModule::sign_egress_message(Origin::signed(1), signed_egress_message(&worker_sks[0], 1, 2, b"c".to_vec()), 0));
+		assert_ok!(PhalaModule::set_egress_threshold(RawOrigin::Root.into(), 1, None));
+		assert_eq!(0, PendingEgress::<Test>::iter_prefix_values(1).count());
+		assert_ok!(PhalaModule::push_egress_message(Origin::signed(1), signed_egress_message(&contract_sk, 1, 2, b"c".to_vec())));
+		assert_eq!(2, PhalaModule::ingress_sequence(1));
+	});
+}
+
+#[test]
+fn test_egress_equivocation() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		set_worker_bond(100);
+		let worker_sk = ecdsa_load_sk(&[1; 32]);
+		let other_sk = ecdsa_load_sk(&[2; 32]);
+		drop(Balances::deposit_creating(&1, 300));
+		assert_ok!(PhalaModule::set_stash(Origin::signed(1), 11));
+		assert_ok!(PhalaModule::bond_worker(Origin::signed(1), 100));
+		assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), 1, vec![1], ecdsa_pubkey(&worker_sk)));
+		assert_ok!(PhalaModule::start_mine(Origin::signed(11)));
+		// Funds being unbonded are slashable too
+		UnbondingBonds::<Test>::insert(1, (50, 20));
+		assert_ok!(Balances::reserve(&1, 50));
+
+		let report = |first: Vec<u8>, second: Vec<u8>| PhalaModule::report_egress_equivocation(Origin::signed(2), 1, first, second);
+		// Not conflicting
+		assert_noop!(
+			report(signed_egress_message(&worker_sk, 1, 1, b"a".to_vec()), signed_egress_message(&worker_sk, 1, 1, b"a".to_vec())),
+			Error::<Test>::InvalidEquivocationProof
+		);
+		assert_noop!(
+			report(signed_egress_message(&worker_sk, 1, 1, b"a".to_vec()), signed_egress_message(&worker_sk, 1, 2, b"b".to_vec())),
+			Error::<Test>::InvalidEquivocationProof
+		);
+		assert_noop!(
+			report(signed_egress_message(&worker_sk, 1, 1, b"a".to_vec()), signed_egress_message(&worker_sk, 2, 1, b"b".to_vec())),
+			Error::<Test>::InvalidEquivocationProof
+		);
+		// Not signed by the worker
+		assert_noop!(
+			report(signed_egress_message(&worker_sk, 1, 1, b"a".to_vec()), signed_egress_message(&other_sk, 1, 1, b"b".to_vec())),
+			Error::<Test>::InvalidEquivocationProof
+		);
+		events();
+
+		let first = signed_egress_message(&worker_sk, 1, 1, b"a".to_vec());
+		let second = signed_egress_message(&worker_sk, 1, 1, b"b".to_vec());
+		assert_ok!(report(first.clone(), second.clone()));
+		assert_eq!(0, PhalaModule::worker_bond(1));
+		assert_eq!(None, PhalaModule::unbonding_bond(1));
+		assert_eq!(0, Balances::reserved_balance(1));
+		assert_eq!(150, Balances::free_balance(1));
+		assert_eq!(MinerState::Evicted, PhalaModule::worker_state(1).state);
+		assert_eq!(
+			events().as_slice(),
+			[
+				TestEvent::phala(RawEvent::WorkerEvicted(1, vec![1])),
+				TestEvent::phala(RawEvent::EquivocationSlashed(1, 1, 1, 150)),
+			]
+		);
+		// Cannot be reported twice
+		assert_ok!(PhalaModule::bond_worker(Origin::signed(1), 100));
+		assert_noop!(report(second, first), Error::<Test>::DuplicateEquivocationReport);
+	});
+}
+
+#[test]
+fn test_report_offences() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let worker_sk = ecdsa_load_sk(&[1; 32]);
+		// Only the workers of the controllers 11 and 12 run validators
+		set_validators(vec![11, 12]);
+		for i in 1..=3 {
+			assert_ok!(PhalaModule::set_stash(Origin::signed(i), i + 10));
+			assert_ok!(PhalaModule::force_register_worker(RawOrigin::Root.into(), i, vec![i as u8], ecdsa_pubkey(&worker_sk)));
+			assert_ok!(PhalaModule::start_mine(Origin::signed(i + 10)));
+		}
+
+		// Going offline
+		PhalaModule::on_initialize(15);
+		let mut reported = offences();
+		assert_eq!(1, reported.len());
+		let (kind, mut offenders, reporters) = reported.remove(0);
+		offenders.sort();
+		assert_eq!(*b"phala:wk-offline", kind);
+		assert_eq!(vec![11, 12], offenders);
+		assert!(reporters.is_empty());
+		// Nothing to report
+		PhalaModule::on_initialize(20);
+		assert!(offences().is_empty());
+
+		// Equivocation of a validator, reported by the caller
+		let first = signed_egress_message(&worker_sk, 1, 1, b"a".to_vec());
+		let second = signed_egress_message(&worker_sk, 1, 1, b"b".to_vec());
+		assert_ok!(PhalaModule::report_egress_equivocation(Origin::signed(4), 2, first.clone(), second.clone()));
+		assert_eq!(offences(), vec![(*b"phala:equivocati", vec![12], vec![4])]);
+		// Not a validator
+		assert_ok!(PhalaModule::report_egress_equivocation(Origin::signed(4), 3, first, second));
+		assert!(offences().is_empty());
+	});
+}
+
 fn ecdsa_load_sk(raw_key: &[u8]) -> secp256k1::SecretKey {
     secp256k1::SecretKey::parse_slice(raw_key).expect("can't parse private key")
 }
@@ -262,3 +2050,31 @@ fn ecdsa_sign(sk: &secp256k1::SecretKey, data: &impl Encode) -> Vec<u8> {
 	let raw_sig: sp_core::ecdsa::Signature = sig.into();
 	raw_sig.0.to_vec()
 }
+
+fn ecdsa_pubkey(sk: &secp256k1::SecretKey) -> Vec<u8> {
+	secp256k1::PublicKey::from_secret_key(sk).serialize_compressed().to_vec()
+}
+
+fn signed_key_rotation(sk: &secp256k1::SecretKey, contract_id: u32, contract_sk: &secp256k1::SecretKey, sequence: u64) -> Vec<u8> {
+	let data = ContractKeyRotation { contract_id, pubkey: ecdsa_pubkey(contract_sk), sequence };
+	let signature = ecdsa_sign(sk, &data);
+	SignedContractKeyRotation { data, signature }.encode()
+}
+
+fn signed_heartbeat(sk: &secp256k1::SecretKey, block_num: u32) -> Vec<u8> {
+	let data = Heartbeat { block_num };
+	let signature = ecdsa_sign(sk, &data);
+	HeartbeatData { data, signature }.encode()
+}
+
+fn signed_challenge_response(sk: &secp256k1::SecretKey, nonce: [u8; 32], block_num: u32) -> Vec<u8> {
+	let data = ChallengeResponse { nonce, block_num };
+	let signature = ecdsa_sign(sk, &data);
+	SignedChallengeResponse { data, signature }.encode()
+}
+
+fn signed_egress_message(sk: &secp256k1::SecretKey, contract_id: u32, sequence: u64, payload: Vec<u8>) -> Vec<u8> {
+	let data = EgressMessage { contract_id, sequence, payload };
+	let signature = ecdsa_sign(sk, &data);
+	SignedEgressMessage { data, signature }.encode()
+}
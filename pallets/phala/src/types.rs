@@ -1,28 +1,60 @@
 use alloc::vec::Vec;
 use codec::{Encode, Decode};
+use sp_runtime::RuntimeDebug;
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
 
-#[derive(Encode, Decode)]
-pub struct Transfer<AccountId, Balance> {
-	pub dest: AccountId,
-	pub amount: Balance,
+pub use phala_types::{
+	Transfer, TransferData, Heartbeat, HeartbeatData, ContractKeyRotation, SignedContractKeyRotation,
+	ChallengeResponse, SignedChallengeResponse, PRuntimeInfo, DcapReportData, ECDH_PUBKEY_LEN,
+};
+
+/// A message sent from a confidential contract to the chain
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct EgressMessage {
+	pub contract_id: u32,
 	pub sequence: u64,
+	pub payload: Vec<u8>,
 }
 
 #[derive(Encode, Decode)]
-pub struct TransferData<AccountId, Balance> {
-	pub data: Transfer<AccountId, Balance>,
+pub struct SignedEgressMessage {
+	pub data: EgressMessage,
 	pub signature: Vec<u8>,
 }
 
+/// An egress message collecting the signatures of the workers assigned to its contract
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct PendingEgressMessage<AccountId, BlockNumber> {
+	pub message: EgressMessage,
+	/// Stashes of the workers signed the message
+	pub signers: Vec<AccountId>,
+	/// Last block to accept the signatures
+	pub expiry: BlockNumber,
+}
+
+/// The on-chain handler an egress message is routed to, determining how its payload is decoded
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum EgressHandler {
+	/// Release the native tokens held by the pallet (payload: `BalanceRelease`)
+	Balance,
+	/// Release an asset issued by a confidential contract (payload: `AssetRelease`)
+	Asset,
+	/// Emit the payload as an event
+	Event,
+}
+
 #[derive(Encode, Decode)]
-pub struct Heartbeat {
-	pub block_num: u32,
+pub struct BalanceRelease<AccountId, Balance> {
+	pub dest: AccountId,
+	pub amount: Balance,
 }
 
 #[derive(Encode, Decode)]
-pub struct HeartbeatData {
-	pub data: Heartbeat,
-	pub signature: Vec<u8>,
+pub struct AssetRelease<AccountId, Balance> {
+	pub asset_id: u32,
+	pub dest: AccountId,
+	pub amount: Balance,
 }
 
 pub trait SignedDataType<T> {
@@ -40,6 +72,26 @@ impl<AccountId: Encode, Balance: Encode> SignedDataType<Vec<u8>> for TransferDat
 	}
 }
 
+impl SignedDataType<Vec<u8>> for SignedEgressMessage {
+	fn raw_data(&self) -> Vec<u8> {
+		Encode::encode(&self.data)
+	}
+
+	fn signature(&self) -> Vec<u8> {
+		self.signature.clone()
+	}
+}
+
+impl SignedDataType<Vec<u8>> for SignedContractKeyRotation {
+	fn raw_data(&self) -> Vec<u8> {
+		Encode::encode(&self.data)
+	}
+
+	fn signature(&self) -> Vec<u8> {
+		self.signature.clone()
+	}
+}
+
 impl SignedDataType<Vec<u8>> for HeartbeatData {
 	fn raw_data(&self) -> Vec<u8> {
 		Encode::encode(&self.data)
@@ -50,46 +102,239 @@ impl SignedDataType<Vec<u8>> for HeartbeatData {
 	}
 }
 
+impl SignedDataType<Vec<u8>> for SignedChallengeResponse {
+	fn raw_data(&self) -> Vec<u8> {
+		Encode::encode(&self.data)
+	}
+
+	fn signature(&self) -> Vec<u8> {
+		self.signature.clone()
+	}
+}
+
 // Types used in storage
 
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum ContractStatus {
+	/// Accepting commands
+	Active,
+	/// Not accepting commands anymore
+	Retired,
+}
+
+/// A confidential contract registered on chain
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct ContractInfo<AccountId, Hash> {
+	pub owner: AccountId,
+	/// Hash of the code (including the version) of the contract
+	pub code_hash: Hash,
+	pub pubkey: Vec<u8>,
+	pub status: ContractStatus,
+}
+
+/// The calls limited by the per-account call quota, each kind counted separately
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum RateLimitedCall {
+	/// `push_command`
+	Command,
+	/// `heartbeat`
+	Heartbeat,
+	/// `transfer_to_chain` and `transfer_to_chain_batch`
+	Transfer,
+}
+
+/// Mining state of a registered worker
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum MinerState {
+	/// Registered but not mining
+	Idle,
+	/// Mining and rewarded for the heartbeats
+	Mining,
+	/// Requested to stop mining; still rewarded until the end of the current round
+	PendingStop,
+	/// Stopped mining because of missing heartbeats
+	Offline,
+	/// Removed from the registry by the governance; has to register again
+	Evicted,
+}
+
+impl Default for MinerState {
+	fn default() -> Self {
+		MinerState::Idle
+	}
+}
+
+impl MinerState {
+	/// Whether the worker is rewarded for its heartbeats in the current round
+	pub fn is_mining(&self) -> bool {
+		match self {
+			MinerState::Mining | MinerState::PendingStop => true,
+			_ => false,
+		}
+	}
+}
+
 #[derive(Encode, Decode, Default)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
 pub struct WorkerInfo {
 	// identity
+	#[cfg_attr(feature = "std", serde(with = "sp_core::bytes"))]
 	pub machine_id: Vec<u8>,
+	#[cfg_attr(feature = "std", serde(with = "sp_core::bytes"))]
 	pub pubkey: Vec<u8>,
+	/// The ECDH public key to encrypt the commands, empty if not reported by the pRuntime
+	#[cfg_attr(feature = "std", serde(with = "sp_core::bytes"))]
+	pub ecdh_pubkey: Vec<u8>,
+	#[cfg_attr(feature = "std", serde(with = "sp_core::bytes"))]
+	pub mr_enclave: Vec<u8>,
 	pub last_updated: u64,
 	// contract
 	// ...
 	// mining
-	pub status: i32,
+	pub state: MinerState,
 	// preformance
-	pub score: Option<Score>
+	pub score: Option<Score>,
+	/// Registered by the root or in the genesis without an attestation, and never required to
+	/// attest again
+	pub attestation_exempt: bool,
 }
 
 #[derive(Encode, Decode, Default)]
-pub struct StashInfo<AccountId: Default> {
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct StashInfo<AccountId> {
 	pub controller: AccountId,
 	pub payout_prefs: PayoutPrefs::<AccountId>,
 }
 
 #[derive(Encode, Decode, Default)]
-pub struct PayoutPrefs<AccountId: Default> {
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct PayoutPrefs<AccountId> {
 	pub commission: u32,
 	pub target: AccountId,
 }
 
 #[derive(Encode, Decode, Default)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
 pub struct Score {
 	pub overall_score: u32,
 	pub features: Vec<u32>
 }
 
-type MachineId = [u8; 16];
-type WorkerPublicKey = [u8; 33];
-#[derive(Encode, Decode)]
-pub struct PRuntimeInfo {
-	pub version: u8,
-	pub machine_id: MachineId,
-	pub pubkey: WorkerPublicKey,
-	pub features: Vec<u32>
+/// Weight and cap of a feature in the worker score
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct FeatureWeight {
+	pub weight: u32,
+	/// Values above the cap are counted as the cap
+	pub cap: u32,
+}
+
+/// Parameters of the formula scoring a worker by the features reported in `PRuntimeInfo`:
+///
+/// `min(cores, max_cores) * (baseline + sum(weight_i * min(feature_i, cap_i)))`
+///
+/// where `cores` is the first feature and `feature_i` is the i-th feature after it. Features not
+/// reported by the worker count as 0, and features without a weight are ignored.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct ScoringParams {
+	pub max_cores: u32,
+	/// Score of each core regardless of the other features
+	pub baseline: u32,
+	pub feature_weights: Vec<FeatureWeight>,
+}
+
+impl Default for ScoringParams {
+	/// `cores * (cpu_feature_level * 10 + 60)`
+	fn default() -> Self {
+		ScoringParams {
+			max_cores: u32::max_value(),
+			baseline: 60,
+			feature_weights: sp_std::vec![FeatureWeight { weight: 10, cap: u32::max_value() }],
+		}
+	}
+}
+
+impl ScoringParams {
+	/// Calculates the overall score of the features. Returns `None` if the number of cores is
+	/// missing.
+	pub fn overall_score(&self, features: &[u32]) -> Option<u32> {
+		let (cores, features) = features.split_first()?;
+		let per_core = self.feature_weights.iter()
+			.zip(features.iter().chain(core::iter::repeat(&0)))
+			.fold(self.baseline, |acc, (w, value)| {
+				acc.saturating_add(w.weight.saturating_mul(core::cmp::min(*value, w.cap)))
+			});
+		Some(core::cmp::min(*cores, self.max_cores).saturating_mul(per_core))
+	}
+}
+
+/// A challenge issued to a mining worker
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Challenge<BlockNumber> {
+	pub nonce: [u8; 32],
+	/// Block the challenge is issued in
+	pub issued_at: BlockNumber,
+	/// Last block to accept the response
+	pub deadline: BlockNumber,
+}
+
+/// Mining state of a worker, as exposed by the runtime API
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct MiningStatus<BlockNumber, Balance> {
+	pub state: MinerState,
+	/// Block of the last accepted heartbeat and the pRuntime block number reported in it
+	pub last_heartbeat: Option<(BlockNumber, u32)>,
+	/// Heartbeats accepted in the current round
+	pub round_heartbeats: u32,
+	/// Reward accumulated by the stash and not claimed yet
+	pub pending_reward: Balance,
+}
+
+/// A trust anchor (root certificate) for the IAS report signing certificate chain
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+pub struct IASTrustAnchor {
+	pub subject: Vec<u8>,
+	pub spki: Vec<u8>,
+}
+
+/// A trust anchor (Intel SGX root certificate) for the PCK certificate chain of the DCAP quotes
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+pub struct DCAPTrustAnchor {
+	pub subject: Vec<u8>,
+	pub spki: Vec<u8>,
+}
+
+/// Policy of a whitelisted enclave build, keyed by its MRENCLAVE
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+pub struct EnclaveMeasurement {
+	pub mr_signer: Vec<u8>,
+	pub isv_prod_id: u16,
+	pub min_isv_svn: u16,
+}
+
+/// A TCB level of a platform model, as published in the TCB info collateral of Intel or reported
+/// in the PCK certificate of a platform
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct TcbLevel {
+	/// SVNs of the 16 CPU components
+	pub components: [u8; 16],
+	pub pce_svn: u16,
+}
+
+impl TcbLevel {
+	/// Whether the platform at the `platform` level has all the SVNs at or above this level
+	pub fn is_reached_by(&self, platform: &TcbLevel) -> bool {
+		self.pce_svn <= platform.pce_svn &&
+			self.components.iter().zip(platform.components.iter()).all(|(min, svn)| min <= svn)
+	}
 }
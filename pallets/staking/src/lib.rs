@@ -890,6 +890,10 @@ pub trait Trait: frame_system::Trait + pallet_phala::Trait + SendTransactionType
 	/// enough to fit in the block.
 	type OffchainSolutionWeightLimit: Get<Weight>;
 
+	/// Bonus on the self-stake of a validator in the election, for each point of the overall score
+	/// of its mining worker.
+	type MinerScoreBonus: Get<Perbill>;
+
 	/// Weight information for extrinsics in this pallet.
 	type WeightInfo: WeightInfo;
 }
@@ -1286,6 +1290,10 @@ decl_module! {
 		/// their reward. This used to limit the i/o cost for the nominator payout.
 		const MaxNominatorRewardedPerValidator: u32 = T::MaxNominatorRewardedPerValidator::get();
 
+		/// Bonus on the self-stake of a validator in the election, for each point of the overall
+		/// score of its mining worker.
+		const MinerScoreBonus: Perbill = T::MinerScoreBonus::get();
+
 		type Error = Error<T>;
 
 		fn deposit_event() = default;
@@ -1423,7 +1431,7 @@ decl_module! {
 		/// - Read: Bonded, Ledger, [Origin Account], Current Era, History Depth, Locks
 		/// - Write: Bonded, Payee, [Origin Account], Locks, Ledger
 		/// # </weight>
-		#[weight = T::WeightInfo::bond()]
+		#[weight = <T as Trait>::WeightInfo::bond()]
 		pub fn bond(origin,
 			controller: <T::Lookup as StaticLookup>::Source,
 			#[compact] value: BalanceOf<T>,
@@ -1491,7 +1499,7 @@ decl_module! {
 		/// - Read: Era Election Status, Bonded, Ledger, [Origin Account], Locks
 		/// - Write: [Origin Account], Locks, Ledger
 		/// # </weight>
-		#[weight = T::WeightInfo::bond_extra()]
+		#[weight = <T as Trait>::WeightInfo::bond_extra()]
 		fn bond_extra(origin, #[compact] max_additional: BalanceOf<T>) {
 			ensure!(Self::era_election_status().is_closed(), Error::<T>::CallNotAllowed);
 			let stash = ensure_signed(origin)?;
@@ -1542,7 +1550,7 @@ decl_module! {
 		/// - Read: EraElectionStatus, Ledger, CurrentEra, Locks, BalanceOf Stash,
 		/// - Write: Locks, Ledger, BalanceOf Stash,
 		/// </weight>
-		#[weight = T::WeightInfo::unbond()]
+		#[weight = <T as Trait>::WeightInfo::unbond()]
 		fn unbond(origin, #[compact] value: BalanceOf<T>) {
 			ensure!(Self::era_election_status().is_closed(), Error::<T>::CallNotAllowed);
 			let controller = ensure_signed(origin)?;
@@ -1602,7 +1610,7 @@ decl_module! {
 		/// - Writes Each: SpanSlash * S
 		/// NOTE: Weight annotation is the kill scenario, we refund otherwise.
 		/// # </weight>
-		#[weight = T::WeightInfo::withdraw_unbonded_kill(*num_slashing_spans)]
+		#[weight = <T as Trait>::WeightInfo::withdraw_unbonded_kill(*num_slashing_spans)]
 		fn withdraw_unbonded(origin, num_slashing_spans: u32) -> DispatchResultWithPostInfo {
 			ensure!(Self::era_election_status().is_closed(), Error::<T>::CallNotAllowed);
 			let controller = ensure_signed(origin)?;
@@ -1626,7 +1634,7 @@ decl_module! {
 				Self::update_ledger(&controller, &ledger);
 
 				// This is only an update, so we use less overall weight.
-				Some(T::WeightInfo::withdraw_unbonded_update(num_slashing_spans))
+				Some(<T as Trait>::WeightInfo::withdraw_unbonded_update(num_slashing_spans))
 			};
 
 			// `old_total` should never be less than the new total because
@@ -1657,7 +1665,7 @@ decl_module! {
 		/// - Read: Era Election Status, Ledger
 		/// - Write: Nominators, Validators
 		/// # </weight>
-		#[weight = T::WeightInfo::validate()]
+		#[weight = <T as Trait>::WeightInfo::validate()]
 		pub fn validate(origin, prefs: ValidatorPrefs) {
 			ensure!(Self::era_election_status().is_closed(), Error::<T>::CallNotAllowed);
 			let controller = ensure_signed(origin)?;
@@ -1687,7 +1695,7 @@ decl_module! {
 		/// - Reads: Era Election Status, Ledger, Current Era
 		/// - Writes: Validators, Nominators
 		/// # </weight>
-		#[weight = T::WeightInfo::nominate(targets.len() as u32)]
+		#[weight = <T as Trait>::WeightInfo::nominate(targets.len() as u32)]
 		pub fn nominate(origin, targets: Vec<<T::Lookup as StaticLookup>::Source>) {
 			ensure!(Self::era_election_status().is_closed(), Error::<T>::CallNotAllowed);
 			let controller = ensure_signed(origin)?;
@@ -1727,7 +1735,7 @@ decl_module! {
 		/// - Read: EraElectionStatus, Ledger
 		/// - Write: Validators, Nominators
 		/// # </weight>
-		#[weight = T::WeightInfo::chill()]
+		#[weight = <T as Trait>::WeightInfo::chill()]
 		fn chill(origin) {
 			ensure!(Self::era_election_status().is_closed(), Error::<T>::CallNotAllowed);
 			let controller = ensure_signed(origin)?;
@@ -1751,7 +1759,7 @@ decl_module! {
 		///     - Read: Ledger
 		///     - Write: Payee
 		/// # </weight>
-		#[weight = T::WeightInfo::set_payee()]
+		#[weight = <T as Trait>::WeightInfo::set_payee()]
 		fn set_payee(origin, payee: RewardDestination<T::AccountId>) {
 			let controller = ensure_signed(origin)?;
 			let ledger = Self::ledger(&controller).ok_or(Error::<T>::NotController)?;
@@ -1775,7 +1783,7 @@ decl_module! {
 		/// - Read: Bonded, Ledger New Controller, Ledger Old Controller
 		/// - Write: Bonded, Ledger New Controller, Ledger Old Controller
 		/// # </weight>
-		#[weight = T::WeightInfo::set_controller()]
+		#[weight = <T as Trait>::WeightInfo::set_controller()]
 		fn set_controller(origin, controller: <T::Lookup as StaticLookup>::Source) {
 			let stash = ensure_signed(origin)?;
 			let old_controller = Self::bonded(&stash).ok_or(Error::<T>::NotStash)?;
@@ -1799,7 +1807,7 @@ decl_module! {
 		/// Weight: O(1)
 		/// Write: Validator Count
 		/// # </weight>
-		#[weight = T::WeightInfo::set_validator_count()]
+		#[weight = <T as Trait>::WeightInfo::set_validator_count()]
 		fn set_validator_count(origin, #[compact] new: u32) {
 			ensure_root(origin)?;
 			ValidatorCount::put(new);
@@ -1812,7 +1820,7 @@ decl_module! {
 		/// # <weight>
 		/// Same as [`set_validator_count`].
 		/// # </weight>
-		#[weight = T::WeightInfo::set_validator_count()]
+		#[weight = <T as Trait>::WeightInfo::set_validator_count()]
 		fn increase_validator_count(origin, #[compact] additional: u32) {
 			ensure_root(origin)?;
 			ValidatorCount::mutate(|n| *n += additional);
@@ -1825,7 +1833,7 @@ decl_module! {
 		/// # <weight>
 		/// Same as [`set_validator_count`].
 		/// # </weight>
-		#[weight = T::WeightInfo::set_validator_count()]
+		#[weight = <T as Trait>::WeightInfo::set_validator_count()]
 		fn scale_validator_count(origin, factor: Percent) {
 			ensure_root(origin)?;
 			ValidatorCount::mutate(|n| *n += factor * *n);
@@ -1840,7 +1848,7 @@ decl_module! {
 		/// - Weight: O(1)
 		/// - Write: ForceEra
 		/// # </weight>
-		#[weight = T::WeightInfo::force_no_eras()]
+		#[weight = <T as Trait>::WeightInfo::force_no_eras()]
 		fn force_no_eras(origin) {
 			ensure_root(origin)?;
 			ForceEra::put(Forcing::ForceNone);
@@ -1856,7 +1864,7 @@ decl_module! {
 		/// - Weight: O(1)
 		/// - Write ForceEra
 		/// # </weight>
-		#[weight = T::WeightInfo::force_new_era()]
+		#[weight = <T as Trait>::WeightInfo::force_new_era()]
 		fn force_new_era(origin) {
 			ensure_root(origin)?;
 			ForceEra::put(Forcing::ForceNew);
@@ -1870,7 +1878,7 @@ decl_module! {
 		/// - O(V)
 		/// - Write: Invulnerables
 		/// # </weight>
-		#[weight = T::WeightInfo::set_invulnerables(invulnerables.len() as u32)]
+		#[weight = <T as Trait>::WeightInfo::set_invulnerables(invulnerables.len() as u32)]
 		fn set_invulnerables(origin, invulnerables: Vec<T::AccountId>) {
 			ensure_root(origin)?;
 			<Invulnerables<T>>::put(invulnerables);
@@ -1886,7 +1894,7 @@ decl_module! {
 		/// Writes: Bonded, Slashing Spans (if S > 0), Ledger, Payee, Validators, Nominators, Account, Locks
 		/// Writes Each: SpanSlash * S
 		/// # </weight>
-		#[weight = T::WeightInfo::force_unstake(*num_slashing_spans)]
+		#[weight = <T as Trait>::WeightInfo::force_unstake(*num_slashing_spans)]
 		fn force_unstake(origin, stash: T::AccountId, num_slashing_spans: u32) {
 			ensure_root(origin)?;
 
@@ -1905,7 +1913,7 @@ decl_module! {
 		/// - Weight: O(1)
 		/// - Write: ForceEra
 		/// # </weight>
-		#[weight = T::WeightInfo::force_new_era_always()]
+		#[weight = <T as Trait>::WeightInfo::force_new_era_always()]
 		fn force_new_era_always(origin) {
 			ensure_root(origin)?;
 			ForceEra::put(Forcing::ForceAlways);
@@ -1924,7 +1932,7 @@ decl_module! {
 		/// - Read: Unapplied Slashes
 		/// - Write: Unapplied Slashes
 		/// # </weight>
-		#[weight = T::WeightInfo::cancel_deferred_slash(slash_indices.len() as u32)]
+		#[weight = <T as Trait>::WeightInfo::cancel_deferred_slash(slash_indices.len() as u32)]
 		fn cancel_deferred_slash(origin, era: EraIndex, slash_indices: Vec<u32>) {
 			T::SlashCancelOrigin::ensure_origin(origin)?;
 
@@ -1971,7 +1979,7 @@ decl_module! {
 		///   NOTE: weights are assuming that payouts are made to alive stash account (Staked).
 		///   Paying even a dead controller is cheaper weight-wise. We don't do any refunds here.
 		/// # </weight>
-		#[weight = T::WeightInfo::payout_stakers_alive_staked(T::MaxNominatorRewardedPerValidator::get())]
+		#[weight = <T as Trait>::WeightInfo::payout_stakers_alive_staked(T::MaxNominatorRewardedPerValidator::get())]
 		fn payout_stakers(origin, validator_stash: T::AccountId, era: EraIndex) -> DispatchResult {
 			ensure!(Self::era_election_status().is_closed(), Error::<T>::CallNotAllowed);
 			ensure_signed(origin)?;
@@ -1992,7 +2000,7 @@ decl_module! {
 		///     - Reads: EraElectionStatus, Ledger, Locks, [Origin Account]
 		///     - Writes: [Origin Account], Locks, Ledger
 		/// # </weight>
-		#[weight = T::WeightInfo::rebond(MAX_UNLOCKING_CHUNKS as u32)]
+		#[weight = <T as Trait>::WeightInfo::rebond(MAX_UNLOCKING_CHUNKS as u32)]
 		fn rebond(origin, #[compact] value: BalanceOf<T>) -> DispatchResultWithPostInfo {
 			ensure!(Self::era_election_status().is_closed(), Error::<T>::CallNotAllowed);
 			let controller = ensure_signed(origin)?;
@@ -2029,7 +2037,7 @@ decl_module! {
 		///     - Clear Prefix Each: Era Stakers, EraStakersClipped, ErasValidatorPrefs
 		///     - Writes Each: ErasValidatorReward, ErasRewardPoints, ErasTotalStake, ErasStartSessionIndex
 		/// # </weight>
-		#[weight = T::WeightInfo::set_history_depth(*_era_items_deleted)]
+		#[weight = <T as Trait>::WeightInfo::set_history_depth(*_era_items_deleted)]
 		fn set_history_depth(origin,
 			#[compact] new_history_depth: EraIndex,
 			#[compact] _era_items_deleted: u32,
@@ -2062,7 +2070,7 @@ decl_module! {
 		/// - Writes: Bonded, Slashing Spans (if S > 0), Ledger, Payee, Validators, Nominators, Stash Account, Locks
 		/// - Writes Each: SpanSlash * S
 		/// # </weight>
-		#[weight = T::WeightInfo::reap_stash(*num_slashing_spans)]
+		#[weight = <T as Trait>::WeightInfo::reap_stash(*num_slashing_spans)]
 		fn reap_stash(_origin, stash: T::AccountId, num_slashing_spans: u32) {
 			ensure!(T::Currency::total_balance(&stash).is_zero(), Error::<T>::FundedTarget);
 			Self::kill_stash(&stash, num_slashing_spans)?;
@@ -2118,7 +2126,7 @@ decl_module! {
 		///   - Initial solution is almost the same.
 		///   - Worse solution is retraced in pre-dispatch-checks which sets its own weight.
 		/// # </weight>
-		#[weight = T::WeightInfo::submit_solution_better(
+		#[weight = <T as Trait>::WeightInfo::submit_solution_better(
 			size.validators.into(),
 			size.nominators.into(),
 			compact.len() as u32,
@@ -2152,7 +2160,7 @@ decl_module! {
 		/// # <weight>
 		/// See [`submit_election_solution`].
 		/// # </weight>
-		#[weight = T::WeightInfo::submit_solution_better(
+		#[weight = <T as Trait>::WeightInfo::submit_solution_better(
 			size.validators.into(),
 			size.nominators.into(),
 			compact.len() as u32,
@@ -2211,6 +2219,38 @@ impl<T: Trait> Module<T> {
 		})
 	}
 
+	/// The overall score of the mining worker run by the controller of a validator, if the worker
+	/// is mining and hasn't gone offline since the active era started. Validators without such a
+	/// worker sit out the election.
+	pub fn healthy_miner_score_of(validator: &T::AccountId) -> Option<u32> {
+		let controller = Self::bonded(validator)?;
+		let era_start = Self::active_era()
+			.and_then(|era| era.start)
+			.unwrap_or_else(|| <T as Trait>::UnixTime::now().as_millis().saturated_into::<u64>());
+		<pallet_phala::Module<T>>::healthy_miner_score(&controller, era_start)
+	}
+
+	/// Returns a closure of the vote weight in the election. It's the slashable balance, plus
+	/// `MinerScoreBonus` of the slashable balance of a validator for each point of the score of
+	/// its mining worker.
+	///
+	/// It only ranks the candidates in [`do_phragmen`]. The staked assignments, and so the
+	/// exposures, are built from [`slashable_balance_of_fn`].
+	///
+	/// The same caveat as [`slashable_balance_of_fn`] applies.
+	pub fn election_weight_of_fn() -> Box<dyn Fn(&T::AccountId) -> VoteWeight> {
+		let weight_of = Self::slashable_balance_of_fn();
+		Box::new(move |who: &T::AccountId| -> VoteWeight {
+			let weight = weight_of(who);
+			if !<Validators<T>>::contains_key(who) {
+				return weight;
+			}
+			let score = Self::healthy_miner_score_of(who).unwrap_or(0);
+			let bonus = (T::MinerScoreBonus::get() * weight).saturating_mul(score as VoteWeight);
+			weight.saturating_add(bonus)
+		})
+	}
+
 	/// Dump the list of validators and nominators into vectors and keep them on-chain.
 	///
 	/// This data is used to efficiently evaluate election results. returns `true` if the operation
@@ -2220,7 +2260,10 @@ impl<T: Trait> Module<T> {
 		let mut add_db_reads_writes = |reads, writes| {
 			consumed_weight += T::DbWeight::get().reads_writes(reads, writes);
 		};
-		let validators = <Validators<T>>::iter().map(|(v, _)| v).collect::<Vec<_>>();
+		let validators = <Validators<T>>::iter()
+			.map(|(v, _)| v)
+			.filter(|v| Self::healthy_miner_score_of(v).is_some())
+			.collect::<Vec<_>>();
 		let mut nominators = <Nominators<T>>::iter().map(|(n, _)| n).collect::<Vec<_>>();
 
 		let num_validators = validators.len();
@@ -2913,13 +2956,17 @@ impl<T: Trait> Module<T> {
 	/// raw edge weights are returned.
 	///
 	/// Self votes are added and nominations before the most recent slashing span are ignored.
+	/// Validators whose controllers left the worker registry are chilled, while validators without a
+	/// healthy mining worker are skipped and stay candidates for the next elections. The self votes
+	/// are weighted by the score of the mining workers to rank the candidates, which is not part of
+	/// the returned edge weights.
 	///
 	/// No storage item is updated.
 	pub fn do_phragmen<Accuracy: PerThing>(iterations: usize)
 	-> Option<PrimitiveElectionResult<T::AccountId, Accuracy>>
 		where ExtendedBalance: From<InnerOf<Accuracy>>
 	{
-		let weight_of = Self::slashable_balance_of_fn();
+		let weight_of = Self::election_weight_of_fn();
 		let mut all_nominators: Vec<(T::AccountId, VoteWeight, Vec<T::AccountId>)> = Vec::new();
 		let mut all_validators = Vec::new();
 		for (validator, _) in <Validators<T>>::iter() {
@@ -2930,6 +2977,9 @@ impl<T: Trait> Module<T> {
 					continue;
 				}
 			}
+			if Self::healthy_miner_score_of(&validator).is_none() {
+				continue;
+			}
 
 			// append self vote
 			let self_vote = (validator.clone(), weight_of(&validator), vec![validator.clone()]);
@@ -3130,6 +3180,41 @@ impl<T: Trait> pallet_session::SessionManager<T::AccountId> for Module<T> {
 	}
 }
 
+/// A miner leaving Phala can no longer validate, so the staker bonded by its controller is chilled
+/// right away instead of at the next election.
+impl<T: Trait> pallet_phala::OnStashRemoved<T::AccountId> for Module<T> {
+	fn on_stash_removed(_stash: &T::AccountId, controller: &T::AccountId) {
+		if let Some(ledger) = Self::ledger(controller) {
+			Self::chill_stash(&ledger.stash);
+		}
+	}
+}
+
+/// The workers are identified by the validators they run in the current session, along with the
+/// exposures in the active era, so that their offences are slashed by `on_offence`.
+impl<T: Trait> pallet_phala::IdentifyValidator<T::AccountId, historical::IdentificationTuple<T>>
+for Module<T> where
+	T: pallet_session::Trait<ValidatorId = <T as frame_system::Trait>::AccountId>,
+	T: pallet_session::historical::Trait<
+		FullIdentification = Exposure<<T as frame_system::Trait>::AccountId, BalanceOf<T>>,
+		FullIdentificationOf = ExposureOf<T>,
+	>,
+{
+	fn current_session() -> (SessionIndex, u32) {
+		let validators = <pallet_session::Module<T>>::validators();
+		(<pallet_session::Module<T>>::current_index(), validators.len() as u32)
+	}
+
+	fn identify_validator(controller: &T::AccountId) -> Option<historical::IdentificationTuple<T>> {
+		let stash = Self::ledger(controller)?.stash;
+		if !<pallet_session::Module<T>>::validators().contains(&stash) {
+			return None;
+		}
+		let exposure = ExposureOf::<T>::convert(stash.clone())?;
+		Some((stash, exposure))
+	}
+}
+
 impl<T: Trait> historical::SessionManager<T::AccountId, Exposure<T::AccountId, BalanceOf<T>>> for Module<T> {
 	fn new_session(new_index: SessionIndex)
 		-> Option<Vec<(T::AccountId, Exposure<T::AccountId, BalanceOf<T>>)>>
@@ -20,11 +20,11 @@
 use crate::*;
 use frame_support::{
 	assert_ok, impl_outer_dispatch, impl_outer_event, impl_outer_origin, parameter_types,
-	traits::{Currency, FindAuthor, Get, OnFinalize, OnInitialize},
+	traits::{Currency, FindAuthor, Get, OnFinalize, OnInitialize, UnfilteredDispatchable},
 	weights::{constants::RocksDbWeight, Weight},
 	IterableStorageMap, StorageDoubleMap, StorageMap, StorageValue,
 };
-use sp_core::H256;
+use sp_core::{H256, Pair};
 use sp_io;
 use sp_npos_elections::{
 	build_support_map, evaluate_support, reduce, ExtendedBalance, StakedAssignment, ElectionScore,
@@ -34,9 +34,9 @@ use sp_runtime::{
 	testing::{Header, TestXt, UintAuthorityId},
 	traits::{IdentityLookup, Zero},
 };
-use sp_staking::offence::{OffenceDetails, OnOffenceHandler};
+use sp_staking::offence::{Offence, OffenceDetails, OffenceError, OnOffenceHandler, ReportOffence};
 use std::{cell::RefCell, collections::HashSet};
-use pallet_phala as phala
+use pallet_phala as phala;
 
 pub const INIT_TIMESTAMP: u64 = 30_000;
 
@@ -54,6 +54,7 @@ thread_local! {
 	static ELECTION_LOOKAHEAD: RefCell<BlockNumber> = RefCell::new(0);
 	static PERIOD: RefCell<BlockNumber> = RefCell::new(1);
 	static MAX_ITERATIONS: RefCell<u32> = RefCell::new(0);
+	static MINER_SCORE_BONUS: RefCell<Perbill> = RefCell::new(Perbill::zero());
 }
 
 /// Another session handler struct to test on_disabled.
@@ -126,6 +127,13 @@ impl Get<BlockNumber> for Period {
 	}
 }
 
+pub struct MinerScoreBonus;
+impl Get<Perbill> for MinerScoreBonus {
+	fn get() -> Perbill {
+		MINER_SCORE_BONUS.with(|v| *v.borrow())
+	}
+}
+
 pub struct SlashDeferDuration;
 impl Get<EraIndex> for SlashDeferDuration {
 	fn get() -> EraIndex {
@@ -284,6 +292,23 @@ parameter_types! {
 	pub const UnsignedPriority: u64 = 1 << 20;
 	pub const MinSolutionScoreBump: Perbill = Perbill::zero();
 	pub const OffchainSolutionWeightLimit: Weight = MaximumBlockWeight::get();
+	pub const MiningRewardPerBlock: Balance = 0;
+	pub const MiningRoundInterval: BlockNumber = 10;
+	pub const OfflineThreshold: BlockNumber = 20;
+	pub const HeartbeatMaxLag: BlockNumber = 5;
+	pub const ChallengesPerRound: u32 = 3;
+	pub const ChallengeDeadline: BlockNumber = 5;
+	pub const MaxCommandSize: u32 = 1024;
+	pub const CommandByteFee: Balance = 0;
+	pub const ContractKeyGracePeriod: BlockNumber = 10;
+	pub const LeaveCooldown: BlockNumber = 20;
+	pub const EgressSignatureTimeout: BlockNumber = 5;
+	pub const MaxReportAge: u64 = 10 * 60 * 1000;
+	pub const ReattestationInterval: u64 = 24 * 60 * 60 * 1000;
+	pub const WorkerBond: Balance = 0;
+	pub const BondUnbondingPeriod: BlockNumber = 10;
+	pub const CallQuotaWindow: BlockNumber = 5;
+	pub const MaxCallsPerWindow: u32 = 10;
 }
 
 thread_local! {
@@ -320,6 +345,7 @@ impl Trait for Test {
 	type Call = Call;
 	type MaxIterations = MaxIterations;
 	type MinSolutionScoreBump = MinSolutionScoreBump;
+	type MinerScoreBonus = MinerScoreBonus;
 	type MaxNominatorRewardedPerValidator = MaxNominatorRewardedPerValidator;
 	type UnsignedPriority = UnsignedPriority;
 	type OffchainSolutionWeightLimit = OffchainSolutionWeightLimit;
@@ -330,6 +356,56 @@ impl pallet_phala::Trait for Test {
 	type Event = MetaEvent;
 	type TEECurrency = Balances;
 	type UnixTime = Timestamp;
+	type GovernanceOrigin = frame_system::EnsureRoot<Self::AccountId>;
+	type RewardPerBlock = MiningRewardPerBlock;
+	type RoundInterval = MiningRoundInterval;
+	type OfflineThreshold = OfflineThreshold;
+	type HeartbeatMaxLag = HeartbeatMaxLag;
+	type Randomness = ();
+	type ChallengesPerRound = ChallengesPerRound;
+	type ChallengeDeadline = ChallengeDeadline;
+	type MaxCommandSize = MaxCommandSize;
+	type CommandByteFee = CommandByteFee;
+	type CommandFee = ();
+	type ContractKeyGracePeriod = ContractKeyGracePeriod;
+	type EgressSignatureTimeout = EgressSignatureTimeout;
+	type LeaveCooldown = LeaveCooldown;
+	type OnStashRemoved = Staking;
+	type MaxReportAge = MaxReportAge;
+	type ReattestationInterval = ReattestationInterval;
+	type WorkerBond = WorkerBond;
+	type BondUnbondingPeriod = BondUnbondingPeriod;
+	type Slash = ();
+	type ValidatorIdentification = pallet_session::historical::IdentificationTuple<Test>;
+	type IdentifyValidator = Staking;
+	type ReportOffence = OffenceHandler;
+	type CallQuotaWindow = CallQuotaWindow;
+	type MaxCallsPerWindow = MaxCallsPerWindow;
+	type WeightInfo = ();
+}
+
+/// Slashes the reported offenders right away, as the offences module does for the new offences
+pub struct OffenceHandler;
+impl<O> ReportOffence<AccountId, pallet_session::historical::IdentificationTuple<Test>, O> for OffenceHandler where
+	O: Offence<pallet_session::historical::IdentificationTuple<Test>>,
+{
+	fn report_offence(reporters: Vec<AccountId>, offence: O) -> Result<(), OffenceError> {
+		let offenders = offence.offenders();
+		let slash_fraction = O::slash_fraction(offenders.len() as u32, offence.validator_set_count());
+		let details: Vec<_> = offenders.into_iter()
+			.map(|offender| OffenceDetails { offender, reporters: reporters.clone() })
+			.collect();
+		Staking::on_offence(&details, &vec![slash_fraction; details.len()], offence.session_index())
+			.map(|_| ())
+			.map_err(|_| OffenceError::Other(0))
+	}
+
+	fn is_known_offence(
+		_offenders: &[pallet_session::historical::IdentificationTuple<Test>],
+		_time_slot: &O::TimeSlot,
+	) -> bool {
+		false
+	}
 }
 
 impl<LocalCall> frame_system::offchain::SendTransactionTypes<LocalCall> for Test
@@ -357,6 +433,7 @@ pub struct ExtBuilder {
 	invulnerables: Vec<AccountId>,
 	has_stakers: bool,
 	max_offchain_iterations: u32,
+	miner_score_bonus: Perbill,
 }
 
 impl Default for ExtBuilder {
@@ -376,6 +453,7 @@ impl Default for ExtBuilder {
 			invulnerables: vec![],
 			has_stakers: true,
 			max_offchain_iterations: 0,
+			miner_score_bonus: Perbill::zero(),
 		}
 	}
 }
@@ -437,6 +515,10 @@ impl ExtBuilder {
 		self.max_offchain_iterations = iterations;
 		self
 	}
+	pub fn miner_score_bonus(mut self, bonus: Perbill) -> Self {
+		self.miner_score_bonus = bonus;
+		self
+	}
 	pub fn offchain_election_ext(self) -> Self {
 		self.session_per_era(4)
 			.session_length(5)
@@ -449,6 +531,7 @@ impl ExtBuilder {
 		ELECTION_LOOKAHEAD.with(|v| *v.borrow_mut() = self.election_lookahead);
 		PERIOD.with(|v| *v.borrow_mut() = self.session_length);
 		MAX_ITERATIONS.with(|v| *v.borrow_mut() = self.max_offchain_iterations);
+		MINER_SCORE_BONUS.with(|v| *v.borrow_mut() = self.miner_score_bonus);
 	}
 	pub fn build(self) -> sp_io::TestExternalities {
 		sp_tracing::try_init_simple();
@@ -508,6 +591,12 @@ impl ExtBuilder {
 				(101, 100, balance_factor * 500, StakerStatus::<AccountId>::Nominator(nominated))
 			];
 		}
+		// Only the healthy miners are elected, so every staker runs a mining worker
+		frame_support::BasicExternalities::execute_with_storage(&mut storage, || {
+			for (stash, controller, _, _) in stakers.iter() {
+				register_mining_worker(*stash, *controller);
+			}
+		});
 		let _ = GenesisConfig::<Test>{
 			stakers: stakers,
 			validator_count: self.validator_count,
@@ -549,11 +638,28 @@ impl ExtBuilder {
 	}
 }
 
+/// The identity key of the worker of a stash
+pub(crate) fn worker_pair(stash: AccountId) -> sp_core::ecdsa::Pair {
+	sp_core::ecdsa::Pair::from_seed(&sp_io::hashing::blake2_256(&stash.to_le_bytes()))
+}
+
+/// Register the worker of a stash in pallet_phala and start mining
+pub(crate) fn register_mining_worker(stash: AccountId, controller: AccountId) {
+	let pubkey = worker_pair(stash).public().0.to_vec();
+	assert_ok!(phala::Call::<Test>::set_stash(controller).dispatch_bypass_filter(Origin::signed(stash)));
+	assert_ok!(
+		phala::Call::<Test>::force_register_worker(stash, stash.to_le_bytes().to_vec(), pubkey)
+			.dispatch_bypass_filter(Origin::root())
+	);
+	assert_ok!(phala::Call::<Test>::start_mine().dispatch_bypass_filter(Origin::signed(controller)));
+}
+
 pub type System = frame_system::Module<Test>;
 pub type Balances = pallet_balances::Module<Test>;
 pub type Session = pallet_session::Module<Test>;
 pub type Timestamp = pallet_timestamp::Module<Test>;
 pub type Staking = Module<Test>;
+pub type Phala = phala::Module<Test>;
 
 pub(crate) fn current_era() -> EraIndex {
 	Staking::current_era().unwrap()
@@ -649,6 +755,7 @@ pub(crate) fn bond_validator(stash: AccountId, ctrl: AccountId, val: Balance) {
 		val,
 		RewardDestination::Controller,
 	));
+	register_mining_worker(stash, ctrl);
 	assert_ok!(Staking::validate(
 		Origin::signed(ctrl),
 		ValidatorPrefs::default()
@@ -396,11 +396,11 @@ where
 
 	// potentially reduce the size of the compact to fit weight.
 	let maximum_allowed_voters =
-		maximum_compact_len::<T::WeightInfo>(winners.len() as u32, size, maximum_weight);
+		maximum_compact_len::<<T as Trait>::WeightInfo>(winners.len() as u32, size, maximum_weight);
 
 	crate::log!(debug, "💸 Maximum weight = {:?} // current weight = {:?} // maximum voters = {:?} // current votes = {:?}",
 		maximum_weight,
-		T::WeightInfo::submit_solution_better(
+		<T as Trait>::WeightInfo::submit_solution_better(
 				size.validators.into(),
 				size.nominators.into(),
 				compact.len() as u32,
@@ -646,7 +646,7 @@ fn pay_reporters<T: Trait>(
 	if reward_payout.is_zero() || reporters.is_empty() {
 		// nobody to pay out to or nothing to pay;
 		// just treat the whole value as slashed.
-		T::Slash::on_unbalanced(slashed_imbalance);
+		<T as Trait>::Slash::on_unbalanced(slashed_imbalance);
 		return
 	}
 
@@ -666,7 +666,7 @@ fn pay_reporters<T: Trait>(
 
 	// the rest goes to the on-slash imbalance handler (e.g. treasury)
 	value_slashed.subsume(reward_payout); // remainder of reward division remains.
-	T::Slash::on_unbalanced(value_slashed);
+	<T as Trait>::Slash::on_unbalanced(value_slashed);
 }
 
 #[cfg(test)]
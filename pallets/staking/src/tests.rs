@@ -22,11 +22,13 @@ use mock::*;
 use sp_runtime::{
 	assert_eq_error_rate, traits::BadOrigin,
 };
+use sp_core::Pair;
 use sp_staking::offence::OffenceDetails;
 use frame_support::{
 	assert_ok, assert_noop, StorageMap,
-	traits::{Currency, ReservableCurrency, OnInitialize, OnFinalize},
+	traits::{Currency, ReservableCurrency, OnInitialize, OnFinalize, UnfilteredDispatchable},
 };
+use pallet_phala as phala;
 use pallet_balances::Error as BalancesError;
 use substrate_test_utils::assert_eq_uvec;
 
@@ -4696,3 +4698,159 @@ fn payout_to_any_account_works() {
 		assert!(Balances::free_balance(42) > 0);
 	})
 }
+
+#[test]
+fn offline_workers_get_validators_slashed() {
+	ExtBuilder::default().build_and_execute(|| {
+		mock::start_era(1);
+		let exposure_11 = Staking::eras_stakers(active_era(), 11);
+		let exposure_21 = Staking::eras_stakers(active_era(), 21);
+		let validator_stake_11 = Staking::ledger(10).unwrap().active;
+		let validator_stake_21 = Staking::ledger(20).unwrap().active;
+		let nominator_stake = Staking::ledger(100).unwrap().active;
+
+		// No heartbeat is sent by the mining workers since genesis. They are found offline at the
+		// end of the round.
+		let offline_at = System::block_number() + OfflineThreshold::get() + 1;
+		let round = MiningRoundInterval::get();
+		Phala::on_initialize((offline_at + round - 1) / round * round);
+		assert_eq!(phala::types::MinerState::Offline, Phala::worker_state(11).state);
+
+		// Both of the two validators went offline
+		let slash_fraction = phala::offence::WorkerOfflineOffence::<BlockNumber, ()>::slash_fraction(2, 2);
+		assert_eq!(Perbill::from_percent(5), slash_fraction);
+		assert_eq!(
+			Staking::ledger(10).unwrap().active,
+			validator_stake_11 - slash_fraction * exposure_11.own,
+		);
+		assert_eq!(
+			Staking::ledger(20).unwrap().active,
+			validator_stake_21 - slash_fraction * exposure_21.own,
+		);
+		// 101 nominates both of them
+		assert!(Staking::ledger(100).unwrap().active < nominator_stake);
+		assert!(is_disabled(10));
+		assert!(is_disabled(20));
+	});
+}
+
+#[test]
+fn worker_equivocation_slashes_validator_and_nominators() {
+	ExtBuilder::default().validator_count(4).validator_pool(true).build_and_execute(|| {
+		mock::start_era(1);
+		assert_eq!(4, Session::validators().len());
+		let exposure = Staking::eras_stakers(active_era(), 11);
+		// 101 is a nominator for 11
+		assert_eq!(exposure.others.first().unwrap().who, 101);
+		let validator_stake = Staking::ledger(10).unwrap().active;
+		let nominator_stake = Staking::ledger(100).unwrap().active;
+		let validator_balance = balances(&11).0;
+		let reporter_balance = balances(&1).0;
+
+		// The worker of 11 signs two different messages of the same sequence
+		let signed_message = |payload: &[u8]| {
+			let data = phala::types::EgressMessage { contract_id: 1, sequence: 1, payload: payload.to_vec() };
+			let signature = worker_pair(11).sign(&data.encode()).0.to_vec();
+			phala::types::SignedEgressMessage { data, signature }.encode()
+		};
+		assert_ok!(
+			phala::Call::<Test>::report_egress_equivocation(11, signed_message(b"a"), signed_message(b"b"))
+				.dispatch_bypass_filter(Origin::signed(1))
+		);
+		assert_eq!(phala::types::MinerState::Evicted, Phala::worker_state(11).state);
+
+		// One of the four validators equivocated
+		let slash_fraction = phala::offence::EgressEquivocationOffence::<()>::slash_fraction(1, 4);
+		assert!(slash_fraction < Perbill::one());
+		let validator_share = slash_fraction * exposure.own;
+		assert!(validator_share > 0);
+		assert_eq!(Staking::ledger(10).unwrap().active, validator_stake - validator_share);
+		assert_eq!(balances(&11).0, validator_balance - validator_share);
+		let nominator_share = slash_fraction * exposure.others.first().unwrap().value;
+		assert!(nominator_share > 0);
+		assert_eq!(Staking::ledger(100).unwrap().active, nominator_stake - nominator_share);
+		assert!(is_disabled(10));
+		// The reporter is rewarded
+		assert!(balances(&1).0 > reporter_balance);
+	});
+}
+
+#[test]
+fn unhealthy_miners_sit_out_election() {
+	ExtBuilder::default().build_and_execute(|| {
+		assert_eq_uvec!(validator_controllers(), vec![10, 20]);
+		// The worker of 11 stops mining
+		assert_ok!(phala::Call::<Test>::stop_mine().dispatch_bypass_filter(Origin::signed(10)));
+		assert_eq!(None, Staking::healthy_miner_score_of(&11));
+
+		mock::start_era(1);
+		// 31 is elected instead, despite its tiny stake
+		assert_eq_uvec!(validator_controllers(), vec![20, 30]);
+		// 11 is skipped rather than chilled
+		assert!(<Validators<Test>>::contains_key(11));
+	});
+}
+
+#[test]
+fn miner_score_bonus_weights_validators() {
+	// 41 has a bit more self stake than 11 and 21, which are nominated by 101 in addition
+	let elect = |bonus: Perbill| {
+		let mut controllers = vec![];
+		ExtBuilder::default()
+			.validator_pool(true)
+			.miner_score_bonus(bonus)
+			.build_and_execute(|| {
+				assert_ok!(Staking::bond_extra(Origin::signed(41), 100));
+				mock::start_era(1);
+				controllers = validator_controllers();
+			});
+		controllers
+	};
+
+	// Without the bonus, the nominations decide
+	assert_eq_uvec!(elect(Perbill::zero()), vec![10, 20]);
+
+	// The workers score 100 each, so a 2% bonus per point triples the self stake of the
+	// validators and outweighs the nominations
+	let controllers = elect(Perbill::from_percent(2));
+	assert_eq!(2, controllers.len());
+	assert!(controllers.contains(&40));
+
+	ExtBuilder::default()
+		.validator_pool(true)
+		.miner_score_bonus(Perbill::from_percent(2))
+		.build_and_execute(|| {
+			assert_eq!(Some(100), Staking::healthy_miner_score_of(&11));
+			let weight_of = Staking::election_weight_of_fn();
+			assert_eq!(3000, weight_of(&11));
+			// Only the self stake of the validators is weighted
+			assert_eq!(500, weight_of(&101));
+		});
+}
+
+#[test]
+fn miner_score_bonus_not_exposed() {
+	// The bonus only ranks the candidates. The exposures are the bonded stake.
+	ExtBuilder::default()
+		.validator_pool(true)
+		.miner_score_bonus(Perbill::from_percent(2))
+		.build_and_execute(|| {
+			assert_ok!(Staking::bond_extra(Origin::signed(41), 100));
+			mock::start_era(1);
+			assert!(validator_controllers().contains(&40));
+
+			let mut nominated = 0;
+			for stash in Session::validators() {
+				let exposure = Staking::eras_stakers(1, stash);
+				let controller = Staking::bonded(&stash).unwrap();
+				assert_eq!(exposure.own, Staking::ledger(&controller).unwrap().active);
+				assert!(exposure.others.iter().all(|individual| individual.who == 101));
+				let others = exposure.others.iter().map(|individual| individual.value).sum::<Balance>();
+				assert_eq!(exposure.total, exposure.own + others);
+				nominated += others;
+			}
+			// 101 backs the elected one of 11 and 21 with all of its bonded stake
+			assert_eq!(500, nominated);
+			assert_eq!(Staking::eras_total_stake(1), 1100 + 1000 + 500);
+		});
+}
@@ -0,0 +1,293 @@
+//! The messages exchanged between the Phala pallet, pRuntime and phost.
+//!
+//! Every message is SCALE encoded by one component and decoded by another, so they are defined
+//! only here to keep the encodings of the pallet, the enclave and the bridge in sync.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use codec::{Encode, Decode};
+use sp_finality_grandpa::AuthorityList;
+
+pub type StorageProof = Vec<Vec<u8>>;
+
+// Signed by pRuntime and submitted to the chain
+
+/// A transfer from a confidential contract to an account on chain
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct Transfer<AccountId, Balance> {
+	pub dest: AccountId,
+	pub amount: Balance,
+	pub sequence: u64,
+}
+
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct TransferData<AccountId, Balance> {
+	pub data: Transfer<AccountId, Balance>,
+	pub signature: Vec<u8>,
+}
+
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct Heartbeat {
+	pub block_num: u32,
+}
+
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct HeartbeatData {
+	pub data: Heartbeat,
+	pub signature: Vec<u8>,
+}
+
+/// A new key of a contract generated by an assigned worker
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct ContractKeyRotation {
+	pub contract_id: u32,
+	pub pubkey: Vec<u8>,
+	pub sequence: u64,
+}
+
+/// A contract key rotation signed by the identity key of the worker
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct SignedContractKeyRotation {
+	pub data: ContractKeyRotation,
+	pub signature: Vec<u8>,
+}
+
+/// The answer of pRuntime to a challenge, bound to the block it found the challenge in
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct ChallengeResponse {
+	pub nonce: [u8; 32],
+	pub block_num: u32,
+}
+
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct SignedChallengeResponse {
+	pub data: ChallengeResponse,
+	pub signature: Vec<u8>,
+}
+
+pub type MachineId = [u8; 16];
+pub type WorkerPublicKey = [u8; 33];
+/// Length of an uncompressed secp256r1 (P-256) public key
+pub const ECDH_PUBKEY_LEN: usize = 65;
+
+/// The runtime info reported by pRuntime and bound to its attestation report
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PRuntimeInfo {
+	pub version: u8,
+	pub machine_id: MachineId,
+	pub pubkey: WorkerPublicKey,
+	/// [cpu_cores, cpu_feature_level, memory_mb, epc_mb, benchmark], where the pRuntimes before
+	/// version 2 only report the first two
+	pub features: Vec<u32>,
+	/// The ECDH public key to encrypt the commands sent to the worker. Only encoded since version
+	/// 3, and empty for the pRuntimes before.
+	pub ecdh_pubkey: Vec<u8>,
+}
+
+impl Encode for PRuntimeInfo {
+	fn encode_to<W: codec::Output>(&self, dest: &mut W) {
+		self.version.encode_to(dest);
+		self.machine_id.encode_to(dest);
+		self.pubkey.encode_to(dest);
+		self.features.encode_to(dest);
+		if self.version >= 3 {
+			self.ecdh_pubkey.encode_to(dest);
+		}
+	}
+}
+
+impl Decode for PRuntimeInfo {
+	fn decode<I: codec::Input>(input: &mut I) -> Result<Self, codec::Error> {
+		let version = u8::decode(input)?;
+		let machine_id = MachineId::decode(input)?;
+		let pubkey = WorkerPublicKey::decode(input)?;
+		let features = Vec::<u32>::decode(input)?;
+		let ecdh_pubkey = if version >= 3 {
+			Vec::<u8>::decode(input)?
+		} else {
+			Vec::new()
+		};
+		Ok(PRuntimeInfo { version, machine_id, pubkey, features, ecdh_pubkey })
+	}
+}
+
+/// The report data of the DCAP quotes. The quotes carry no timestamp, so the runtime info is bound
+/// to a recent block instead, proving the quote was produced after the block.
+#[derive(Encode, Decode, Default, Clone, Debug, PartialEq, Eq)]
+pub struct DcapReportData {
+	/// `blake2_256(encoded_runtime_info ++ encoded_block_hash)`
+	pub runtime_info_hash: [u8; 32],
+	pub block_number: u32,
+	/// Pads the report data to 64 bytes, must be zeros
+	pub reserved: [u8; 28],
+}
+
+// Sent by phost to pRuntime
+
+/// The genesis block and its validator set to initialize the light client of pRuntime
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct GenesisInfo<Header> {
+	pub header: Header,
+	pub validators: AuthorityList,
+	/// Proof of the validator set in the state of the genesis block
+	pub proof: StorageProof,
+}
+
+/// A header to sync to the light client of pRuntime, with its justification if any
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct HeaderToSync<Header> {
+	pub header: Header,
+	pub justification: Option<Vec<u8>>,
+}
+
+/// A block to dispatch to pRuntime, with its events proved against its state root
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct BlockWithEvents<SignedBlock> {
+	pub block: SignedBlock,
+	pub events: Option<Vec<u8>>,
+	pub proof: Option<StorageProof>,
+	/// Storage key of the events
+	pub key: Option<Vec<u8>>,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_finality_grandpa::AuthorityId;
+	use sp_runtime::{
+		generic::{Block, SignedBlock},
+		testing::Header,
+		traits::Header as _,
+		OpaqueExtrinsic,
+	};
+
+	fn round_trip<T: Encode + Decode + PartialEq + core::fmt::Debug>(value: T) -> Vec<u8> {
+		let encoded = value.encode();
+		assert_eq!(T::decode(&mut &encoded[..]), Ok(value));
+		encoded
+	}
+
+	fn header(number: u64) -> Header {
+		Header::new(number, Default::default(), Default::default(), [1; 32].into(), Default::default())
+	}
+
+	#[test]
+	fn test_transfer_data() {
+		let encoded = round_trip(TransferData {
+			data: Transfer { dest: [1u8; 32], amount: 2u128, sequence: 3 },
+			signature: vec![4; 65],
+		});
+		// dest + amount + sequence, then the signature prefixed by its compact length
+		assert_eq!(32 + 16 + 8 + 2 + 65, encoded.len());
+		assert_eq!(&[1; 32][..], &encoded[..32]);
+		assert_eq!(2u128.encode(), &encoded[32..48]);
+		assert_eq!(3u64.encode(), &encoded[48..56]);
+	}
+
+	#[test]
+	fn test_heartbeat_data() {
+		let encoded = round_trip(HeartbeatData {
+			data: Heartbeat { block_num: 5 },
+			signature: vec![6; 65],
+		});
+		assert_eq!(4 + 2 + 65, encoded.len());
+		assert_eq!(Heartbeat { block_num: 5 }.encode(), &encoded[..4]);
+	}
+
+	#[test]
+	fn test_signed_contract_key_rotation() {
+		let encoded = round_trip(SignedContractKeyRotation {
+			data: ContractKeyRotation { contract_id: 1, pubkey: vec![2; 33], sequence: 3 },
+			signature: vec![4; 65],
+		});
+		// contract_id + pubkey prefixed by its compact length + sequence, then the signature
+		assert_eq!(4 + 1 + 33 + 8 + 2 + 65, encoded.len());
+		assert_eq!(1u32.encode(), &encoded[..4]);
+		assert_eq!(3u64.encode(), &encoded[38..46]);
+	}
+
+	#[test]
+	fn test_signed_challenge_response() {
+		let encoded = round_trip(SignedChallengeResponse {
+			data: ChallengeResponse { nonce: [5; 32], block_num: 6 },
+			signature: vec![7; 65],
+		});
+		assert_eq!(32 + 4 + 2 + 65, encoded.len());
+		assert_eq!(&[5; 32][..], &encoded[..32]);
+		assert_eq!(6u32.encode(), &encoded[32..36]);
+	}
+
+	#[test]
+	fn test_runtime_info() {
+		let mut info = PRuntimeInfo {
+			version: 2,
+			machine_id: [1; 16],
+			pubkey: [2; 33],
+			features: vec![4, 1],
+			ecdh_pubkey: Vec::new(),
+		};
+		// The ECDH public key isn't encoded before version 3
+		assert_eq!(1 + 16 + 33 + 1 + 8, round_trip(info.clone()).len());
+
+		info.version = 3;
+		info.ecdh_pubkey = vec![4; ECDH_PUBKEY_LEN];
+		assert_eq!(1 + 16 + 33 + 1 + 8 + 2 + ECDH_PUBKEY_LEN, round_trip(info.clone()).len());
+
+		// A key reported by an old pRuntime is dropped
+		info.version = 2;
+		let decoded = PRuntimeInfo::decode(&mut &info.encode()[..]).unwrap();
+		assert!(decoded.ecdh_pubkey.is_empty());
+	}
+
+	#[test]
+	fn test_dcap_report_data() {
+		let encoded = round_trip(DcapReportData {
+			runtime_info_hash: [1; 32],
+			block_number: 2,
+			..Default::default()
+		});
+		// Fills the report data of an SGX report
+		assert_eq!(64, encoded.len());
+		assert_eq!(2u32.encode(), &encoded[32..36]);
+	}
+
+	#[test]
+	fn test_genesis_info() {
+		let validator = AuthorityId::decode(&mut &[7u8; 32][..]).unwrap();
+		round_trip(GenesisInfo {
+			header: header(0),
+			validators: vec![(validator, 1)],
+			proof: vec![vec![8; 10], vec![9; 20]],
+		});
+	}
+
+	#[test]
+	fn test_header_to_sync() {
+		round_trip(HeaderToSync { header: header(1), justification: None });
+		round_trip(HeaderToSync { header: header(2), justification: Some(vec![10; 100]) });
+	}
+
+	#[test]
+	fn test_block_with_events() {
+		type OpaqueSignedBlock = SignedBlock<Block<Header, OpaqueExtrinsic>>;
+		let block = OpaqueSignedBlock {
+			block: Block { header: header(3), extrinsics: Vec::new() },
+			justification: None,
+		};
+		round_trip(BlockWithEvents {
+			block: block.clone(),
+			events: Some(vec![11; 10]),
+			proof: Some(vec![vec![12; 10]]),
+			key: Some(vec![13; 32]),
+		});
+		round_trip(BlockWithEvents::<OpaqueSignedBlock> {
+			block,
+			events: None,
+			proof: None,
+			key: None,
+		});
+	}
+}
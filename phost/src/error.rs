@@ -12,6 +12,7 @@ pub enum Error {
   SearchSetIdChangeInEmptyRange,
   FailedToDecode,
   FailedToCallRegisterWorker,
+  UnsupportedRuntime { spec_version: u32, supported: (u32, u32) },
 }
 
 impl From<hyper::error::Error> for Error {
@@ -18,9 +18,10 @@ use crate::error::Error;
 use crate::types::{
     Runtime, Header, Hash, BlockNumber, RawEvents, StorageProof, RawStorageKey,
     GetInfoReq, QueryReq, ReqData, Payload, Query, PendingChainTransfer, TransferData,
-    InitRuntimeReq, GenesisInfo,
+    InitRuntimeReq, AttestationPayload, GenesisInfo,
     SyncHeaderReq, SyncHeaderResp, BlockWithEvents, HeaderToSync, AuthoritySet, AuthoritySetChange,
     DispatchBlockReq, DispatchBlockResp, PingReq, /*PingResp,*/ HeartbeatData, /*Heartbeat*/
+    GetChallengeResponseReq,
 };
 
 use subxt::Signer;
@@ -51,6 +52,11 @@ struct Args {
     help = "Should enable Remote Attestation")]
     ra: bool,
 
+    #[structopt(
+    long = "dcap",
+    help = "Attest pRuntime with an ECDSA quote of SGX DCAP instead of an EPID report from IAS")]
+    dcap: bool,
+
     #[structopt(
     default_value = "ws://localhost:9944", long,
     help = "Substrate rpc websocket endpoint")]
@@ -75,6 +81,10 @@ struct Args {
     #[structopt(default_value = "100", long = "sync-blocks",
     help = "The batch size to sync blocks to pRuntime.")]
     sync_blocks: usize,
+
+    #[structopt(default_value = "10", long = "transfer-batch",
+    help = "The max number of egress transfers submitted to Substrate in one extrinsic. Each of them is counted against the transfer quota of the account on chain, and the batch is split when it's rejected.")]
+    transfer_batch: usize,
 }
 
 struct BlockSyncState {
@@ -115,8 +125,8 @@ async fn get_storage(client: &XtClient, hash: Option<Hash>, storage_key: Storage
     Ok(storage.map(|data| (&data.0[..]).to_vec()))
 }
 
-async fn read_proof(client: &XtClient, hash: Option<Hash>, storage_key: StorageKey) -> Result<ReadProof<Hash>, Error> {
-    client.read_proof(vec![storage_key], hash).await.map_err(Into::into)
+async fn read_proof(client: &XtClient, hash: Option<Hash>, storage_keys: Vec<StorageKey>) -> Result<ReadProof<Hash>, Error> {
+    client.read_proof(storage_keys, hash).await.map_err(Into::into)
 }
 
 async fn get_authority_with_proof_at(client: &XtClient, hash: Hash) -> Result<AuthoritySetChange, Error> {
@@ -127,7 +137,7 @@ async fn get_authority_with_proof_at(client: &XtClient, hash: Hash) -> Result<Au
     let authority_set: AuthorityList = VersionedAuthorityList::decode(&mut value.as_slice())
         .expect("Failed to decode VersionedAuthorityList").into();
     // Proof
-    let proof = read_proof(&client, Some(hash), storage_key).await?.proof;
+    let proof = read_proof(&client, Some(hash), vec![storage_key]).await?.proof;
     let mut prf = Vec::new();
     for p in proof {
         prf.push(p.to_vec());
@@ -195,9 +205,11 @@ async fn fetch_events(client: &XtClient, hash: &Hash)
     // let hash = client.block_hash(Some(subxt::BlockNumber::from(block_number))).await?;
     let key = storage_value_key_vec("System", "Events");
     let storage_key = StorageKey(key.clone());
+    // Also prove the runtime version for pRuntime to check if it can decode the block
+    let runtime_upgrade_key = StorageKey(storage_value_key_vec("System", "LastRuntimeUpgrade"));
     let result = match get_storage(&client, Some(hash.clone()), storage_key.clone()).await? {
         Some(value) => {
-            let proof = read_proof(&client, Some(hash.clone()), storage_key).await?
+            let proof = read_proof(&client, Some(hash.clone()), vec![storage_key, runtime_upgrade_key]).await?
                 .proof
                 .iter()
                 .map(|x| x.to_vec())
@@ -385,7 +397,10 @@ async fn update_singer_nonce(client: &XtClient, signer: &mut subxt::PairSigner<R
     Ok(())
 }
 
-async fn sync_tx_to_chain(client: &XtClient, pr: &PrClient, sequence: &mut u64, pair: sr25519::Pair) -> Result<(), Error> {
+async fn sync_tx_to_chain(
+    client: &XtClient, pr: &PrClient, sequence: &mut u64, pair: sr25519::Pair,
+    batch_size: &mut usize, max_batch_size: usize
+) -> Result<(), Error> {
     let query = Query {
         contract_id: 2,
         nonce: 0,
@@ -407,30 +422,44 @@ async fn sync_tx_to_chain(client: &XtClient, pr: &PrClient, sequence: &mut u64,
         return Ok(());
     }
 
-    let mut signer = subxt::PairSigner::<Runtime, _>::new(pair);
-    update_singer_nonce(&client, &mut signer).await?;
-
+    // Only submit the contiguous run after the last submitted sequence
+    let mut pending: Vec<&TransferData> = transfer_queue.iter()
+        .filter(|transfer_data| transfer_data.data.sequence > *sequence)
+        .collect();
+    pending.sort_by_key(|transfer_data| transfer_data.data.sequence);
+    // Every message is counted against the call quota of the account, so only one batch is
+    // submitted at a time, and the rest are left to the next rounds
+    let mut messages: Vec<Vec<u8>> = Vec::new();
     let mut max_seq = *sequence;
-    for transfer_data in &transfer_queue {
-        if transfer_data.data.sequence <= *sequence {
-            println!("The tx has been submitted.");
-            continue;
-        }
-        if transfer_data.data.sequence > max_seq {
-            max_seq = transfer_data.data.sequence;
-        }
-
-        let call = runtimes::phala::TransferToChainCall { _runtime: PhantomData, data: transfer_data.encode() };
-        let ret = client.submit(call, &signer).await;
-        if ret.is_ok() {
-            println!("Submit tx successfully");
-        } else {
-            println!("Failed to submit tx: {:?}", ret);
+    for transfer_data in pending.into_iter().take((*batch_size).max(1)) {
+        if transfer_data.data.sequence != max_seq + 1 {
+            break;
         }
-        signer.increment_nonce();
+        messages.push(transfer_data.encode());
+        max_seq = transfer_data.data.sequence;
+    }
+    if messages.is_empty() {
+        println!("The txs have been submitted.");
+        return Ok(());
     }
 
-    *sequence = max_seq;
+    let mut signer = subxt::PairSigner::<Runtime, _>::new(pair);
+    update_singer_nonce(&client, &mut signer).await?;
+
+    let num_messages = messages.len();
+    let call = runtimes::phala::TransferToChainBatchCall { _runtime: PhantomData, messages };
+    let ret = client.submit(call, &signer).await;
+    if ret.is_ok() {
+        println!("Submit {} txs successfully", num_messages);
+        *sequence = max_seq;
+        // Grow back after the quota is renewed
+        *batch_size = (*batch_size * 2).min(max_batch_size.max(1));
+    } else {
+        // A batch larger than the remaining quota is rejected as a whole. Split it, and submit it
+        // again in the next round.
+        println!("Failed to submit txs: {:?}", ret);
+        *batch_size = (num_messages / 2).max(1);
+    }
 
     Ok(())
 }
@@ -462,6 +491,48 @@ async fn send_heartbeat_to_chain(client: &XtClient, pr: &PrClient, pair: sr25519
     Ok(())
 }
 
+/// Attempts to submit a challenge response before waiting for the next blocks
+const CHALLENGE_SUBMIT_ATTEMPTS: u32 = 3;
+/// Blocks dispatched by pRuntime after a submission before the same response is submitted again
+const CHALLENGE_RESUBMIT_BLOCKS: BlockNumber = 2;
+
+async fn send_challenge_response_to_chain(
+    client: &XtClient, pr: &PrClient, pair: sr25519::Pair,
+    submitted: &mut Option<(Vec<u8>, BlockNumber)>, blocknum: BlockNumber
+) -> Result<(), Error> {
+    let result = pr.req_decode("get_challenge_response", GetChallengeResponseReq {}).await;
+    let data = match result {
+        Ok(result) if result.status == "ok" => base64::decode(&mut &result.encoded_data).unwrap(),
+        // No challenge to answer
+        _ => return Ok(())
+    };
+    // pRuntime keeps the challenge until it sees it answered or missed. Give the last submission
+    // some blocks to get in before submitting it again.
+    if let Some((last_data, submitted_at)) = submitted {
+        if *last_data == data && blocknum <= *submitted_at + CHALLENGE_RESUBMIT_BLOCKS {
+            return Ok(())
+        }
+    }
+    println!("Answer the challenge");
+
+    let mut signer = subxt::PairSigner::<Runtime, _>::new(pair);
+    for attempt in 1 ..= CHALLENGE_SUBMIT_ATTEMPTS {
+        update_singer_nonce(&client, &mut signer).await?;
+        let call = runtimes::phala::RespondChallengeCall { _runtime: PhantomData, data: data.clone() };
+        let ret = client.submit(call, &signer).await;
+        if ret.is_ok() {
+            println!("Submit challenge response successfully");
+            *submitted = Some((data, blocknum));
+            return Ok(())
+        }
+        println!("Failed to submit challenge response (attempt {}/{}): {:?}",
+                 attempt, CHALLENGE_SUBMIT_ATTEMPTS, ret);
+        delay_for(Duration::from_millis(1000)).await;
+    }
+
+    Ok(())
+}
+
 async fn bridge(args: Args) -> Result<(), Error> {
     // Connect to substrate
     let client = subxt::ClientBuilder::<Runtime>::new()
@@ -489,28 +560,58 @@ async fn bridge(args: Args) -> Result<(), Error> {
         };
 
         let info_b64 = base64::encode(&info.encode());
+        // The DCAP quote is bound to the latest finalized block to prove its freshness
+        let dcap_anchor_b64 = if args.dcap {
+            let anchor_hash = client.finalized_head().await?;
+            let anchor = client.block(Some(anchor_hash)).await?
+                .ok_or(Error::BlockNotFound)?;
+            Some(base64::encode(&(anchor.block.header.number, anchor_hash).encode()))
+        } else {
+            None
+        };
         let runtime_info = pr.req_decode("init_runtime", InitRuntimeReq {
             skip_ra: !args.ra,
             bridge_genesis_info_b64: info_b64,
             debug_set_key: match args.use_dev_key {
                 true => Some(String::from("0000000000000000000000000000000000000000000000000000000000000001")),
                 false => None
-            }
+            },
+            attestation_provider: match args.dcap {
+                true => Some(String::from("DCAP")),
+                false => None
+            },
+            dcap_anchor_b64,
         }).await?;
 
         println!("runtime_info:{:?}", runtime_info);
         if let Some(attestation) = runtime_info.attestation {
-            let signature = base64::decode(&attestation.payload.signature).expect("Failed to decode signature");
-            let raw_signing_cert = base64::decode_config(&attestation.payload.signing_cert, base64::STANDARD).expect("Failed to decode certificate");
-            let call = runtimes::phala::RegisterWorkerCall {
-                _runtime: PhantomData,
-                encoded_runtime_info: runtime_info.encoded_runtime_info.to_vec(),
-                report: attestation.payload.report.as_bytes().to_vec(),
-                signature,
-                raw_signing_cert,
-            };
             let signer = subxt::PairSigner::new(pair.clone());
-            let ret = client.watch(call, &signer).await;
+            let ret = match attestation.payload {
+                AttestationPayload::Ias(report) => {
+                    let signature = base64::decode(&report.signature).expect("Failed to decode signature");
+                    let raw_signing_cert = base64::decode_config(&report.signing_cert, base64::STANDARD).expect("Failed to decode certificate");
+                    let call = runtimes::phala::RegisterWorkerCall {
+                        _runtime: PhantomData,
+                        encoded_runtime_info: runtime_info.encoded_runtime_info.to_vec(),
+                        report: report.report.as_bytes().to_vec(),
+                        signature,
+                        raw_signing_cert,
+                    };
+                    client.watch(call, &signer).await
+                },
+                AttestationPayload::Dcap(quote) => {
+                    let pck_cert_chain = quote.pck_cert_chain.iter()
+                        .map(|cert| base64::decode(cert).expect("Failed to decode certificate"))
+                        .collect();
+                    let call = runtimes::phala::RegisterWorkerDcapCall {
+                        _runtime: PhantomData,
+                        encoded_runtime_info: runtime_info.encoded_runtime_info.to_vec(),
+                        quote: base64::decode(&quote.quote).expect("Failed to decode quote"),
+                        pck_cert_chain,
+                    };
+                    client.watch(call, &signer).await
+                },
+            };
             if !ret.is_ok() {
                 return Err(Error::FailedToCallRegisterWorker);
             }
@@ -525,6 +626,8 @@ async fn bridge(args: Args) -> Result<(), Error> {
     }
 
     let mut sequence = get_balances_ingress_seq(&client).await?;
+    let mut challenge_submitted = None;
+    let mut transfer_batch = args.transfer_batch;
     let mut sync_state = BlockSyncState {
         blocks: Vec::new(),
         authory_set_state: None
@@ -555,6 +658,14 @@ async fn bridge(args: Args) -> Result<(), Error> {
             None => info.headernum
         };
         let batch_end = std::cmp::min(latest_block.block.header.number, next_block + args.fetch_blocks - 1);
+        // stop before fetching the blocks of a chain runtime pRuntime can't decode
+        let batch_end_hash = client.block_hash(Some(subxt::BlockNumber::from(NumberOrHex::Number(batch_end.into())))).await?
+            .ok_or(Error::BlockHashNotFound)?;
+        let spec_version = client.rpc.runtime_version(Some(batch_end_hash)).await?.spec_version;
+        let (min_spec_version, max_spec_version) = info.supported_spec_versions;
+        if spec_version < min_spec_version || spec_version > max_spec_version {
+            return Err(Error::UnsupportedRuntime { spec_version, supported: info.supported_spec_versions });
+        }
         for b in next_block ..= batch_end {
             let block = get_block_at(&client, Some(b), true).await?;
             if block.block.justification.is_some() {
@@ -564,7 +675,7 @@ async fn bridge(args: Args) -> Result<(), Error> {
         }
 
         if !args.no_write_back {
-            sync_tx_to_chain(&client, &pr, &mut sequence, pair.clone()).await?;
+            sync_tx_to_chain(&client, &pr, &mut sequence, pair.clone(), &mut transfer_batch, args.transfer_batch).await?;
         }
 
         // send the blocks to pRuntime in batch
@@ -578,6 +689,9 @@ async fn bridge(args: Args) -> Result<(), Error> {
                 println!("send heartbeat");
                 send_heartbeat_to_chain(&client, &pr, pair.clone()).await?;
             }
+            if info.initialized && !args.no_write_back {
+                send_challenge_response_to_chain(&client, &pr, pair.clone(), &mut challenge_submitted, info.blocknum).await?;
+            }
 
             println!("waiting for new blocks");
             delay_for(Duration::from_millis(5000)).await;
@@ -143,6 +143,15 @@ pub mod phala {
         pub data: Vec<u8>,
     }
 
+    /// The call to transfer_to_chain_batch
+    #[derive(Clone, Debug, PartialEq, Call, Encode)]
+    pub struct TransferToChainBatchCall<T: PhalaModule> {
+        /// Runtime marker
+        pub _runtime: PhantomData<T>,
+        /// The contiguous transfer transaction data, each SCALE encoded
+        pub messages: Vec<Vec<u8>>,
+    }
+
     /// The call to register_worker
     #[derive(Clone, Debug, PartialEq, Call, Encode)]
     pub struct RegisterWorkerCall<T: PhalaModule> {
@@ -158,6 +167,19 @@ pub mod phala {
         pub raw_signing_cert: Vec<u8>,
     }
 
+    /// The call to register_worker_dcap
+    #[derive(Clone, Debug, PartialEq, Call, Encode)]
+    pub struct RegisterWorkerDcapCall<T: PhalaModule> {
+        /// Runtime marker
+        pub _runtime: PhantomData<T>,
+        /// The encoded runtime info
+        pub encoded_runtime_info: Vec<u8>,
+        /// The ECDSA quote
+        pub quote: Vec<u8>,
+        /// The DER encoded PCK certificate chain
+        pub pck_cert_chain: Vec<Vec<u8>>,
+    }
+
     #[derive(Clone, Debug, Eq, PartialEq, Store, Encode)]
     pub struct IngressSequenceStore<T: PhalaModule> {
         #[store(returns = u64)]
@@ -183,4 +205,13 @@ pub mod phala {
         pub data: Vec<u8>,
     }
 
+    /// The call to respond_challenge
+    #[derive(Clone, Debug, PartialEq, Call, Encode)]
+    pub struct RespondChallengeCall<T: PhalaModule> {
+        /// Runtime marker
+        pub _runtime: PhantomData<T>,
+        /// The signed challenge response, SCALE encoded
+        pub data: Vec<u8>,
+    }
+
 }
@@ -4,7 +4,6 @@ use codec::{Encode, Decode};
 use sp_finality_grandpa::{AuthorityList, SetId};
 use sp_runtime::{
     generic::SignedBlock,
-    Justification,
     OpaqueExtrinsic
 };
 
@@ -21,9 +20,16 @@ pub type OpaqueSignedBlock = SignedBlock<OpaqueBlock>;
 pub type BlockNumber = <Runtime as subxt::system::System>::BlockNumber;
 
 pub type RawStorageKey = Vec<u8>;
-pub type StorageProof = Vec<Vec<u8>>;
 pub type RawEvents = Vec<u8>;
 
+// Messages shared with the pallet and pRuntime
+
+pub use phala_types::{StorageProof, Heartbeat, HeartbeatData};
+pub type TransferData = phala_types::TransferData<[u8; 32], u128>;
+pub type GenesisInfo = phala_types::GenesisInfo<Header>;
+pub type HeaderToSync = phala_types::HeaderToSync<Header>;
+pub type BlockWithEvents = phala_types::BlockWithEvents<OpaqueSignedBlock>;
+
 // pRuntime APIs
 
 pub trait Resp {
@@ -70,6 +76,8 @@ pub struct GetInfoResp {
     pub initialized: bool,
     pub public_key: String,
     pub ecdh_public_key: String,
+    pub spec_version: Option<u32>,
+    pub supported_spec_versions: (u32, u32),
 }
 impl Resp for GetInfoReq {
     type Resp = GetInfoResp;
@@ -113,20 +121,6 @@ pub struct TransferQueue {
     pub transfer_queue_b64: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[derive(Encode, Decode)]
-pub struct Transfer {
-    pub dest: [u8; 32],
-    pub amount: u128,
-    pub sequence: u64,
-}
-#[derive(Serialize, Deserialize, Debug)]
-#[derive(Encode, Decode)]
-pub struct TransferData {
-    pub data: Transfer,
-    pub signature: Vec<u8>,
-}
-
 impl Resp for QueryReq {
     type Resp = QueryResp;
 }
@@ -138,6 +132,9 @@ pub struct InitRuntimeReq {
   pub skip_ra: bool,
   pub bridge_genesis_info_b64: String,
   pub debug_set_key: Option<String>,
+  pub attestation_provider: Option<String>,
+  /// The SCALE encoded number and hash of a recent block, which the DCAP quote is bound to
+  pub dcap_anchor_b64: Option<String>,
 }
 #[derive(Serialize, Deserialize, Debug)]
 pub struct InitRuntimeResp {
@@ -150,7 +147,13 @@ pub struct InitRuntimeResp {
 pub struct InitRespAttestation {
   pub version: i32,
   pub provider: String,
-  pub payload: AttestationReport,
+  pub payload: AttestationPayload,
+}
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum AttestationPayload {
+  Ias(AttestationReport),
+  Dcap(DcapQuote),
 }
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AttestationReport {
@@ -158,15 +161,14 @@ pub struct AttestationReport {
   pub signature: String,
   pub signing_cert: String,
 }
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DcapQuote {
+  pub quote: String,
+  pub pck_cert_chain: Vec<String>,
+}
 impl Resp for InitRuntimeReq {
   type Resp = InitRuntimeResp;
 }
-#[derive(Encode, Decode)]
-pub struct GenesisInfo {
-  pub header: Header,
-  pub validators: AuthorityList,
-  pub proof: StorageProof,
-}
 
 // API: sync_header
 
@@ -182,18 +184,6 @@ pub struct SyncHeaderResp {
 impl Resp for SyncHeaderReq {
     type Resp = SyncHeaderResp;
 }
-#[derive(Encode, Decode, Clone, Debug)]
-pub struct BlockWithEvents {
-    pub block: OpaqueSignedBlock,
-    pub events: Option<Vec<u8>>,
-    pub proof: Option<StorageProof>,
-    pub key: Option<Vec<u8>>,
-}
-#[derive(Encode, Decode, Clone, Debug)]
-pub struct HeaderToSync {
-    pub header: Header,
-    pub justification: Option<Justification>,
-}
 #[derive(Encode, Decode, Clone, PartialEq, Debug)]
 pub struct AuthoritySet {
     pub authority_set: AuthorityList,
@@ -207,19 +197,6 @@ pub struct AuthoritySetChange {
 
 // API: ping
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[derive(Encode, Decode)]
-pub struct Heartbeat {
-    pub block_num: u32,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[derive(Encode, Decode)]
-pub struct HeartbeatData {
-    pub data: Heartbeat,
-    pub signature: Vec<u8>,
-}
-
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PingReq {}
 #[derive(Serialize, Deserialize, Debug)]
@@ -231,6 +208,19 @@ impl Resp for PingReq {
     type Resp = PingResp;
 }
 
+// API: get_challenge_response
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetChallengeResponseReq {}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetChallengeResponseResp {
+    pub status: String,
+    pub encoded_data: String
+}
+impl Resp for GetChallengeResponseReq {
+    type Resp = GetChallengeResponseResp;
+}
+
 // API: dispatch_block
 
 #[derive(Serialize, Deserialize, Debug)]
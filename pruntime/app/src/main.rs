@@ -252,6 +252,111 @@ fn ocall_load_persistent_data(
     sgx_status_t::SGX_SUCCESS
 }
 
+// Total physical memory from /proc/meminfo
+fn total_memory_mb() -> u32 {
+    let meminfo = fs::read_to_string("/proc/meminfo").unwrap_or_default();
+    meminfo.lines()
+        .find(|line| line.starts_with("MemTotal:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .map(|kb| (kb / 1024) as u32)
+        .unwrap_or(0)
+}
+
+// Total size of the EPC sections enumerated by CPUID leaf 0x12
+fn total_epc_mb() -> u32 {
+    use std::arch::x86_64::__cpuid_count;
+
+    let mut total: u64 = 0;
+    for subleaf in 2.. {
+        let section = unsafe { __cpuid_count(0x12, subleaf) };
+        // Sub-leaf type 0 marks the end of the EPC sections
+        if section.eax & 0xf != 1 {
+            break;
+        }
+        let size = (section.ecx & 0xfffff000) as u64 | ((section.edx & 0xfffff) as u64) << 32;
+        total += size;
+    }
+    (total / 1024 / 1024) as u32
+}
+
+#[no_mangle]
+pub extern "C"
+fn ocall_get_machine_info(
+    memory_mb: *mut u32,
+    epc_mb: *mut u32
+) -> sgx_status_t {
+    unsafe {
+        *memory_mb = total_memory_mb();
+        *epc_mb = total_epc_mb();
+    }
+
+    sgx_status_t::SGX_SUCCESS
+}
+
+#[cfg(feature = "dcap")]
+#[no_mangle]
+pub extern "C"
+fn ocall_get_dcap_target_info(ret_ti: *mut sgx_target_info_t) -> sgx_status_t {
+    println!("Entering ocall_get_dcap_target_info");
+    let ret = unsafe { sgx_qe_get_target_info(ret_ti) };
+    if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+        println!("sgx_qe_get_target_info returned {:?}", ret);
+        return sgx_status_t::SGX_ERROR_UNEXPECTED;
+    }
+    sgx_status_t::SGX_SUCCESS
+}
+
+#[cfg(feature = "dcap")]
+#[no_mangle]
+pub extern "C"
+fn ocall_get_dcap_quote(p_report: *const sgx_report_t,
+                        p_quote: *mut u8,
+                        maxlen: u32,
+                        p_quote_len: *mut u32) -> sgx_status_t {
+    println!("Entering ocall_get_dcap_quote");
+
+    let mut quote_len: u32 = 0;
+    let ret = unsafe { sgx_qe_get_quote_size(&mut quote_len as *mut u32) };
+    if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+        println!("sgx_qe_get_quote_size returned {:?}", ret);
+        return sgx_status_t::SGX_ERROR_UNEXPECTED;
+    }
+    if quote_len > maxlen {
+        println!("quote size {} exceeds the buffer", quote_len);
+        return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    }
+
+    println!("quote size = {}", quote_len);
+    unsafe { *p_quote_len = quote_len; }
+
+    let ret = unsafe { sgx_qe_get_quote(p_report, quote_len, p_quote) };
+    if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+        println!("sgx_qe_get_quote returned {:?}", ret);
+        return sgx_status_t::SGX_ERROR_UNEXPECTED;
+    }
+    sgx_status_t::SGX_SUCCESS
+}
+
+// Without the DCAP Quote Library, only the EPID attestation via IAS is available
+#[cfg(not(feature = "dcap"))]
+#[no_mangle]
+pub extern "C"
+fn ocall_get_dcap_target_info(_ret_ti: *mut sgx_target_info_t) -> sgx_status_t {
+    println!("DCAP is not enabled in this build");
+    sgx_status_t::SGX_ERROR_FEATURE_NOT_SUPPORTED
+}
+
+#[cfg(not(feature = "dcap"))]
+#[no_mangle]
+pub extern "C"
+fn ocall_get_dcap_quote(_p_report: *const sgx_report_t,
+                        _p_quote: *mut u8,
+                        _maxlen: u32,
+                        _p_quote_len: *mut u32) -> sgx_status_t {
+    sgx_status_t::SGX_ERROR_FEATURE_NOT_SUPPORTED
+}
+
 fn init_enclave() -> SgxResult<SgxEnclave> {
     let mut launch_token: sgx_launch_token_t = [0; 1024];
     let mut launch_token_updated: i32 = 0;
@@ -710,6 +815,88 @@ fn ping(contract_input: Json<ContractInput>) -> JsonValue {
     }
 }
 
+#[post("/get_contract_key", format = "json", data = "<contract_input>")]
+fn get_contract_key(contract_input: Json<ContractInput>) -> JsonValue {
+    println!("{}", ::serde_json::to_string_pretty(&*contract_input).unwrap());
+
+    let eid = get_eid();
+    let mut retval = sgx_status_t::SGX_SUCCESS;
+
+    let input_string = serde_json::to_string(&*contract_input).unwrap();
+
+    let mut return_output_buf = vec![0; ENCLAVE_OUTPUT_BUF_MAX_LEN].into_boxed_slice();
+    let mut output_len : usize = 0;
+    let output_slice = &mut return_output_buf;
+    let output_ptr = output_slice.as_mut_ptr();
+    let output_len_ptr = &mut output_len as *mut usize;
+
+    let mut retval = sgx_status_t::SGX_SUCCESS;
+    let result = unsafe {
+        ecall_handle(
+            eid, &mut retval,
+            9,
+            input_string.as_ptr(), input_string.len(),
+            output_ptr, output_len_ptr, ENCLAVE_OUTPUT_BUF_MAX_LEN
+        )
+    };
+
+    match result {
+        sgx_status_t::SGX_SUCCESS => {
+            let output_slice = unsafe { std::slice::from_raw_parts(output_ptr, output_len) };
+            let output_value: serde_json::value::Value = serde_json::from_slice(output_slice).unwrap();
+            json!(output_value)
+        },
+        _ => {
+            println!("[-] ECALL Enclave Failed {}!", result.as_str());
+            json!({
+                "status": "error",
+                "payload": format!("[-] ECALL Enclave Failed {}!", result.as_str())
+            })
+        }
+    }
+}
+
+#[post("/get_challenge_response", format = "json", data = "<contract_input>")]
+fn get_challenge_response(contract_input: Json<ContractInput>) -> JsonValue {
+    println!("{}", ::serde_json::to_string_pretty(&*contract_input).unwrap());
+
+    let eid = get_eid();
+    let mut retval = sgx_status_t::SGX_SUCCESS;
+
+    let input_string = serde_json::to_string(&*contract_input).unwrap();
+
+    let mut return_output_buf = vec![0; ENCLAVE_OUTPUT_BUF_MAX_LEN].into_boxed_slice();
+    let mut output_len : usize = 0;
+    let output_slice = &mut return_output_buf;
+    let output_ptr = output_slice.as_mut_ptr();
+    let output_len_ptr = &mut output_len as *mut usize;
+
+    let mut retval = sgx_status_t::SGX_SUCCESS;
+    let result = unsafe {
+        ecall_handle(
+            eid, &mut retval,
+            10,
+            input_string.as_ptr(), input_string.len(),
+            output_ptr, output_len_ptr, ENCLAVE_OUTPUT_BUF_MAX_LEN
+        )
+    };
+
+    match result {
+        sgx_status_t::SGX_SUCCESS => {
+            let output_slice = unsafe { std::slice::from_raw_parts(output_ptr, output_len) };
+            let output_value: serde_json::value::Value = serde_json::from_slice(output_slice).unwrap();
+            json!(output_value)
+        },
+        _ => {
+            println!("[-] ECALL Enclave Failed {}!", result.as_str());
+            json!({
+                "status": "error",
+                "payload": format!("[-] ECALL Enclave Failed {}!", result.as_str())
+            })
+        }
+    }
+}
+
 fn cors_options() -> CorsOptions {
     let allowed_origins = AllowedOrigins::all();
     let allowed_methods: AllowedMethods = vec![Method::Get, Method::Post].into_iter().map(From::from).collect();
@@ -730,7 +917,7 @@ fn rocket() -> rocket::Rocket {
             test, init_runtime, get_info,
             dump_states, load_states,
             sync_header, dispatch_block, query,
-            set, get, ping])
+            set, get, ping, get_contract_key, get_challenge_response])
         .attach(cors_options().to_cors().expect("To not fail"))
     // .mount("/", rocket_cors::catch_all_options_routes()) // mount the catch all routes
     // .manage(cors_options().to_cors().expect("To not fail"))
@@ -57,19 +57,41 @@ pub enum Request {
     TotalIssuance,
     PendingChainTransfer { sequence: SequenceType },
 }
+/// The transfer message signed by the contract and submitted to the chain by phost
+type ChainTransferData = phala_types::TransferData<chain::AccountId, chain::Balance>;
+
+// The queued transfers as kept in the contract state. They mirror `phala_types::TransferData`
+// only to keep the JSON shape of the states dumped by the earlier versions.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-#[derive(Encode, Decode)]
 pub struct Transfer {
     dest: AccountIdWrapper,
     amount: chain::Balance,
     sequence: SequenceType,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
-#[derive(Encode, Decode)]
 pub struct TransferData {
     data: Transfer,
     signature: Vec<u8>,
 }
+
+impl Transfer {
+    fn to_chain(&self) -> phala_types::Transfer<chain::AccountId, chain::Balance> {
+        phala_types::Transfer {
+            dest: self.dest.0.clone(),
+            amount: self.amount,
+            sequence: self.sequence,
+        }
+    }
+}
+
+impl TransferData {
+    fn to_chain(&self) -> ChainTransferData {
+        ChainTransferData {
+            data: self.data.to_chain(),
+            signature: self.signature.clone(),
+        }
+    }
+}
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Response {
     FreeBalance {
@@ -156,7 +178,7 @@ impl contracts::Contract<Command, Request, Response> for Balances {
                         };
 
                         let id = self.id.as_ref().unwrap();
-                        let sig = id.sign(&Encode::encode(&data));
+                        let sig = id.sign(&data.to_chain().encode());
                         let transfer_data = TransferData {
                             data,
                             signature: sig.0.to_vec(),
@@ -192,7 +214,10 @@ impl contracts::Contract<Command, Request, Response> for Balances {
                 },
                 Request::PendingChainTransfer {sequence} => {
                     println!("PendingChainTransfer");
-                    let transfer_queue: Vec<&TransferData> = self.queue.iter().filter(|x| x.data.sequence > sequence).collect::<_>();
+                    let transfer_queue: Vec<ChainTransferData> = self.queue.iter()
+                        .filter(|x| x.data.sequence > sequence)
+                        .map(TransferData::to_chain)
+                        .collect::<_>();
 
                     Ok(Response::PendingChainTransfer { transfer_queue_b64: base64::encode(&transfer_queue.encode()) } )
                 },
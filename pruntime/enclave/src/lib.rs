@@ -42,9 +42,13 @@ use serde_cbor;
 use serde_json::{Map, Value};
 use serde::{de, Serialize, Deserialize, Serializer, Deserializer};
 use sp_core::H256 as Hash;
-use sp_core::hashing::blake2_256;
+use sp_core::hashing::{blake2_256, twox_128};
 use sp_core::crypto::Pair;
 use system::EventRecord;
+use phala_types::{
+    PRuntimeInfo, DcapReportData, Heartbeat, HeartbeatData, ChallengeResponse, SignedChallengeResponse,
+    ContractKeyRotation, SignedContractKeyRotation,
+};
 
 mod cert;
 mod contracts;
@@ -102,6 +106,25 @@ extern "C" {
         ret_val: *mut sgx_status_t,
         output_ptr : *mut u8, output_len_ptr: *mut usize, output_buf_len: usize
     ) -> sgx_status_t;
+
+    pub fn ocall_get_machine_info(
+        ret_val: *mut sgx_status_t,
+        memory_mb: *mut u32,
+        epc_mb: *mut u32
+    ) -> sgx_status_t;
+
+    pub fn ocall_get_dcap_target_info(
+        ret_val: *mut sgx_status_t,
+        ret_ti: *mut sgx_target_info_t
+    ) -> sgx_status_t;
+
+    pub fn ocall_get_dcap_quote(
+        ret_val: *mut sgx_status_t,
+        p_report: *const sgx_report_t,
+        p_quote: *mut u8,
+        maxlen: u32,
+        p_quote_len: *mut u32
+    ) -> sgx_status_t;
 }
 
 const IAS_SPID_STR: &str = env!("IAS_SPID");
@@ -112,6 +135,16 @@ pub const IAS_SIGRL_ENDPOINT:&'static str = env!("IAS_SIGRL_ENDPOINT");
 pub const IAS_REPORT_ENDPOINT:&'static str = env!("IAS_REPORT_ENDPOINT");
 
 type ChainLightValidation = light_validation::LightValidation::<chain::Runtime>;
+type OpaqueSignedBlock = sp_runtime::generic::SignedBlock<
+    sp_runtime::generic::Block<chain::Header, sp_runtime::OpaqueExtrinsic>>;
+
+/// The oldest spec_version of the chain runtime whose events can be decoded. The extrinsics are
+/// opaque to pRuntime, so only the layout of the events matters. Up to spec_version 9 the
+/// `pallet_phala` events are laid out differently (e.g. `WorkerRegistered` at index 5 instead of
+/// 16), and the layout decoded here is first released in spec_version 13.
+const MIN_SUPPORTED_SPEC_VERSION: u32 = 13;
+/// The newest spec_version of the chain runtime supported, i.e. the one pRuntime is built with
+const MAX_SUPPORTED_SPEC_VERSION: u32 = chain::VERSION.spec_version;
 type EcdhKey = ring::agreement::EphemeralPrivateKey;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -136,29 +169,8 @@ struct LocalState {
     ecdh_public_key: Option<ring::agreement::PublicKey>,
     machine_id: [u8; 16],
     dev_mode: bool,
-}
-
-// TODO: Move the type definitions to a central repo
-
-#[derive(Encode, Decode)]
-struct RuntimeInfo {
-    version: u8,
-    machine_id: [u8; 16],
-    pubkey: [u8; 33],
-    features: Vec<u32>
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[derive(Encode, Decode)]
-pub struct Heartbeat {
-    block_num: u32,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[derive(Encode, Decode)]
-pub struct HeartbeatData {
-    data: Heartbeat,
-    signature: Vec<u8>,
+    challenge: Option<(chain::AccountId, ChallengeResponse)>,  // the stash and the last challenge found in the dispatched blocks, until answered or missed
+    spec_version: Option<u32>,  // the chain runtime of the dispatched blocks, None if not proved since the last upgrade
 }
 
 fn se_to_b64<S>(value: &ChainLightValidation, serializer: S) -> Result<S::Ok, S::Error>
@@ -217,6 +229,8 @@ lazy_static! {
                 ecdh_public_key: None,
                 machine_id: [0; 16],
                 dev_mode: true,
+                challenge: None,
+                spec_version: None,
             }
         )
     };
@@ -628,6 +642,83 @@ pub fn create_attestation_report(data: &[u8], sign_type: sgx_quote_sign_type_t)
     Ok((attn_report, sig, cert))
 }
 
+/// Creates an ECDSA quote of the data with the DCAP Quoting Enclave. Returns the quote and the DER
+/// encoded PCK certificate chain (from the PCK certificate to the root) in its certification data.
+pub fn create_dcap_quote(data: &[u8]) -> Result<(Vec<u8>, Vec<Vec<u8>>), sgx_status_t> {
+    let data_len = data.len();
+    if data_len > SGX_REPORT_DATA_SIZE {
+        panic!("data length over 64 bytes");
+    }
+
+    // (1) get the target info of the Quoting Enclave
+    let mut ti : sgx_target_info_t = sgx_target_info_t::default();
+    let mut rt : sgx_status_t = sgx_status_t::SGX_ERROR_UNEXPECTED;
+    let res = unsafe {
+        ocall_get_dcap_target_info(&mut rt as *mut sgx_status_t,
+                                   &mut ti as *mut sgx_target_info_t)
+    };
+    if res != sgx_status_t::SGX_SUCCESS {
+        return Err(res);
+    }
+    if rt != sgx_status_t::SGX_SUCCESS {
+        return Err(rt);
+    }
+
+    // (2) generate the report targeting the Quoting Enclave
+    let mut report_data: sgx_report_data_t = sgx_report_data_t::default();
+    report_data.d[..data_len].clone_from_slice(data);
+    let rep = rsgx_create_report(&ti, &report_data)?;
+
+    // (3) get the quote signed by the attestation key
+    const RET_QUOTE_BUF_LEN : u32 = 8192;
+    let mut return_quote_buf = vec![0u8; RET_QUOTE_BUF_LEN as usize];
+    let mut quote_len : u32 = 0;
+    let res = unsafe {
+        ocall_get_dcap_quote(&mut rt as *mut sgx_status_t,
+                             &rep as *const sgx_report_t,
+                             return_quote_buf.as_mut_ptr(),
+                             RET_QUOTE_BUF_LEN,
+                             &mut quote_len as *mut u32)
+    };
+    if res != sgx_status_t::SGX_SUCCESS {
+        return Err(res);
+    }
+    if rt != sgx_status_t::SGX_SUCCESS {
+        println!("ocall_get_dcap_quote returned {}", rt);
+        return Err(rt);
+    }
+    return_quote_buf.truncate(quote_len as usize);
+
+    let pck_cert_chain = parse_pck_cert_chain(&return_quote_buf)
+        .ok_or(sgx_status_t::SGX_ERROR_UNEXPECTED)?;
+    Ok((return_quote_buf, pck_cert_chain))
+}
+
+/// Extracts the PEM encoded PCK certificate chain (certification data type 5) of an ECDSA quote
+fn parse_pck_cert_chain(quote: &[u8]) -> Option<Vec<Vec<u8>>> {
+    // header (48) + isv report (384) + signature data len (4) + isv signature (64) +
+    // attestation key (64) + qe report (384) + qe report signature (64)
+    const QE_AUTH_DATA_OFFSET: usize = 1012;
+    let auth_data_len = quote.get(QE_AUTH_DATA_OFFSET..QE_AUTH_DATA_OFFSET + 2)?;
+    let cert_data_offset = QE_AUTH_DATA_OFFSET + 2 + u16::from_le_bytes([auth_data_len[0], auth_data_len[1]]) as usize;
+    let cert_data_header = quote.get(cert_data_offset..cert_data_offset + 6)?;
+    let cert_data_type = u16::from_le_bytes([cert_data_header[0], cert_data_header[1]]);
+    let cert_data_len = u32::from_le_bytes(cert_data_header[2..6].try_into().ok()?) as usize;
+    if cert_data_type != 5 {
+        println!("Unsupported certification data type {}", cert_data_type);
+        return None;
+    }
+    let cert_data = quote.get(cert_data_offset + 6..cert_data_offset + 6 + cert_data_len)?;
+    let pem = str::from_utf8(cert_data).ok()?;
+    pem.split("-----END CERTIFICATE-----")
+        .filter_map(|block| block.split("-----BEGIN CERTIFICATE-----").nth(1))
+        .map(|body| {
+            let body: String = body.chars().filter(|c| !c.is_whitespace()).collect();
+            base64::decode(&body).ok()
+        })
+        .collect()
+}
+
 fn generate_seal_key() -> [u8; 16] {
     let key_request = sgx_key_request_t {
         key_name: SGX_KEYSELECT_SEAL,
@@ -662,6 +753,8 @@ const ACTION_SYNC_HEADER: u8 = 5;
 const ACTION_QUERY: u8 = 6;
 const ACTION_DISPATCH_BLOCK: u8 = 7;
 const ACTION_PING: u8 = 8;
+const ACTION_GET_CONTRACT_KEY: u8 = 9;
+const ACTION_GET_CHALLENGE_RESPONSE: u8 = 10;
 const ACTION_SET: u8 = 21;
 const ACTION_GET: u8 = 22;
 
@@ -669,7 +762,11 @@ const ACTION_GET: u8 = 22;
 struct InitRuntimeReq {
     skip_ra: bool,
     bridge_genesis_info_b64: String,
-    debug_set_key: Option<String>
+    debug_set_key: Option<String>,
+    /// "DCAP" for an ECDSA quote, otherwise an EPID report from IAS
+    attestation_provider: Option<String>,
+    /// The SCALE encoded number and hash of a recent block, which the DCAP quote is bound to
+    dcap_anchor_b64: Option<String>,
 }
 #[derive(Serialize, Deserialize, Debug)]
 pub struct InitRuntimeResp {
@@ -681,8 +778,15 @@ pub struct InitRuntimeResp {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct InitRespAttestation {
   pub version: i32,
+  /// "SGX" for an EPID report from IAS, or "DCAP" for an ECDSA quote
   pub provider: String,
-  pub payload: AttestationReport,
+  pub payload: AttestationPayload,
+}
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum AttestationPayload {
+  Ias(AttestationReport),
+  Dcap(DcapQuote),
 }
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AttestationReport {
@@ -691,6 +795,13 @@ pub struct AttestationReport {
   pub signing_cert: String,
 }
 #[derive(Serialize, Deserialize, Debug)]
+pub struct DcapQuote {
+  /// Base64 encoded quote
+  pub quote: String,
+  /// Base64 encoded DER certificates, from the PCK certificate to the root
+  pub pck_cert_chain: Vec<String>,
+}
+#[derive(Serialize, Deserialize, Debug)]
 struct TestReq {
     test_parse_block: Option<bool>,
     test_bridge: Option<bool>,
@@ -711,19 +822,14 @@ struct TestEcdhParam {
     message_b64: Option<String>,
 }
 
-#[derive(Encode, Decode, Debug, Clone)]
-struct HeaderToSync {
-    header: chain::Header,
-    justification: Option<Vec<u8>>,
-}
+type HeaderToSync = phala_types::HeaderToSync<chain::Header>;
+type GenesisInfo = phala_types::GenesisInfo<chain::Header>;
 
-#[derive(Encode, Decode, Clone, Debug)]
-pub struct BlockWithEvents {
-    pub block: chain::SignedBlock,
-    pub events: Option<Vec<u8>>,
-    pub proof: Option<Vec<Vec<u8>>>,
-    pub key: Option<Vec<u8>>,
-}
+pub type BlockWithEvents = phala_types::BlockWithEvents<chain::SignedBlock>;
+
+/// `BlockWithEvents` with the extrinsics left encoded, to check the runtime of the block before
+/// decoding them
+pub type OpaqueBlockWithEvents = phala_types::BlockWithEvents<OpaqueSignedBlock>;
 
 #[no_mangle]
 pub extern "C" fn ecall_set_state(
@@ -766,6 +872,8 @@ pub extern "C" fn ecall_handle(
                 ACTION_GET => get(payload),
                 ACTION_SET => set(payload),
                 ACTION_PING => ping(payload),
+                ACTION_GET_CONTRACT_KEY => get_contract_key(payload),
+                ACTION_GET_CHALLENGE_RESPONSE => get_challenge_response(payload),
                 _ => unknown()
             }
         }
@@ -1042,6 +1150,25 @@ fn load_states(input: &Map<String, Value>) -> Result<Value, Value> {
     Ok(json!({}))
 }
 
+const BENCHMARK_DURATION_MS: u128 = 100;
+
+/// Number of 1 KiB blocks hashed with blake2-256 per millisecond on a single core. The duration
+/// is measured by the untrusted clock.
+fn run_benchmark() -> u32 {
+    use crate::std::untrusted::time::InstantEx;
+
+    let data = [0u8; 1024];
+    let start = std::time::Instant::now();
+    let mut iterations: u128 = 0;
+    while start.elapsed().as_millis() < BENCHMARK_DURATION_MS {
+        for _ in 0..100 {
+            let _ = blake2_256(&data);
+        }
+        iterations += 100;
+    }
+    (iterations / start.elapsed().as_millis().max(1)) as u32
+}
+
 fn init_runtime(input: InitRuntimeReq) -> Result<Value, Value> {
     // TODO: Guard only initialize once
     let mut local_state = LOCAL_STATE.lock().unwrap();
@@ -1094,12 +1221,28 @@ fn init_runtime(input: InitRuntimeReq) -> Result<Value, Value> {
         }
     }
 
-    // Build RuntimeInfo
-    let runtime_info = RuntimeInfo {
-        version: 1,
+    // The memory and EPC size are reported by the untrusted host
+    let mut memory_mb: u32 = 0;
+    let mut epc_mb: u32 = 0;
+    let mut retval = sgx_status_t::SGX_SUCCESS;
+    let result = unsafe {
+        ocall_get_machine_info(&mut retval, &mut memory_mb, &mut epc_mb)
+    };
+    if result != sgx_status_t::SGX_SUCCESS || retval != sgx_status_t::SGX_SUCCESS {
+        println!("Failed to get the machine info");
+    }
+    println!("Memory: {} MB, EPC: {} MB", memory_mb, epc_mb);
+
+    let benchmark = run_benchmark();
+    println!("Benchmark: {}", benchmark);
+
+    // Build PRuntimeInfo
+    let runtime_info = PRuntimeInfo {
+        version: 3,
         machine_id: local_state.machine_id.clone(),
         pubkey: ecdsa_serialized_pk,
-        features: vec![cpu_core_num, cpu_feature_level],
+        features: vec![cpu_core_num, cpu_feature_level, memory_mb, epc_mb, benchmark],
+        ecdh_pubkey: ecdh_pk.as_ref().to_vec(),
     };
     let encoded_runtime_info = runtime_info.encode();
     let runtime_info_hash = sp_core::hashing::blake2_512(&encoded_runtime_info);
@@ -1109,7 +1252,37 @@ fn init_runtime(input: InitRuntimeReq) -> Result<Value, Value> {
 
     // Produce remote attestation report
     let mut attestation: Option<InitRespAttestation> = None;
-    if !input.skip_ra {
+    if !input.skip_ra && input.attestation_provider.as_ref().map(String::as_str) == Some("DCAP") {
+        // The quote carries no timestamp, so its freshness is proved by a recent block instead
+        let raw_anchor = input.dcap_anchor_b64.as_ref()
+            .and_then(|anchor_b64| base64::decode(anchor_b64).ok())
+            .ok_or_else(|| error_msg("Bad dcap_anchor_b64"))?;
+        let (block_number, block_hash) = <(chain::BlockNumber, chain::Hash)>::decode(&mut raw_anchor.as_slice())
+            .map_err(|_| error_msg("Can't decode dcap_anchor_b64"))?;
+        let mut bound_data = encoded_runtime_info.clone();
+        bound_data.extend(block_hash.encode());
+        let report_data = DcapReportData {
+            runtime_info_hash: sp_core::hashing::blake2_256(&bound_data),
+            block_number,
+            ..Default::default()
+        };
+        let (quote, pck_cert_chain) = match create_dcap_quote(&report_data.encode()) {
+            Ok(r) => r,
+            Err(e) => {
+                println!("Error in create_dcap_quote: {:?}", e);
+                return Err(json!({"message": "Error while generating the DCAP quote"}))
+            }
+        };
+
+        attestation = Some(InitRespAttestation {
+            version: 1,
+            provider: "DCAP".to_string(),
+            payload: AttestationPayload::Dcap(DcapQuote {
+                quote: base64::encode(&quote),
+                pck_cert_chain: pck_cert_chain.iter().map(base64::encode).collect(),
+            })
+        });
+    } else if !input.skip_ra {
         let (attn_report, sig, cert) = match create_attestation_report(&runtime_info_hash, sgx_quote_sign_type_t::SGX_LINKABLE_SIGNATURE) {
             Ok(r) => r,
             Err(e) => {
@@ -1121,34 +1294,39 @@ fn init_runtime(input: InitRuntimeReq) -> Result<Value, Value> {
         attestation = Some(InitRespAttestation {
             version: 1,
             provider: "SGX".to_string(),
-            payload: AttestationReport {
+            payload: AttestationPayload::Ias(AttestationReport {
                 report: attn_report,
                 signature: sig,
                 signing_cert: cert,
-            }
+            })
         });
     }
 
     // Initialize bridge
     let raw_genesis = base64::decode(&input.bridge_genesis_info_b64)
         .expect("Bad bridge_genesis_info_b64");
-    let genesis = light_validation::BridgeInitInfo::<chain::Runtime>
-        ::decode(&mut raw_genesis.as_slice())
+    let genesis = GenesisInfo::decode(&mut raw_genesis.as_slice())
         .expect("Can't decode bridge_genesis_info_b64");
 
     let mut state = STATE.lock().unwrap();
     let bridge_id = state.light_client.initialize_bridge(
-        genesis.block_header,
-        genesis.validator_set,
-        genesis.validator_set_proof)
+        genesis.header,
+        genesis.validators,
+        genesis.proof)
         .expect("Bridge initialize failed");
     state.main_bridge = bridge_id;
-    let ecdsa_seed = local_state.private_key.serialize();
-    let id_pair = sp_core::ecdsa::Pair::from_seed_slice(&ecdsa_seed)
+    // The dev chain is initialized with the identity key as the contract keys
+    let contract_sk = if local_state.dev_mode {
+        (*local_state.private_key).clone()
+    } else {
+        derive_contract_key(&local_state.private_key, BALANCES)
+    };
+    let contract_pair = sp_core::ecdsa::Pair::from_seed_slice(&contract_sk.serialize())
         .expect("Unexpected ecdsa key error in init_runtime");
-    state.contract2 = contracts::balances::Balances::new(Some(id_pair));
+    state.contract2 = contracts::balances::Balances::new(Some(contract_pair));
     local_state.headernum = 1;
     local_state.blocknum = 1;
+    local_state.spec_version = None;
 
     let resp = InitRuntimeResp {
         encoded_runtime_info,
@@ -1262,17 +1440,16 @@ fn test_bridge() {
     // 1. load genesis
     let raw_genesis = base64::decode("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAh9rd6Uku4dTja+JQVMLsOZ5GtS4nU0cdpuvgchlapeMDFwoudZe3t+PYTAU5HROaYrFX54eG2MCC8p3PTBETFAAIiNw0F9UFjsS0UD4MEuoaCom+IA/piSJCPUM0AU+msO4BAAAAAAAAANF8LXgj6/Jg/ROPLX4n0RTAFF2Wi1/1AGEl8kFPra5pAQAAAAAAAAAMoQKALhCA33I0FGiDoLZ6HBWl1uCIt+sLgUbPlfMJqUk/gukhEt6AviHkl5KFGndUmA+ClBT2kPSvmBOvZTWowWjNYfynHU6AOFjSvKwU3s/vvRg7QOrJeehLgo9nGfN91yHXkHcWLkuAUJegqkIzp2A6LPkZouRRsKgiY4Wu92V8JXrn3aSXrw2AXDYZ0c8CICTMvasQ+rEpErmfEmg+BzH19s/zJX4LP8adAWRyYW5kcGFfYXV0aG9yaXRpZXNJAQEIiNw0F9UFjsS0UD4MEuoaCom+IA/piSJCPUM0AU+msO4BAAAAAAAAANF8LXgj6/Jg/ROPLX4n0RTAFF2Wi1/1AGEl8kFPra5pAQAAAAAAAABtAYKmqACASqIhjIQMli+MpltqIZlc2FVhXCd/m9F6k9Q5u13xU3JQXHh0cmluc2ljX2luZGV4EAAAAACAc0yvcsUiYcma5kSPZKxrMxbyDufisOfMmIsX1bDxfHc=")
         .expect("Bad bridge_genesis_innfo_b64");
-    let genesis = light_validation::BridgeInitInfo::<chain::Runtime>
-    ::decode(&mut raw_genesis.as_slice())
+    let genesis = GenesisInfo::decode(&mut raw_genesis.as_slice())
         .expect("Can't decode bridge_genesis_info_b64");
 
     println!("bridge_genesis_info_b64: {:?}", genesis);
 
     let mut state = STATE.lock().unwrap();
     let id = state.light_client.initialize_bridge(
-        genesis.block_header,
-        genesis.validator_set,
-        genesis.validator_set_proof)
+        genesis.header,
+        genesis.validators,
+        genesis.proof)
         .expect("Init bridge failed; qed");
 
     // 2. import a few blocks
@@ -1483,8 +1660,8 @@ fn dispatch_block(input: DispatchBlockReq) -> Result<Value, Value> {
         .collect();
     let blocks_data = parsed_data
         .map_err(|_| error_msg("Failed to parse base64 block"))?;
-    // Parse data to blocks
-    let parsed_blocks: Result<Vec<BlockWithEvents>, _> = blocks_data
+    // Parse data to blocks, leaving the extrinsics to decode after checking the runtime version
+    let parsed_blocks: Result<Vec<OpaqueBlockWithEvents>, _> = blocks_data
         .iter()
         .map(|d| Decode::decode(&mut &d[..]))
         .collect();
@@ -1511,11 +1688,14 @@ fn dispatch_block(input: DispatchBlockReq) -> Result<Value, Value> {
     let ecdh_privkey = ecdh::clone_key(
         local_state.ecdh_private_key.as_ref().expect("ECDH not initizlied"));
     let mut last_block = 0;
-    for block in blocks.iter() {
+    for (opaque_block, data) in blocks.iter().zip(blocks_data.iter()) {
+        check_runtime_version(opaque_block, &mut local_state)?;
+        let block: BlockWithEvents = Decode::decode(&mut &data[..])
+            .map_err(|_| error_msg("Invalid block"))?;
         dispatch(&block, &ecdh_privkey);
 
         if block.events.is_some() {
-            parse_events(&block)?;
+            parse_events(&block, &mut local_state)?;
         }
 
         last_block = block.block.block.header.number;
@@ -1528,6 +1708,40 @@ fn dispatch_block(input: DispatchBlockReq) -> Result<Value, Value> {
     }))
 }
 
+/// Updates the spec_version of the chain runtime from the `System::LastRuntimeUpgrade` proved
+/// along with the events, and refuses the block if it's produced by an unsupported runtime
+fn check_runtime_version(block: &OpaqueBlockWithEvents, local_state: &mut LocalState) -> Result<(), Value> {
+    let header = &block.block.block.header;
+    if let Some(proof) = &block.proof {
+        let mut key = twox_128(b"System").to_vec();
+        key.extend(&twox_128(b"LastRuntimeUpgrade"));
+        let state = STATE.lock().unwrap();
+        // The proofs not covering the key are ignored
+        let value = state.light_client
+            .read_proved_storage(&header.state_root, proof.clone(), &key)
+            .unwrap_or(None);
+        if let Some(value) = value {
+            let info = system::LastRuntimeUpgradeInfo::decode(&mut value.as_slice())
+                .map_err(|_| error_msg("Invalid LastRuntimeUpgrade"))?;
+            local_state.spec_version = Some(info.spec_version.0);
+        }
+    }
+    ensure_supported_spec_version(local_state.spec_version, header.number)
+}
+
+fn ensure_supported_spec_version(spec_version: Option<u32>, block_number: chain::BlockNumber) -> Result<(), Value> {
+    match spec_version {
+        Some(spec_version) if spec_version >= MIN_SUPPORTED_SPEC_VERSION
+            && spec_version <= MAX_SUPPORTED_SPEC_VERSION => Ok(()),
+        Some(spec_version) => Err(error_msg(&format!(
+            "Unsupported runtime spec_version {} at block {}, supported: {}..={}",
+            spec_version, block_number, MIN_SUPPORTED_SPEC_VERSION, MAX_SUPPORTED_SPEC_VERSION))),
+        None => Err(error_msg(&format!(
+            "Unknown runtime spec_version at block {}, LastRuntimeUpgrade not proved",
+            block_number))),
+    }
+}
+
 fn parse_authority_set_change(data_b64: String) -> Result<AuthoritySetChange, Value> {
     let data = base64::decode(&data_b64)
         .map_err(|_| error_msg("cannot decode authority_set_change_b64"))?;
@@ -1535,7 +1749,7 @@ fn parse_authority_set_change(data_b64: String) -> Result<AuthoritySetChange, Va
         .map_err(|_| error_msg("cannot decode authority_set_change"))
 }
 
-fn parse_events(block_with_events: &BlockWithEvents) -> Result<(), Value> {
+fn parse_events(block_with_events: &BlockWithEvents, local_state: &mut LocalState) -> Result<(), Value> {
     let mut state = STATE.lock().unwrap();
     let missing_field = error_msg("Missing field");
     let events = block_with_events.clone().events.ok_or(missing_field.clone())?;
@@ -1547,8 +1761,32 @@ fn parse_events(block_with_events: &BlockWithEvents) -> Result<(), Value> {
     let events = Vec::<EventRecord<chain::Event, Hash>>::decode(&mut events.as_slice());
     if let Ok(evts) = events {
         for evt in &evts {
+            // The next block is produced by the new runtime, unknown until proved
+            if let chain::Event::frame_system(system::RawEvent::CodeUpdated) = &evt.event {
+                println!("Runtime upgraded at block {}", block_with_events.block.block.header.number);
+                local_state.spec_version = None;
+            }
             if let chain::Event::pallet_phala(pe) = &evt.event {
                 println!("pallet_phala event: {:?}", pe);
+                // Record the challenge to this worker with the block it's found in, which proves
+                // the block is synced
+                match pe {
+                    chain::pallet_phala::RawEvent::ChallengeIssued(stash, machine_id, nonce)
+                        if machine_id[..] == local_state.machine_id[..] => {
+                        local_state.challenge = Some((stash.clone(), ChallengeResponse {
+                            nonce: *nonce,
+                            block_num: block_with_events.block.block.header.number,
+                        }));
+                    },
+                    // The response can be produced again until the chain settles the challenge
+                    chain::pallet_phala::RawEvent::ChallengeAnswered(stash) |
+                    chain::pallet_phala::RawEvent::ChallengeMissed(stash) => {
+                        if local_state.challenge.as_ref().map_or(false, |(challenged, _)| challenged == stash) {
+                            local_state.challenge = None;
+                        }
+                    },
+                    _ => {},
+                }
                 state.contract2.handle_event(evt.event.clone());
             }
         }
@@ -1581,6 +1819,8 @@ fn get_info(_input: &Map<String, Value>) -> Result<Value, Value> {
         "blocknum": blocknum,
         "machine_id": machine_id,
         "dev_mode": local_state.dev_mode,
+        "spec_version": local_state.spec_version,
+        "supported_spec_versions": [MIN_SUPPORTED_SPEC_VERSION, MAX_SUPPORTED_SPEC_VERSION],
     }))
 }
 
@@ -1765,9 +2005,109 @@ fn ping(_input: &Map<String, Value>) -> Result<Value, Value> {
     }))
 }
 
+/// Derive the key of a contract from the identity key of the worker
+fn derive_contract_key(identity_sk: &SecretKey, contract_id: ContractId) -> SecretKey {
+    let mut seed = b"phala_contract_key".to_vec();
+    seed.extend_from_slice(&identity_sk.serialize());
+    seed.extend_from_slice(&contract_id.to_le_bytes());
+    SecretKey::parse(&blake2_256(&seed)).expect("Invalid derived contract key")
+}
+
+/// Produce a contract key rotation signed by the identity key, to be submitted to the chain
+/// by `rotate_contract_key`
+fn get_contract_key(input: &Map<String, Value>) -> Result<Value, Value> {
+    let local_state = LOCAL_STATE.lock().unwrap();
+    if !local_state.initialized {
+        return Err(json!({"status": "not_initialized", "encoded_data": ""}))
+    }
+    let contract_id = input.get("contract_id").and_then(|v| v.as_u64())
+        .ok_or_else(|| error_msg("Missing contract_id"))? as ContractId;
+    let sequence = input.get("sequence").and_then(|v| v.as_u64())
+        .ok_or_else(|| error_msg("Missing sequence"))?;
+
+    let contract_sk = if local_state.dev_mode {
+        (*local_state.private_key).clone()
+    } else {
+        derive_contract_key(&local_state.private_key, contract_id)
+    };
+    let data = ContractKeyRotation {
+        contract_id,
+        pubkey: PublicKey::from_secret_key(&contract_sk).serialize_compressed().to_vec(),
+        sequence,
+    };
+
+    let msg_hash = blake2_256(&Encode::encode(&data));
+    let message = secp256k1::Message::parse(&msg_hash);
+    let (signature, recovery_id) = secp256k1::sign(&message, &local_state.private_key);
+    let mut raw_signature = signature.serialize().to_vec();
+    raw_signature.push(recovery_id.serialize());
+
+    let rotation = SignedContractKeyRotation {
+        data,
+        signature: raw_signature,
+    };
+    let data_b64 = base64::encode(&rotation.encode());
+
+    Ok(json!({
+        "status": "ok",
+        "encoded_data": data_b64.to_string()
+    }))
+}
+
+/// Produce the response to the last challenge issued to this worker, signed by the identity key,
+/// to be submitted to the chain by `respond_challenge`. The challenge is kept until a dispatched
+/// block shows it answered or missed, so that the response can be submitted again if lost.
+fn get_challenge_response(_input: &Map<String, Value>) -> Result<Value, Value> {
+    let local_state = LOCAL_STATE.lock().unwrap();
+    if !local_state.initialized {
+        return Err(json!({"status": "not_initialized", "encoded_data": ""}))
+    }
+    let data = match &local_state.challenge {
+        Some((_, data)) => data.clone(),
+        None => return Err(json!({"status": "no_challenge", "encoded_data": ""})),
+    };
+
+    let msg_hash = blake2_256(&Encode::encode(&data));
+    let message = secp256k1::Message::parse(&msg_hash);
+    let (signature, recovery_id) = secp256k1::sign(&message, &local_state.private_key);
+    let mut raw_signature = signature.serialize().to_vec();
+    raw_signature.push(recovery_id.serialize());
+
+    let response = SignedChallengeResponse {
+        data,
+        signature: raw_signature,
+    };
+    let data_b64 = base64::encode(&response.encode());
+
+    Ok(json!({
+        "status": "ok",
+        "encoded_data": data_b64.to_string()
+    }))
+}
 
 lazy_static! {
     static ref GLOBAL_RECEIPT: SgxMutex<ReceiptStore> = {
         SgxMutex::new(ReceiptStore::new())
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ensure_supported_spec_version() {
+        assert!(ensure_supported_spec_version(Some(MIN_SUPPORTED_SPEC_VERSION), 1).is_ok());
+        assert!(ensure_supported_spec_version(Some(MAX_SUPPORTED_SPEC_VERSION), 1).is_ok());
+        // The runtime before the events were laid out as decoded here
+        assert_eq!(
+            ensure_supported_spec_version(Some(9), 1),
+            Err(error_msg(&format!("Unsupported runtime spec_version 9 at block 1, supported: {}..={}",
+                MIN_SUPPORTED_SPEC_VERSION, MAX_SUPPORTED_SPEC_VERSION)))
+        );
+        // Upgraded to a runtime newer than pRuntime
+        assert!(ensure_supported_spec_version(Some(MAX_SUPPORTED_SPEC_VERSION + 1), 1).is_err());
+        // Not proved since the last upgrade
+        assert!(ensure_supported_spec_version(None, 1).is_err());
+    }
+}
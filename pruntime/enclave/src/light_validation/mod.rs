@@ -58,13 +58,6 @@ use sp_runtime::traits::{Block as BlockT, Header, NumberFor};
 
 pub use types::{AuthoritySet, AuthoritySetChange};
 
-#[derive(Encode, Decode, Clone, PartialEq)]
-pub struct BridgeInitInfo<T: Trait> {
-	pub block_header: T::Header,
-	pub validator_set: AuthorityList,
-	pub validator_set_proof: StorageProof,
-}
-
 #[derive(Encode, Decode, Clone, PartialEq)]
 pub struct BridgeInfo<T: Trait> {
 	last_finalized_block_header: T::Header,
@@ -231,6 +224,21 @@ impl<T: Trait> LightValidation<T>
 			Err(Error::EventsMismatch)
 		}
 	}
+
+	/// Reads a storage value from a proof against the state root of a block. Returns `None` if the
+	/// value doesn't exist, or an error if the proof doesn't cover the key.
+	pub fn read_proved_storage(
+		&self,
+		state_root: &T::Hash,
+		proof: StorageProof,
+		key: &[u8],
+	) -> Result<Option<Vec<u8>>, Error> {
+		let checker = <StorageProofChecker<T::Hashing>>::new(
+			*state_root,
+			proof
+		)?;
+		Ok(checker.read_value(key)?)
+	}
 }
 
 #[derive(Debug)]
@@ -369,10 +377,3 @@ impl<T: Trait> fmt::Debug for BridgeInfo<T> {
 			self.last_finalized_block_header, self.current_set.authority_set, self.current_set.set_id)
 	}
 }
-
-impl<T: Trait> fmt::Debug for BridgeInitInfo<T> {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "BridgeInfo {{ block_header: {:?}, validator_set: {:?}, validator_set_proof: {:?} }}",
-			self.block_header, self.validator_set, self.validator_set_proof)
-	}
-}
@@ -120,10 +120,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	// and set impl_version to 0. If only runtime
 	// implementation changes and behavior does not, then leave spec_version as
 	// is and increment impl_version.
-	spec_version: 9,
+	spec_version: 13,
 	impl_version: 0,
 	apis: RUNTIME_API_VERSIONS,
-	transaction_version: 1,
+	transaction_version: 2,
 };
 
 /// Native version.
@@ -451,6 +451,8 @@ parameter_types! {
 	pub const MaxIterations: u32 = 10;
 	// 0.05%. The higher the value, the more strict solution acceptance becomes.
 	pub MinSolutionScoreBump: Perbill = Perbill::from_rational_approximation(5u32, 10_000);
+	// 0.1% more self-stake for each point of the worker score
+	pub const MinerScoreBonus: Perbill = Perbill::from_parts(1_000_000);
 	pub OffchainSolutionWeightLimit: Weight = MaximumExtrinsicWeight::get()
 		.saturating_sub(BlockExecutionWeight::get())
 		.saturating_sub(ExtrinsicBaseWeight::get());
@@ -480,6 +482,7 @@ impl pallet_staking::Trait for Runtime {
 	type Call = Call;
 	type MaxIterations = MaxIterations;
 	type MinSolutionScoreBump = MinSolutionScoreBump;
+	type MinerScoreBonus = MinerScoreBonus;
 	type MaxNominatorRewardedPerValidator = MaxNominatorRewardedPerValidator;
 	type UnsignedPriority = StakingUnsignedPriority;
 	// The unsigned solution weight targeted by the OCW. We set it to the maximum possible value of
@@ -752,6 +755,7 @@ impl<LocalCall> frame_system::offchain::CreateSignedTransaction<LocalCall> for R
 			frame_system::CheckNonce::<Runtime>::from(nonce),
 			frame_system::CheckWeight::<Runtime>::new(),
 			pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(tip),
+			pallet_phala::CheckCallQuota::<Runtime>::new(),
 		);
 		let raw_payload = SignedPayload::new(call, extra)
 			.map_err(|e| {
@@ -914,10 +918,60 @@ impl pallet_vesting::Trait for Runtime {
 	type WeightInfo = weights::pallet_vesting::WeightInfo<Runtime>;
 }
 
+parameter_types! {
+	pub const MiningRewardPerBlock: Balance = 1 * DOLLARS;
+	pub const MiningRoundInterval: BlockNumber = 1 * HOURS;
+	pub const OfflineThreshold: BlockNumber = 10 * MINUTES;
+	pub const HeartbeatMaxLag: BlockNumber = 5 * MINUTES;
+	pub const ChallengesPerRound: u32 = 10;
+	pub const ChallengeDeadline: BlockNumber = 10 * MINUTES;
+	pub const MaxCommandSize: u32 = 64 * 1024;
+	pub const CommandByteFee: Balance = 10 * MILLICENTS;
+	pub const ContractKeyGracePeriod: BlockNumber = 1 * HOURS;
+	pub const EgressSignatureTimeout: BlockNumber = 1 * HOURS;
+	pub const LeaveCooldown: BlockNumber = 7 * DAYS;
+	pub const MaxReportAge: Moment = HOURS as Moment * MILLISECS_PER_BLOCK;
+	pub const ReattestationInterval: Moment = 7 * DAYS as Moment * MILLISECS_PER_BLOCK;
+	pub const WorkerBond: Balance = 100 * DOLLARS;
+	pub const BondUnbondingPeriod: BlockNumber = 28 * DAYS;
+	pub const CallQuotaWindow: BlockNumber = 1 * MINUTES;
+	pub const MaxCallsPerWindow: u32 = 10;
+}
+
 impl pallet_phala::Trait for Runtime {
 	type Event = Event;
 	type TEECurrency = Balances;
 	type UnixTime = Timestamp;
+	type GovernanceOrigin = EnsureOneOf<
+		AccountId,
+		EnsureRoot<AccountId>,
+		pallet_collective::EnsureProportionAtLeast<_1, _2, AccountId, CouncilCollective>
+	>;
+	type RewardPerBlock = MiningRewardPerBlock;
+	type RoundInterval = MiningRoundInterval;
+	type OfflineThreshold = OfflineThreshold;
+	type HeartbeatMaxLag = HeartbeatMaxLag;
+	type Randomness = RandomnessCollectiveFlip;
+	type ChallengesPerRound = ChallengesPerRound;
+	type ChallengeDeadline = ChallengeDeadline;
+	type MaxCommandSize = MaxCommandSize;
+	type CommandByteFee = CommandByteFee;
+	type CommandFee = Treasury;
+	type ContractKeyGracePeriod = ContractKeyGracePeriod;
+	type EgressSignatureTimeout = EgressSignatureTimeout;
+	type LeaveCooldown = LeaveCooldown;
+	type OnStashRemoved = Staking;
+	type MaxReportAge = MaxReportAge;
+	type ReattestationInterval = ReattestationInterval;
+	type WorkerBond = WorkerBond;
+	type BondUnbondingPeriod = BondUnbondingPeriod;
+	type Slash = Treasury;
+	type ValidatorIdentification = pallet_session::historical::IdentificationTuple<Self>;
+	type IdentifyValidator = Staking;
+	type ReportOffence = Offences;
+	type CallQuotaWindow = CallQuotaWindow;
+	type MaxCallsPerWindow = MaxCallsPerWindow;
+	type WeightInfo = weights::pallet_phala::WeightInfo<Runtime>;
 }
 
 construct_runtime!(
@@ -985,6 +1039,7 @@ pub type SignedExtra = (
 	frame_system::CheckNonce<Runtime>,
 	frame_system::CheckWeight<Runtime>,
 	pallet_transaction_payment::ChargeTransactionPayment<Runtime>,
+	pallet_phala::CheckCallQuota<Runtime>,
 );
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
@@ -1187,6 +1242,53 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl pallet_phala_rpc_runtime_api::PhalaApi<
+		Block,
+		AccountId,
+		BlockNumber,
+		Balance,
+	> for Runtime {
+		fn worker_info(stash: AccountId) -> Option<pallet_phala::types::WorkerInfo> {
+			PhalaModule::worker_info(&stash)
+		}
+
+		fn worker_info_by_machine_id(
+			machine_id: Vec<u8>,
+		) -> Option<(AccountId, pallet_phala::types::WorkerInfo)> {
+			PhalaModule::worker_info_by_machine_id(&machine_id)
+		}
+
+		fn stash_info(stash: AccountId) -> Option<pallet_phala::types::StashInfo<AccountId>> {
+			PhalaModule::stash_info(&stash)
+		}
+
+		fn contract_key(contract_id: u32) -> Option<Vec<u8>> {
+			PhalaModule::contract_pubkey(contract_id)
+		}
+
+		fn contract_ecdh_keys(contract_id: u32) -> Vec<Vec<u8>> {
+			PhalaModule::contract_ecdh_keys(contract_id)
+		}
+
+		fn ingress_sequence(contract_id: u32) -> u64 {
+			PhalaModule::ingress_sequence(contract_id)
+		}
+
+		fn command_number() -> u64 {
+			PhalaModule::command_number().unwrap_or(0)
+		}
+
+		fn contract_command_number(contract_id: u32) -> u64 {
+			PhalaModule::contract_command_number(contract_id)
+		}
+
+		fn mining_status(
+			stash: AccountId,
+		) -> Option<pallet_phala::types::MiningStatus<BlockNumber, Balance>> {
+			PhalaModule::mining_status(&stash)
+		}
+	}
+
 	impl sp_session::SessionKeys<Block> for Runtime {
 		fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
 			SessionKeys::generate(seed)
@@ -1246,6 +1348,7 @@ impl_runtime_apis! {
 			add_benchmark!(params, batches, pallet_indices, Indices);
 			add_benchmark!(params, batches, pallet_multisig, Multisig);
 			add_benchmark!(params, batches, pallet_offences, OffencesBench::<Runtime>);
+			add_benchmark!(params, batches, pallet_phala, PhalaModule);
 			add_benchmark!(params, batches, pallet_proxy, Proxy);
 			add_benchmark!(params, batches, pallet_scheduler, Scheduler);
 			add_benchmark!(params, batches, pallet_session, SessionBench::<Runtime>);
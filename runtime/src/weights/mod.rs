@@ -25,6 +25,7 @@ pub mod pallet_identity;
 pub mod pallet_im_online;
 pub mod pallet_indices;
 pub mod pallet_multisig;
+pub mod pallet_phala;
 pub mod pallet_proxy;
 pub mod pallet_scheduler;
 pub mod pallet_session;